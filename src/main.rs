@@ -1,74 +1,575 @@
 use std::fs;
+use std::io::{self, Read, Write};
 use std::process::Command;
 
-use clap::{Parser};
+use clap::{Parser, Subcommand, ValueEnum};
 use systemd_parser::parser::systemd::{SystemdFile};
+use systemd_parser::parser::systemd::lint::{Finding, Linter, Severity};
+use systemd_parser::parser::systemd::graph::DependencyGraph;
+use systemd_parser::parser::systemd::loader::{Scope, UnitLoader};
+use systemd_parser::parser::systemd::{install, report, transaction};
+use systemd_parser::parser::systemd::install::{Installer, UnitFile, UnitFileState};
 use systemd_parser::parser;
+use systemd_parser::parser::error::{Error, Location};
+
+/// `validate`, `lint`, `simulate` or `fmt --check` found problems in the input, or `unset` found nothing to
+/// remove.
+const EXIT_PROBLEMS: i32 = 1;
+/// The command line is invalid, the same code clap exits with.
+const EXIT_USAGE: i32 = 2;
+/// The input or output file couldn't be read or written, or `ip` couldn't be run.
+const EXIT_IO: i32 = 3;
+/// The input isn't a unit file, JSON or `ip a` output that can be parsed.
+const EXIT_PARSE: i32 = 4;
+
+const EXIT_CODES: &str = "\
+Exit codes:
+  0  success
+  1  validate, lint, simulate or fmt --check found problems, or unset found nothing to remove
+  2  invalid command line
+  3  an input or output could not be read or written
+  4  an input could not be parsed";
 
 fn main() {
 
     let args = Cli::parse();
 
-    if args.ipaddr {
+    match args.command {
+        Commands::Parse { input: Some(input), output, recursive: true } => {
+            if input == "-" {
+                eprintln!("error: parse --recursive reads a directory, not stdin");
+                std::process::exit(EXIT_USAGE)
+            }
+            parse_recursive(&input, &output)
+        }
+        Commands::Parse { input, output, .. } => {
+            let input = input.unwrap_or_else(|| "-".to_string());
+            let parsed = parse_input(&input).unwrap_or_else(|e| fail(e, &input));
+            let json_data = serde_json::to_string_pretty(&parsed).unwrap_or_default();
+            write_output(&output, &json_data);
+        }
+        Commands::Render { input, output } => {
+            let json_file = read_input(&input).unwrap_or_else(|e| fail(e, &input));
+            let mut systemd_file: SystemdFile = serde_json::from_str(json_file.as_str())
+                .unwrap_or_else(|e| fail(json_error(&json_file, e), &input));
+            if let Err(e) = systemd_file.apply_typed_edits() {
+                fail(e, &input);
+            }
+            write_output(&output, &systemd_file.to_string());
+        }
+        Commands::Validate { inputs, format } => validate(&inputs, format),
+        Commands::Lint { inputs, disable, enable, list_rules, format } => {
+            let mut linter = Linter::new();
+            let toggled = disable.iter().try_for_each(|id| linter.disable(id))
+                .and_then(|_| enable.iter().try_for_each(|id| linter.enable(id)));
+            if let Err(message) = toggled {
+                eprintln!("error: {message}");
+                std::process::exit(EXIT_USAGE)
+            }
+
+            if list_rules {
+                for rule in linter.rules() {
+                    println!("{:<24} {:<8} {}", rule.id, rule.severity, rule.summary);
+                }
+                return;
+            }
+            lint(&linter, &inputs, format);
+        }
+        Commands::Fmt { input, output, check } => {
+            let mut unit = parse_input(&input).unwrap_or_else(|e| fail(e, &input));
+            let original = unit.to_string();
+            unit.format();
+            let formatted = unit.to_string();
+
+            if check {
+                if formatted != original {
+                    eprintln!("{input} is not formatted");
+                    std::process::exit(EXIT_PROBLEMS)
+                }
+                return;
+            }
+            // without an output file the input is formatted in place, unless it was read from stdin
+            match output.as_deref() {
+                None if input != "-" => write_output(&input, &formatted),
+                output => write_output(output.unwrap_or("-"), &formatted),
+            }
+        }
+        Commands::Ipaddr { input } => {
+            let ip_a = match input.as_deref() {
+                Some(input) => read_input(input).unwrap_or_else(|e| fail(e, input)),
+                None => {
+                    let cmd_out = Command::new("/usr/sbin/ip").arg("a").output()
+                        .unwrap_or_else(|e| fail(e.into(), "/usr/sbin/ip"));
+                    String::from_utf8_lossy(&cmd_out.stdout).into_owned()
+                }
+            };
+            match parser::ipaddr::parse(ip_a.as_str()) {
+                Ok(_) => println!("Successful parse"),
+                Err(e) => fail(e, input.as_deref().unwrap_or("ip a")),
+            }
+        }
+        Commands::Graph { root, format, from, only, output } => {
+            let graph = DependencyGraph::from_loader(&UnitLoader::new(&root, Scope::System));
+            for e in graph.errors() {
+                eprintln!("{}", e.render());
+            }
+
+            let graph = match from.as_deref() {
+                Some(unit) if graph.state(unit).is_none() => {
+                    eprintln!("error: {unit} isn't on the search path below {root}");
+                    std::process::exit(EXIT_USAGE)
+                }
+                Some(unit) => graph.reachable_from(unit),
+                None => graph,
+            };
+            let graph = match only {
+                Some(EdgeKinds::Requirements) => graph.filter_edges(|e| !e.kind.is_ordering()),
+                Some(EdgeKinds::Ordering) => graph.filter_edges(|e| e.kind.is_ordering()),
+                None => graph,
+            };
 
-        let cmd_out = Command::new("/usr/sbin/ip").arg("a").output().expect("command out");
-        let parse_result = parser::ipaddr::parse(String::from_utf8(cmd_out.stdout).unwrap().as_str());
-        match parse_result {
-            Ok(_) => println!("Successful parse"),
-            Err(e) => eprintln!("failed to parse: {e}")
+            let drawn = match format {
+                GraphFormat::Dot => graph.to_dot(),
+                GraphFormat::Mermaid => graph.to_mermaid(),
+            };
+            write_output(&output, &drawn);
+        }
+        Commands::Simulate { target, root, json } => {
+            let transaction = transaction::simulate(&UnitLoader::new(&root, Scope::System), &target);
+            if json {
+                let json_data = serde_json::to_string_pretty(&transaction).unwrap_or_default();
+                write_output("-", &(json_data + "\n"));
+            } else {
+                for (i, step) in transaction.start_order.iter().enumerate() {
+                    println!("{:>3}. {}", i + 1, step.join(" "));
+                }
+                for dropped in &transaction.dropped {
+                    let kind = if dropped.essential { "error" } else { "warning" };
+                    eprintln!("{kind}: {} won't start: {}", dropped.unit, dropped.reason);
+                }
+            }
+
+            if !transaction.succeeded() {
+                eprintln!("{} would fail to start", transaction.target);
+            }
+            if !transaction.dropped.is_empty() {
+                std::process::exit(EXIT_PROBLEMS)
+            }
+        }
+        Commands::ListUnitFiles { root, user, state, json } => {
+            let scope = if user { Scope::User } else { Scope::System };
+            let unit_files: Vec<UnitFile> = install::list_unit_files(&UnitLoader::new(&root, scope)).into_iter()
+                .filter(|unit_file| state.is_empty() || state.contains(&unit_file.state))
+                .collect();
+            if json {
+                let json_data = serde_json::to_string_pretty(&unit_files).unwrap_or_default();
+                write_output("-", &(json_data + "\n"));
+            } else {
+                let width = unit_files.iter().map(|u| u.name.len()).max().unwrap_or_default().max("UNIT FILE".len());
+                let mut out = format!("{:width$} STATE\n", "UNIT FILE");
+                for unit_file in &unit_files {
+                    out.push_str(format!("{:width$} {}\n", unit_file.name, unit_file.state).as_str());
+                }
+                out.push_str(format!("\n{} unit files listed.\n", unit_files.len()).as_str());
+                write_output("-", &out);
+            }
         }
-        return;
+        Commands::Enable { units, root, user } => install(&units, &root, user, true),
+        Commands::Disable { units, root, user } => install(&units, &root, user, false),
+        Commands::Set { file, section, key, value, append } => {
+            let mut unit = parse_input(&file).unwrap_or_else(|e| fail(e, &file));
+            let edited = if append {
+                unit.append(&section, &key, &value)
+            } else {
+                unit.set(&section, &key, &value)
+            };
+            if let Err(e) = edited {
+                fail(e, &file);
+            }
+            write_output(&file, &unit.to_string());
+        }
+        Commands::Unset { file, section, key } => {
+            let mut unit = parse_input(&file).unwrap_or_else(|e| fail(e, &file));
+            // nothing to remove, so the file is left as it is
+            if unit.remove(&section, &key) == 0 {
+                eprintln!("{file} has no {key}= in [{section}]");
+                std::process::exit(EXIT_PROBLEMS)
+            }
+            write_output(&file, &unit.to_string());
+        }
+    }
+}
+
+fn validate(inputs: &[String], format: Format) {
+    let mut findings = Vec::new();
+    for input in inputs {
+        let errors = match input.as_str() {
+            "-" => match read_input(input) {
+                Ok(text) => parser::systemd::validate_str(&text),
+                Err(e) => vec![e],
+            },
+            path => parser::systemd::validate(path),
+        };
+        findings.extend(errors.into_iter().map(|e| Finding::from(with_input_name(e, input))));
     }
 
-    if args.validate {
-        match parser::systemd::parse(args.in_file.as_str()) {
-            Ok(_) => {
-                println!("File has valid syntax");
-                std::process::exit(0)},
-            Err(e) => {
-                eprintln!("An error occurred: \n{e}");
-                std::process::exit(1)
+    let problems = findings.len();
+    report(&findings, format);
+    if problems > 0 {
+        if format == Format::Text {
+            eprintln!("{problems} problem(s) found");
+        }
+        std::process::exit(exit_code(&findings))
+    }
+    if format == Format::Text {
+        println!("File has valid syntax");
+    }
+}
+
+fn lint(linter: &Linter, inputs: &[String], format: Format) {
+    let mut findings = Vec::new();
+    for input in inputs {
+        match parse_input(input) {
+            Ok(unit) => {
+                let name = if input == "-" { "<stdin>" } else { input };
+                findings.extend(linter.lint(&unit).into_iter().map(|f| f.with_file(name)));
             }
+            Err(e) => findings.push(Finding::from(with_input_name(e, input))),
         }
     }
 
-    if args.parse {
-        let unparsed_file = fs::read_to_string(args.in_file).expect("cannot read file");
+    // notes are worth reading but not worth failing over
+    let problems = findings.iter().filter(|f| f.severity > Severity::Note).count();
+    report(&findings, format);
+    if problems > 0 {
+        if format == Format::Text {
+            eprintln!("{problems} problem(s) found");
+        }
+        std::process::exit(exit_code(&findings))
+    }
+}
 
-        let parsed = parser::systemd::parse(unparsed_file.as_str()).unwrap();
+/// Writes `findings` in `format`: text goes to stderr, the machine readable formats to stdout.
+fn report(findings: &[Finding], format: Format) {
+    match format {
+        Format::Text => findings.iter().for_each(|f| eprintln!("{}", f.render())),
+        Format::Sarif => write_output("-", &(report::sarif(findings) + "\n")),
+        Format::Github => write_output("-", &report::github_annotations(findings)),
+    }
+}
 
-        let json_data = serde_json::to_string_pretty(&parsed).unwrap();
+/// EXIT_IO if a file couldn't be read, otherwise EXIT_PROBLEMS.
+fn exit_code(findings: &[Finding]) -> i32 {
+    if findings.iter().any(|f| f.rule == "io") {
+        EXIT_IO
+    } else {
+        EXIT_PROBLEMS
+    }
+}
 
-        fs::write(args.out_file, json_data.to_string()).unwrap();
+/// Writes one line of JSON per unit file under `root` as soon as it is parsed, and exits with EXIT_IO at
+/// the end if a file or directory couldn't be read.
+fn parse_recursive(root: &str, output: &str) {
+    let out: Box<dyn Write> = if output == "-" {
+        Box::new(io::stdout().lock())
     } else {
-        let json_file = fs::read_to_string(args.in_file).expect("cannot read file");
-        let systemd_file: SystemdFile = serde_json::from_str(json_file.as_str()).unwrap();
+        Box::new(fs::File::create(output).unwrap_or_else(|e| fail(e.into(), output)))
+    };
+    let mut out = io::LineWriter::new(out);
+
+    let mut written = Ok(());
+    let mut unreadable = false;
+    let walked = parser::systemd::bulk::parse_tree(root, |unit| {
+        unreadable |= unit.file.is_none();
+        if written.is_ok() {
+            let line = serde_json::to_string(&unit).unwrap_or_default();
+            written = writeln!(out, "{line}");
+        }
+    });
 
-        fs::write(args.out_file, systemd_file.to_string()).unwrap();
+    if let Err(e) = walked {
+        fail(e.into(), root)
     }
+    if let Err(e) = written.and_then(|_| out.flush()) {
+        fail(e.into(), output)
+    }
+    if unreadable {
+        std::process::exit(EXIT_IO)
+    }
+}
 
+/// Enables or disables `units` below `root` and prints the symlinks that were created or removed.
+fn install(units: &[String], root: &str, user: bool, enable: bool) {
+    let (scope, config_dir) = if user {
+        (Scope::User, install::USER_CONFIG_DIR)
+    } else {
+        (Scope::System, install::SYSTEM_CONFIG_DIR)
+    };
+    let loader = UnitLoader::new(root, scope);
+    let installer = Installer::new(&loader, config_dir);
+    let names: Vec<&str> = units.iter().map(String::as_str).collect();
+
+    let changes = if enable { installer.enable(&names) } else { installer.disable(&names) };
+    match changes {
+        Ok(changes) => changes.iter().for_each(|change| println!("{change}")),
+        Err(e) => fail(e, root),
+    }
+}
+
+/// Names the input in messages; stdin is `<stdin>`. Errors that already name a file are left alone.
+fn with_input_name(e: Error, input: &str) -> Error {
+    if e.file().is_some() {
+        return e;
+    }
+    e.with_file(if input == "-" { "<stdin>" } else { input })
+}
+
+/// Reads the file `input`, or stdin if it is `-`.
+fn read_input(input: &str) -> Result<String, Error> {
+    if input == "-" {
+        let mut text = String::new();
+        io::stdin().read_to_string(&mut text)?;
+        return Ok(text);
+    }
+
+    fs::read_to_string(input).map_err(|e| Error::from(e).with_file(input))
+}
+
+/// Parses the unit file `input`, or one read from stdin if it is `-`.
+fn parse_input(input: &str) -> Result<SystemdFile, Error> {
+    match input {
+        "-" => parser::systemd::parse_reader(io::stdin()),
+        path => parser::systemd::parse(path),
+    }
 }
 
+/// Writes `text` to the file `output`, or stdout if it is `-`.
+fn write_output(output: &str, text: &str) {
+    let written = if output == "-" {
+        io::stdout().write_all(text.as_bytes())
+    } else {
+        fs::write(output, text)
+    };
+
+    if let Err(e) = written {
+        fail(e.into(), output)
+    }
+}
+
+fn json_error(json: &str, e: serde_json::Error) -> Error {
+    Error::Syntax {
+        location: Box::new(Location::from_source(json, e.line(), e.column(), 1)),
+        message: e.to_string(),
+    }
+}
+
+/// Reports `e` and exits with the code for its class.
+fn fail(e: Error, input: &str) -> ! {
+    let e = with_input_name(e, input);
+    eprintln!("{}", e.render());
+
+    std::process::exit(match e {
+        Error::Io { .. } => EXIT_IO,
+        _ => EXIT_PARSE,
+    })
+}
+
+/// Parses, checks and edits systemd unit files. Inputs and outputs given as `-` are stdin and stdout.
 #[derive(Parser)]
 #[command(version)]
-#[command(about, long_about = None, arg_required_else_help(true))]
+#[command(about, long_about = None, arg_required_else_help(true), after_help = EXIT_CODES)]
 pub struct Cli {
+    #[command(subcommand)]
+    pub command: Commands,
+}
 
-    #[arg(short, long)]
-    pub parse: bool,
+#[derive(Subcommand)]
+pub enum Commands {
+    /// Parses a unit file and writes it as JSON
+    Parse {
+        /// The unit file to parse, `-` or nothing for stdin. A directory is required with --recursive
+        #[arg(required_if_eq("recursive", "true"))]
+        input: Option<String>,
 
-    // #[arg(short, long)]
-    // pub build: bool,
+        #[arg(short, long, default_value = "-")]
+        output: String,
 
-    #[arg(short, long)]
-    pub validate: bool,
+        /// Parse every unit file and drop-in under the input directory, writing a line of JSON with the
+        /// path, the parsed file and its diagnostics for each
+        #[arg(short, long)]
+        recursive: bool,
+    },
+    /// Writes a unit file from its JSON form
+    Render {
+        #[arg(default_value = "-")]
+        input: String,
 
-    #[arg(short, long)]
-    pub in_file: String,
+        #[arg(short, long, default_value = "-")]
+        output: String,
+    },
+    /// Checks unit files for syntax errors, unknown directives and invalid values
+    Validate {
+        #[arg(default_value = "-")]
+        inputs: Vec<String>,
 
-    #[arg(short, long)]
-    pub out_file: String,
+        #[arg(long, value_enum, default_value_t = Format::Text)]
+        format: Format,
+    },
+    /// Checks unit files against best-practice rules
+    Lint {
+        #[arg(default_value = "-")]
+        inputs: Vec<String>,
+
+        /// Turn off a rule by its ID, can be repeated
+        #[arg(long, value_name = "RULE")]
+        disable: Vec<String>,
+
+        /// Turn a rule back on after --disable, can be repeated
+        #[arg(long, value_name = "RULE")]
+        enable: Vec<String>,
+
+        /// List the enabled rules instead of checking anything
+        #[arg(long)]
+        list_rules: bool,
+
+        #[arg(long, value_enum, default_value_t = Format::Text)]
+        format: Format,
+    },
+    /// Lays a unit file out the usual way, in place unless an output is given
+    Fmt {
+        #[arg(default_value = "-")]
+        input: String,
+
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// Only check whether the file is formatted
+        #[arg(long)]
+        check: bool,
+    },
+    /// Parses the output of `ip a`, running it unless an input is given
+    Ipaddr {
+        input: Option<String>,
+    },
+    /// Draws the dependencies between the units below a root directory
+    Graph {
+        /// Directory standing in for `/`, whose unit search path is read
+        #[arg(long, default_value = "/")]
+        root: String,
+
+        #[arg(long, value_enum, default_value_t = GraphFormat::Dot)]
+        format: GraphFormat,
+
+        /// Only draw the units starting this one pulls in, e.g. multi-user.target
+        #[arg(long, value_name = "UNIT")]
+        from: Option<String>,
+
+        /// Only draw edges of one kind
+        #[arg(long, value_enum)]
+        only: Option<EdgeKinds>,
+
+        #[arg(short, long, default_value = "-")]
+        output: String,
+    },
+    /// Works out which units starting a target at boot would start, in which order, and which would not
+    Simulate {
+        /// The unit to start, e.g. multi-user.target
+        target: String,
+
+        /// Directory standing in for `/`, whose unit search path is read
+        #[arg(long, default_value = "/")]
+        root: String,
+
+        /// Write the transaction as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Lists the unit files on the search path below a root with their state, like systemctl list-unit-files
+    ListUnitFiles {
+        /// Directory standing in for `/`, whose unit search path is read
+        #[arg(long, default_value = "/")]
+        root: String,
+
+        /// List user units instead of system units
+        #[arg(long)]
+        user: bool,
+
+        /// Only list unit files in these states, e.g. enabled,masked
+        #[arg(long, value_delimiter = ',')]
+        state: Vec<UnitFileState>,
+
+        /// Write the unit files as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Creates the symlinks the [Install] sections of units ask for below a root, like systemctl enable
+    Enable {
+        #[arg(required = true)]
+        units: Vec<String>,
+
+        /// Directory standing in for `/`
+        #[arg(long)]
+        root: String,
+
+        /// Enable user units for all users, like systemctl --global
+        #[arg(long)]
+        user: bool,
+    },
+    /// Removes the symlinks of units below a root, like systemctl disable
+    Disable {
+        #[arg(required = true)]
+        units: Vec<String>,
+
+        /// Directory standing in for `/`
+        #[arg(long)]
+        root: String,
+
+        /// Disable user units for all users, like systemctl --global
+        #[arg(long)]
+        user: bool,
+    },
+    /// Sets a directive in place, replacing any earlier assignments of it in the section
+    Set {
+        file: String,
+        section: String,
+        key: String,
+        value: String,
+
+        /// Add the assignment after the existing ones instead of replacing them
+        #[arg(long)]
+        append: bool,
+    },
+    /// Removes every assignment of a directive from a section in place
+    Unset {
+        file: String,
+        section: String,
+        key: String,
+    },
+}
+
+/// How `validate` and `lint` report what they find.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Messages quoting the offending lines, on stderr
+    Text,
+    /// A SARIF 2.1.0 log, for code scanning tools
+    Sarif,
+    /// GitHub Actions workflow commands that annotate the offending lines
+    Github,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum GraphFormat {
+    /// Graphviz DOT
+    Dot,
+    /// A Mermaid flowchart
+    Mermaid,
+}
 
-    #[arg(short='a', long)]
-    pub ipaddr: bool,
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum EdgeKinds {
+    /// Requires=, Wants=, Conflicts= and the like
+    Requirements,
+    /// Before= and After=
+    Ordering,
 }
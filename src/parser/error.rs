@@ -0,0 +1,275 @@
+use serde_derive::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::io;
+use pest::error::{InputLocation, LineColLocation};
+use pest::RuleType;
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+/// Points at the text a diagnostic is about.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all="camelCase")]
+pub struct Location {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// 1-based line number.
+    pub line: usize,
+    /// 1-based column number, counted in characters.
+    pub column: usize,
+    /// Number of characters from `column` that the diagnostic covers.
+    pub length: usize,
+    /// The full source line the diagnostic points into.
+    pub snippet: String,
+}
+
+impl Location {
+    /// Builds a location for `line`/`column` of `source`, taking the snippet from the source text.
+    pub fn from_source(source: &str, line: usize, column: usize, length: usize) -> Self {
+        Location {
+            file: None,
+            line,
+            column,
+            length,
+            snippet: source.lines().nth(line.saturating_sub(1)).unwrap_or_default().to_string(),
+        }
+    }
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.file.as_ref() {
+            Some(file) => write!(f, "{file}:{}:{}", self.line, self.column),
+            None => write!(f, "{}:{}", self.line, self.column),
+        }
+    }
+}
+
+/// Everything that can go wrong while reading, parsing or checking an input. Locations are boxed so that
+/// results carrying an error stay small.
+#[derive(Debug)]
+pub enum Error {
+    /// The input could not be read.
+    Io { file: Option<String>, source: io::Error },
+    /// The input doesn't match the grammar.
+    Syntax { location: Box<Location>, message: String },
+    /// A directive that systemd doesn't know in the section it appears in.
+    UnknownDirective { location: Box<Location>, section: String, key: String },
+    /// A directive that only takes a single value was assigned more than once. Only the last assignment
+    /// has any effect.
+    DuplicateKey { location: Box<Location>, section: String, key: String, first_line: usize },
+    /// A directive whose value can't be interpreted.
+    InvalidValue { location: Box<Location>, key: String, value: String, message: String },
+}
+
+impl Error {
+    /// A stable identifier for the kind of error, meant for machine consumption.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::Io { .. } => "io",
+            Error::Syntax { .. } => "syntax",
+            Error::UnknownDirective { .. } => "unknown-directive",
+            Error::DuplicateKey { .. } => "duplicate-key",
+            Error::InvalidValue { .. } => "invalid-value",
+        }
+    }
+
+    pub fn location(&self) -> Option<&Location> {
+        match self {
+            Error::Io { .. } => None,
+            Error::Syntax { location, .. }
+            | Error::UnknownDirective { location, .. }
+            | Error::DuplicateKey { location, .. }
+            | Error::InvalidValue { location, .. } => Some(location),
+        }
+    }
+
+    pub fn file(&self) -> Option<&str> {
+        match self {
+            Error::Io { file, .. } => file.as_deref(),
+            _ => self.location().and_then(|l| l.file.as_deref()),
+        }
+    }
+
+    /// Records the file the error was found in.
+    pub fn with_file(mut self, name: &str) -> Self {
+        match &mut self {
+            Error::Io { file, .. } => *file = Some(name.to_string()),
+            Error::Syntax { location, .. }
+            | Error::UnknownDirective { location, .. }
+            | Error::DuplicateKey { location, .. }
+            | Error::InvalidValue { location, .. } => location.file = Some(name.to_string()),
+        }
+
+        self
+    }
+
+    /// The error message without any location information.
+    pub fn message(&self) -> String {
+        match self {
+            Error::Io { source, .. } => source.to_string(),
+            Error::Syntax { message, .. } => message.clone(),
+            Error::UnknownDirective { section, key, .. } => {
+                format!("unknown directive `{key}` in section [{section}]")
+            }
+            Error::DuplicateKey { section, key, first_line, .. } => {
+                format!("`{key}` in section [{section}] takes a single value and was already set on line {first_line}")
+            }
+            Error::InvalidValue { key, value, message, .. } => {
+                format!("invalid value `{value}` for `{key}`: {message}")
+            }
+        }
+    }
+
+    /// Renders the error the way rustc does, with the offending line quoted and the problem underlined.
+    pub fn render(&self) -> String {
+        render_diagnostic("error", self.code(), &self.message(), self.location(), self.file())
+    }
+
+    /// Converts a pest error into a syntax error. pest only reports the position it gave up at, so the
+    /// underline starts there and runs to the end of the line.
+    pub fn from_pest<R: RuleType>(e: pest::error::Error<R>) -> Self {
+        let (line, column) = match e.line_col {
+            LineColLocation::Pos(pos) => pos,
+            LineColLocation::Span(start, _) => start,
+        };
+        let snippet = e.line().to_string();
+        // pest measures spans in bytes, the location in characters
+        let length = match e.location {
+            InputLocation::Span((start, end)) => snippet.chars().skip(column - 1)
+                .scan(0, |bytes, c| {
+                    *bytes += c.len_utf8();
+                    Some(*bytes)
+                })
+                .take_while(|bytes| *bytes <= end - start)
+                .count(),
+            InputLocation::Pos(_) => snippet.chars().count().saturating_sub(column - 1),
+        };
+
+        Error::Syntax {
+            location: Box::new(Location { file: None, line, column, length, snippet }),
+            message: e.variant.message().to_string(),
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match (self.location(), self.file()) {
+            (Some(location), _) => write!(f, "{location}: {}", self.message()),
+            (None, Some(file)) => write!(f, "{file}: {}", self.message()),
+            (None, None) => write!(f, "{}", self.message()),
+        }
+    }
+}
+
+/// Renders a diagnostic the way rustc does: `level[code]: message`, then the line `location` points at
+/// with the problem underlined, or just the `file` if there is no line to point at.
+pub(crate) fn render_diagnostic(level: &str, code: &str, message: &str, location: Option<&Location>, file: Option<&str>) -> String {
+    let mut out = format!("{level}[{code}]: {message}\n");
+    // errors about arguments rather than file contents have no line to point at
+    let location = match location {
+        Some(location) if location.line > 0 => location,
+        _ => {
+            if let Some(file) = file {
+                out.push_str(format!(" --> {file}\n").as_str());
+            }
+            return out;
+        }
+    };
+
+    let gutter = " ".repeat(location.line.to_string().len());
+    out.push_str(format!("{gutter}--> {location}\n").as_str());
+    out.push_str(format!("{gutter} |\n").as_str());
+    out.push_str(format!("{} | {}\n", location.line, location.snippet).as_str());
+    out.push_str(format!(
+        "{gutter} | {}{}\n",
+        " ".repeat(location.column.saturating_sub(1)),
+        "^".repeat(location.length.max(1))
+    ).as_str());
+
+    out
+}
+
+/// Serialized as a diagnostic for machine consumption: the `code`, the `message` and the `location`, or
+/// just the `file` for errors without one.
+impl Serialize for Error {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut diagnostic = serializer.serialize_struct("Error", 3)?;
+        diagnostic.serialize_field("code", self.code())?;
+        diagnostic.serialize_field("message", &self.message())?;
+        match self.location() {
+            Some(location) if location.line > 0 => diagnostic.serialize_field("location", location)?,
+            _ => match self.file() {
+                Some(file) => diagnostic.serialize_field("file", file)?,
+                None => diagnostic.skip_field("file")?,
+            },
+        }
+
+        diagnostic.end()
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(source: io::Error) -> Self {
+        Error::Io { file: None, source }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pest::error::ErrorVariant;
+    use pest::Span;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+    enum Rule {}
+
+    fn invalid_restart() -> Error {
+        let source = "[Service]\nRestart=sometimes\n";
+        Error::InvalidValue {
+            location: Box::new(Location::from_source(source, 2, 9, 9)),
+            key: "Restart".to_string(),
+            value: "sometimes".to_string(),
+            message: "expected one of no, always".to_string(),
+        }
+    }
+
+    #[test]
+    fn errors_render_like_rustc() {
+        assert_eq!(invalid_restart().with_file("a.service").render(), "\
+error[invalid-value]: invalid value `sometimes` for `Restart`: expected one of no, always
+ --> a.service:2:9
+  |
+2 | Restart=sometimes
+  |         ^^^^^^^^^
+");
+    }
+
+    #[test]
+    fn errors_display_their_location() {
+        assert_eq!(
+            invalid_restart().to_string(),
+            "2:9: invalid value `sometimes` for `Restart`: expected one of no, always",
+        );
+        let e = Error::from(io::Error::new(io::ErrorKind::NotFound, "gone")).with_file("a.service");
+        assert_eq!((e.code(), e.to_string()), ("io", "a.service: gone".to_string()));
+        assert_eq!(e.render(), "error[io]: gone\n --> a.service\n");
+    }
+
+    #[test]
+    fn pest_spans_are_measured_in_characters() {
+        let input = "Key=ééx\n";
+        let span = Span::new(input, 4, 8).unwrap();
+        let e = pest::error::Error::<Rule>::new_from_span(ErrorVariant::CustomError { message: "bad".to_string() }, span);
+        let location = Error::from_pest(e).location().cloned().unwrap();
+        assert_eq!((location.line, location.column, location.length), (1, 5, 2));
+    }
+}
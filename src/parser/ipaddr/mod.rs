@@ -2,6 +2,7 @@ use std::collections::HashMap;
 use pest::Parser;
 use pest_derive::Parser;
 use serde_derive::{Deserialize, Serialize};
+use crate::parser::error::{Error, Location};
 
 #[derive(Parser)]
 #[grammar = "src/parser/ipaddr/ipaddr_grammar.pest"]
@@ -45,12 +46,12 @@ pub struct  Inet {
     pub lifetime: String
 }
 
-pub fn parse(out: &str) -> Result<(), String> {
+pub fn parse(out: &str) -> Result<(), Error> {
     let ip_addr_out = IpaddrParser::parse(Rule::ip_a_out, out)
-        .map_err(|e| e.to_string())?
+        .map_err(Error::from_pest)?
         .next()
         // this is documented as never panics but i don't want to use expect or unwrap
-        .ok_or("unable to convert Option to Result")?;
+        .ok_or_else(|| Error::Syntax { location: Box::new(Location::default()), message: "empty parse tree".to_string() })?;
 
     let mut interfaces: HashMap<String, Interface> = HashMap::new();
     for line in ip_addr_out.into_inner() {
@@ -0,0 +1,271 @@
+//! Parsing every unit file under a directory, e.g. `/etc/systemd/system` or the root of a container
+//! image, in parallel.
+
+use serde_derive::Serialize;
+use std::fs;
+use std::io;
+use std::iter::Peekable;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::vec;
+use crate::parser::error::Error;
+use super::{parse_and_check, SystemdFile, UnitType};
+
+/// The outcome of parsing one file found by `parse_tree`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all="camelCase")]
+pub struct ParsedUnit {
+    pub path: PathBuf,
+    /// Whether the file is a `.conf` drop-in rather than a unit file.
+    pub dropin: bool,
+    /// What could be parsed, or nothing if the file couldn't be read.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<SystemdFile>,
+    /// Everything `validate` reports for the file.
+    pub diagnostics: Vec<Error>,
+}
+
+/// Paths of the unit files and drop-ins under `root`, sorted. Unit files are recognised by their suffix,
+/// drop-ins are the `.conf` files in directories whose name ends in `.d`. Symbolic links to files are
+/// included, such as aliases and masked units; links to directories are not followed. A directory below
+/// `root` that can't be read is skipped and reported in the second list, with the error reading it; only
+/// failing to read `root` itself is an error.
+pub fn find_unit_files<P: AsRef<Path>>(root: P) -> io::Result<(Vec<PathBuf>, Vec<ParsedUnit>)> {
+    let root = root.as_ref();
+    let mut found = Vec::new();
+    let mut unreadable = Vec::new();
+    let mut dirs = vec![root.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if dir == root => return Err(e),
+            Err(e) => {
+                unreadable.push(unreadable_dir(&dir, e));
+                continue;
+            }
+        };
+        for entry in entries {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    unreadable.push(unreadable_dir(&dir, e));
+                    break;
+                }
+            };
+            let path = entry.path();
+            match entry.file_type() {
+                Ok(file_type) if file_type.is_dir() => dirs.push(path),
+                Ok(_) if is_unit_file(&path) => found.push(path),
+                Ok(_) => {}
+                Err(e) => unreadable.push(unreadable_dir(&path, e)),
+            }
+        }
+    }
+    found.sort();
+    unreadable.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok((found, unreadable))
+}
+
+fn unreadable_dir(dir: &Path, e: io::Error) -> ParsedUnit {
+    ParsedUnit {
+        path: dir.to_path_buf(),
+        dropin: false,
+        file: None,
+        diagnostics: vec![Error::from(e).with_file(&dir.display().to_string())],
+    }
+}
+
+fn is_unit_file(path: &Path) -> bool {
+    if UnitType::from_file_name(path).is_some() {
+        return true;
+    }
+    let in_dropin_dir = path.parent()
+        .and_then(|dir| dir.file_name())
+        .is_some_and(|name| name.to_string_lossy().ends_with(".d"));
+
+    in_dropin_dir && path.extension().is_some_and(|ext| ext == "conf")
+}
+
+/// Parses and checks every file `find_unit_files` finds under `root`, spread over as many threads as there
+/// are cores. `each` is called on the calling thread for every file, in the order of their paths, as soon
+/// as that file and all before it are done. A file that can't be read or parsed, or a directory that can't
+/// be read, is reported through its diagnostics and doesn't stop the others. Returns the number of results
+/// passed to `each`, or the error reading `root`.
+pub fn parse_tree<P, F>(root: P, mut each: F) -> io::Result<usize>
+where
+    P: AsRef<Path>,
+    F: FnMut(ParsedUnit),
+{
+    let (paths, unreadable) = find_unit_files(root)?;
+    let results = paths.len() + unreadable.len();
+    let workers = thread::available_parallelism().map_or(1, NonZeroUsize::get).min(paths.len());
+    let next = AtomicUsize::new(0);
+    let mut in_order = InOrder::new(paths.len(), unreadable);
+
+    thread::scope(|scope| {
+        let (sender, receiver) = mpsc::channel();
+        for _ in 0..workers {
+            let sender = sender.clone();
+            let (paths, next) = (&paths, &next);
+            scope.spawn(move || loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(path) = paths.get(i) else { break };
+                if sender.send((i, parse_unit(path))).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        for (i, unit) in receiver {
+            in_order.push(i, unit, &mut each);
+        }
+    });
+    in_order.finish(&mut each);
+
+    Ok(results)
+}
+
+/// Puts the results of the workers, which arrive in whatever order they finish them, back into the order
+/// of their paths, with the directories that couldn't be read in their place among them.
+struct InOrder {
+    /// Results that arrived before some result for an earlier path, by the index of their path.
+    pending: Vec<Option<ParsedUnit>>,
+    emitted: usize,
+    unreadable: Peekable<vec::IntoIter<ParsedUnit>>,
+}
+
+impl InOrder {
+    /// Expects results for `count` paths. `unreadable` must be sorted by path.
+    fn new(count: usize, unreadable: Vec<ParsedUnit>) -> Self {
+        InOrder { pending: (0..count).map(|_| None).collect(), emitted: 0, unreadable: unreadable.into_iter().peekable() }
+    }
+
+    /// Takes the result for the path at index `i` and passes every result that is now in order to `each`.
+    fn push<F: FnMut(ParsedUnit)>(&mut self, i: usize, unit: ParsedUnit, each: &mut F) {
+        self.pending[i] = Some(unit);
+        while let Some(unit) = self.pending.get_mut(self.emitted).and_then(Option::take) {
+            while let Some(dir) = self.unreadable.next_if(|dir| dir.path < unit.path) {
+                each(dir);
+            }
+            each(unit);
+            self.emitted += 1;
+        }
+    }
+
+    /// Passes the unreadable directories that come after the last path to `each`.
+    fn finish<F: FnMut(ParsedUnit)>(self, each: &mut F) {
+        self.unreadable.for_each(each);
+    }
+}
+
+/// Parses and checks a single file found by `find_unit_files`.
+pub fn parse_unit(path: &Path) -> ParsedUnit {
+    let file_name = path.display().to_string();
+    let dropin = UnitType::from_file_name(path).is_none();
+    let unparsed_file = match fs::read_to_string(path) {
+        Ok(unparsed_file) => unparsed_file,
+        Err(e) => {
+            return ParsedUnit {
+                path: path.to_path_buf(),
+                dropin,
+                file: None,
+                diagnostics: vec![Error::from(e).with_file(&file_name)],
+            };
+        }
+    };
+
+    let (mut file, diagnostics) = parse_and_check(&unparsed_file);
+    file.unit_type = if dropin { dropin_unit_type(path) } else { UnitType::from_file_name(path) };
+
+    ParsedUnit {
+        path: path.to_path_buf(),
+        dropin,
+        file: Some(file),
+        diagnostics: diagnostics.into_iter().map(|e| e.with_file(&file_name)).collect(),
+    }
+}
+
+/// The type of the units a drop-in applies to, from the name of its directory: `foo.service.d` and
+/// `service.d` both hold drop-ins for services.
+fn dropin_unit_type(path: &Path) -> Option<UnitType> {
+    let dir = path.parent()?.file_name()?.to_str()?;
+    let unit_name = dir.strip_suffix(".d")?;
+
+    UnitType::from_file_name(unit_name).or_else(|| unit_name.parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::testing::tree;
+
+    #[test]
+    fn unit_files_and_dropins_are_recognised() {
+        assert!(is_unit_file(Path::new("/etc/systemd/system/a.service")));
+        assert!(is_unit_file(Path::new("/etc/systemd/system/a.service.d/10-override.conf")));
+        assert!(is_unit_file(Path::new("/usr/lib/systemd/system/service.d/10-all.conf")));
+        assert!(!is_unit_file(Path::new("/etc/systemd/system/a.conf")));
+        assert!(!is_unit_file(Path::new("/etc/systemd/system/a.service.d/README")));
+        assert!(!is_unit_file(Path::new("/etc/systemd/system.conf")));
+
+        assert_eq!(dropin_unit_type(Path::new("a.service.d/10.conf")), Some(UnitType::Service));
+        assert_eq!(dropin_unit_type(Path::new("getty@.service.d/10.conf")), Some(UnitType::Service));
+        assert_eq!(dropin_unit_type(Path::new("socket.d/10.conf")), Some(UnitType::Socket));
+        assert_eq!(dropin_unit_type(Path::new("journald.conf.d/10.conf")), None);
+    }
+
+    #[test]
+    fn unit_files_are_found_in_path_order() {
+        let root = tree("bulk-find", &[
+            ("b.service", ""),
+            ("a/z.timer", ""),
+            ("a/y.service.d/10.conf", ""),
+            ("a/notes.txt", ""),
+            ("system.conf", ""),
+        ]);
+        let (found, unreadable) = find_unit_files(&root).unwrap();
+        assert!(unreadable.is_empty());
+        assert_eq!(found, [root.join("a/y.service.d/10.conf"), root.join("a/z.timer"), root.join("b.service")]);
+        assert!(find_unit_files(root.join("missing")).is_err());
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn results_come_in_path_order_whatever_thread_parses_them() {
+        let files: Vec<(String, String)> = (0..64)
+            .map(|i| (format!("d{}/u{i:02}.service", i % 5), format!("[Service]\nExecStart=/bin/u{i}\n")))
+            .chain([("bad.service".to_string(), "[Service\n".to_string())])
+            .collect();
+        let files: Vec<(&str, &str)> = files.iter().map(|(p, c)| (p.as_str(), c.as_str())).collect();
+        let root = tree("bulk-order", &files);
+
+        let mut units = Vec::new();
+        let count = parse_tree(&root, |unit| units.push(unit)).unwrap();
+        assert_eq!(count, 65);
+        let paths: Vec<&PathBuf> = units.iter().map(|unit| &unit.path).collect();
+        assert_eq!(paths, find_unit_files(&root).unwrap().0.iter().collect::<Vec<_>>());
+        assert!(units.iter().all(|unit| unit.file.as_ref().unwrap().unit_type == Some(UnitType::Service)));
+        let bad = units.iter().find(|unit| unit.path.ends_with("bad.service")).unwrap();
+        assert_eq!(bad.diagnostics.len(), 1);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn unreadable_directories_come_in_path_order_among_the_results() {
+        let unit = |path: &str| ParsedUnit { path: PathBuf::from(path), dropin: false, file: None, diagnostics: vec![] };
+        let mut in_order = InOrder::new(3, vec![unit("b"), unit("d"), unit("z")]);
+        let mut paths = Vec::new();
+        let mut each = |unit: ParsedUnit| paths.push(unit.path.display().to_string());
+
+        in_order.push(2, unit("e.service"), &mut each);
+        in_order.push(0, unit("a.service"), &mut each);
+        in_order.push(1, unit("c/x.service"), &mut each);
+        in_order.finish(&mut each);
+        assert_eq!(paths, ["a.service", "b", "c/x.service", "d", "e.service", "z"]);
+    }
+}
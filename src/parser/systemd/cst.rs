@@ -0,0 +1,217 @@
+use std::fmt::{Display, Formatter};
+use pest::iterators::Pair;
+use super::Rule;
+
+/// What a line of a unit file holds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LineKind {
+    /// A `[Section]` header, with the section name.
+    SectionHeader(String),
+    /// A `Key=Value` assignment, with the key. The line may be continued over several physical lines.
+    Entry(String),
+    Comment,
+    Blank,
+    /// A line the grammar doesn't accept, only kept by the recovering parser.
+    Invalid,
+}
+
+/// A logical line exactly as it appears in the file, without its line break.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    pub kind: LineKind,
+    /// The text of the line. Continued assignments keep the line breaks between their physical lines.
+    pub text: String,
+}
+
+/// The lines of a unit file in source order, keeping everything the typed model throws away: comments
+/// where they were, blank lines, whitespace, `:` separators and continuations. Writing it out gives back
+/// the parsed file byte for byte.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Cst {
+    pub lines: Vec<Line>,
+    /// Whether the last line ends in a line break.
+    pub final_newline: bool,
+}
+
+impl Cst {
+    /// Splits `source` into lines classified by the parse tree `file` the grammar produced for it.
+    pub(super) fn from_parse(file: Pair<'_, Rule>, source: &str) -> Cst {
+        let mut physical: Vec<&str> = source.split('\n').collect();
+        let final_newline = source.ends_with('\n');
+        if final_newline {
+            physical.pop();
+        }
+
+        // kind and last physical line of every line the grammar matched, by index of its first line
+        let mut matched: Vec<Option<(LineKind, usize)>> = vec![None; physical.len()];
+        for pair in file.into_inner() {
+            let kind = match pair.as_rule() {
+                Rule::section_header => LineKind::SectionHeader(inner_str(&pair, Rule::section_name)),
+                Rule::entry => LineKind::Entry(inner_str(&pair, Rule::key)),
+                Rule::comment => LineKind::Comment,
+                Rule::invalid => LineKind::Invalid,
+                _ => continue,
+            };
+            let first = pair.as_span().start_pos().line_col().0 - 1;
+            let last = pair.as_span().end_pos().line_col().0 - 1;
+            if let Some(slot) = matched.get_mut(first) {
+                *slot = Some((kind, last));
+            }
+        }
+
+        let mut lines = Vec::new();
+        let mut i = 0;
+        while i < physical.len() {
+            let (kind, last) = matched[i].take().unwrap_or((LineKind::Blank, i));
+            let last = last.clamp(i, physical.len() - 1);
+            lines.push(Line { kind, text: physical[i..=last].join("\n") });
+            i = last + 1;
+        }
+
+        Cst { lines, final_newline }
+    }
+
+    /// Whether the file has Windows line breaks, judging by its first line. The `\r` of each line is part
+    /// of its text, so lines added to such a file have to end in one as well.
+    pub fn uses_crlf(&self) -> bool {
+        self.lines.first().is_some_and(|line| line.text.ends_with('\r'))
+    }
+
+    /// The same lines laid out the usual way: no whitespace around section names, keys and `=`, no
+    /// trailing whitespace, a single blank line before each section and its comments and none elsewhere
+    /// but where there was one, and a line break at the end. Comments, continuations and the order of
+    /// everything are kept.
+    pub fn formatted(&self) -> Cst {
+        let mut lines: Vec<Line> = Vec::new();
+        for line in &self.lines {
+            let text = match &line.kind {
+                LineKind::SectionHeader(name) => format!("[{}]", name.trim()),
+                LineKind::Entry(key) => format_entry(&line.text, key),
+                LineKind::Comment => line.text.trim().to_string(),
+                LineKind::Blank => {
+                    // blank lines are collapsed and never start the file
+                    if lines.last().is_some_and(|l| l.kind != LineKind::Blank) {
+                        lines.push(Line { kind: LineKind::Blank, text: String::new() });
+                    }
+                    continue;
+                }
+                LineKind::Invalid => trim_line_end(&line.text).to_string(),
+            };
+
+            if matches!(line.kind, LineKind::SectionHeader(_)) {
+                // the comments right above a header belong to its section
+                let comments = lines.iter().rev().take_while(|l| l.kind == LineKind::Comment).count();
+                let start = lines.len() - comments;
+                if start > 0 && lines[start - 1].kind != LineKind::Blank {
+                    lines.insert(start, Line { kind: LineKind::Blank, text: String::new() });
+                }
+            }
+            lines.push(Line { kind: line.kind.clone(), text });
+        }
+        while lines.last().is_some_and(|l| l.kind == LineKind::Blank) {
+            lines.pop();
+        }
+
+        let final_newline = !lines.is_empty();
+        Cst { lines, final_newline }
+    }
+}
+
+/// Writes an assignment as `Key=value`, keeping the physical lines of a continued value as they are apart
+/// from trailing whitespace.
+fn format_entry(text: &str, key: &str) -> String {
+    let mut physical = text.split('\n');
+    let first = physical.next().unwrap_or_default().trim_start();
+    let value = first.strip_prefix(key).unwrap_or(first).trim_start();
+    let value = value.strip_prefix(['=', ':']).unwrap_or(value).trim_start();
+
+    let mut formatted = format!("{key}={}", trim_line_end(value));
+    for line in physical {
+        formatted.push('\n');
+        formatted.push_str(trim_line_end(line));
+    }
+
+    formatted
+}
+
+/// Strips trailing whitespace unless that would leave a backslash at the end, which would turn the line
+/// into a continuation.
+fn trim_line_end(line: &str) -> &str {
+    let trimmed = line.trim_end();
+    if trimmed.ends_with('\\') && trimmed.len() != line.len() {
+        line
+    } else {
+        trimmed
+    }
+}
+
+fn inner_str(pair: &Pair<'_, Rule>, rule: Rule) -> String {
+    pair.clone().into_inner()
+        .find(|p| p.as_rule() == rule)
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_default()
+}
+
+impl Display for Cst {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (i, line) in self.lines.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", line.text)?;
+        }
+        if self.final_newline {
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{parse_str, parse_str_recovering};
+
+    fn round_trip(source: &str) -> String {
+        parse_str(source).unwrap().to_string()
+    }
+
+    #[test]
+    fn sample_units_are_written_back_byte_for_byte() {
+        for source in [
+            include_str!("../../../sample.service"),
+            include_str!("../../../sshd.service"),
+            include_str!("../../../syslog.service"),
+            include_str!("../../../unit.service"),
+        ] {
+            assert_eq!(round_trip(source), source);
+        }
+    }
+
+    #[test]
+    fn formatting_is_kept() {
+        for source in [
+            "# leading\n\n[Unit]\n  Description = spaced out  \n\n\n; other comment\n[Service]\nExecStart=/bin/a \\\n    --flag \\\n# inside\n    arg\n",
+            "[Unit]\r\nDescription=crlf\r\n",
+            "[Unit]\nDescription=no final newline",
+            "",
+        ] {
+            assert_eq!(round_trip(source), source);
+        }
+    }
+
+    #[test]
+    fn invalid_lines_are_kept_by_the_recovering_parser() {
+        let source = "[Unit]\nthis is not an assignment\nDescription=x\n";
+        let (file, errors) = parse_str_recovering(source);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(file.to_string(), source);
+    }
+
+    #[test]
+    fn formatted_lays_a_file_out_the_usual_way() {
+        let mut file = parse_str("\n[Unit]\n\n\nDescription = x\n# about the service\n[Service]\nType=simple   ").unwrap();
+        file.format();
+        assert_eq!(file.to_string(), "[Unit]\n\nDescription=x\n\n# about the service\n[Service]\nType=simple\n");
+    }
+}
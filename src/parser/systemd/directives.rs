@@ -0,0 +1,197 @@
+//! Catalogue of the directives systemd understands, used to flag unknown or repeated keys.
+
+pub const UNIT: &[&str] = &[
+    "Description", "Documentation", "Wants", "Requires", "Requisite", "BindsTo", "PartOf", "Upholds",
+    "Conflicts", "Before", "After", "OnFailure", "OnSuccess", "PropagatesReloadTo", "ReloadPropagatedFrom",
+    "PropagatesStopTo", "StopPropagatedFrom", "JoinsNamespaceOf", "RequiresMountsFor", "WantsMountsFor",
+    "OnFailureJobMode", "IgnoreOnIsolate", "StopWhenUnneeded", "RefuseManualStart", "RefuseManualStop",
+    "AllowIsolate", "DefaultDependencies", "SurviveFinalKillSignal", "CollectMode", "FailureAction",
+    "SuccessAction", "FailureActionExitStatus", "SuccessActionExitStatus", "JobTimeoutSec",
+    "JobRunningTimeoutSec", "JobTimeoutAction", "JobTimeoutRebootArgument", "StartLimitIntervalSec",
+    "StartLimitBurst", "StartLimitAction", "RebootArgument", "SourcePath",
+];
+
+/// Condition checks. Every one of them also exists as an `Assert...` variant.
+pub const CONDITIONS: &[&str] = &[
+    "ConditionArchitecture", "ConditionFirmware", "ConditionVirtualization", "ConditionHost",
+    "ConditionKernelCommandLine", "ConditionKernelVersion", "ConditionCredential", "ConditionEnvironment",
+    "ConditionSecurity", "ConditionCapability", "ConditionACPower", "ConditionNeedsUpdate",
+    "ConditionFirstBoot", "ConditionPathExists", "ConditionPathExistsGlob", "ConditionPathIsDirectory",
+    "ConditionPathIsSymbolicLink", "ConditionPathIsMountPoint", "ConditionPathIsReadWrite",
+    "ConditionPathIsEncrypted", "ConditionDirectoryNotEmpty", "ConditionFileNotEmpty",
+    "ConditionFileIsExecutable", "ConditionUser", "ConditionGroup", "ConditionControlGroupController",
+    "ConditionMemory", "ConditionCPUs", "ConditionCPUFeature", "ConditionOSRelease",
+    "ConditionMemoryPressure", "ConditionCPUPressure", "ConditionIOPressure",
+];
+
+pub const SERVICE: &[&str] = &[
+    "Type", "ExitType", "RemainAfterExit", "GuessMainPID", "PIDFile", "BusName", "ExecStart", "ExecStartPre",
+    "ExecStartPost", "ExecCondition", "ExecReload", "ExecStop", "ExecStopPost", "RestartSec", "RestartSteps",
+    "RestartMaxDelaySec", "TimeoutStartSec", "TimeoutStopSec", "TimeoutAbortSec", "TimeoutSec",
+    "TimeoutStartFailureMode", "TimeoutStopFailureMode", "RuntimeMaxSec", "RuntimeRandomizedExtraSec",
+    "WatchdogSec", "Restart", "RestartMode", "SuccessExitStatus", "RestartPreventExitStatus",
+    "RestartForceExitStatus", "RootDirectoryStartOnly", "NonBlocking", "NotifyAccess", "Sockets",
+    "FileDescriptorStoreMax", "FileDescriptorStorePreserve", "USBFunctionDescriptors", "USBFunctionStrings",
+    "OOMPolicy", "OpenFile", "ReloadSignal", "PermissionsStartOnly",
+    // [Unit] settings that older releases accepted in [Service]
+    "StartLimitInterval", "StartLimitBurst", "StartLimitAction", "FailureAction", "RebootArgument",
+];
+
+pub const SOCKET: &[&str] = &[
+    "ListenStream", "ListenDatagram", "ListenSequentialPacket", "ListenFIFO", "ListenSpecial", "ListenNetlink",
+    "ListenMessageQueue", "ListenUSBFunction", "SocketProtocol", "BindIPv6Only", "Backlog", "BindToDevice",
+    "SocketUser", "SocketGroup", "DirectoryMode", "SocketMode", "Accept", "Writable", "FlushPending",
+    "MaxConnections", "MaxConnectionsPerSource", "KeepAlive", "KeepAliveTimeSec", "KeepAliveIntervalSec",
+    "KeepAliveProbes", "NoDelay", "Priority", "DeferAcceptSec", "ReceiveBuffer", "SendBuffer", "IPTOS",
+    "IPTTL", "Mark", "ReusePort", "SmackLabel", "SmackLabelIPIn", "SmackLabelIPOut", "SELinuxContextFromNet",
+    "PipeSize", "MessageQueueMaxMessages", "MessageQueueMessageSize", "FreeBind", "Transparent", "Broadcast",
+    "PassCredentials", "PassSecurity", "PassPacketInfo", "Timestamping", "TCPCongestion", "ExecStartPre",
+    "ExecStartPost", "ExecStopPre", "ExecStopPost", "TimeoutSec", "Service", "RemoveOnStop", "Symlinks",
+    "FileDescriptorName", "TriggerLimitIntervalSec", "TriggerLimitBurst", "PollLimitIntervalSec",
+    "PollLimitBurst",
+];
+
+pub const TIMER: &[&str] = &[
+    "OnActiveSec", "OnBootSec", "OnStartupSec", "OnUnitActiveSec", "OnUnitInactiveSec", "OnCalendar",
+    "AccuracySec", "RandomizedDelaySec", "FixedRandomDelay", "OnClockChange", "OnTimezoneChange", "Unit",
+    "Persistent", "WakeSystem", "RemainAfterElapse",
+];
+
+pub const MOUNT: &[&str] = &[
+    "What", "Where", "Type", "Options", "SloppyOptions", "LazyUnmount", "ReadWriteOnly", "ForceUnmount",
+    "DirectoryMode", "TimeoutSec",
+];
+
+pub const AUTOMOUNT: &[&str] = &["Where", "ExtraOptions", "DirectoryMode", "TimeoutIdleSec"];
+
+pub const PATH: &[&str] = &[
+    "PathExists", "PathExistsGlob", "PathChanged", "PathModified", "DirectoryNotEmpty", "Unit",
+    "MakeDirectory", "DirectoryMode", "TriggerLimitIntervalSec", "TriggerLimitBurst",
+];
+
+pub const SWAP: &[&str] = &["What", "Priority", "Options", "TimeoutSec"];
+
+pub const SCOPE: &[&str] = &["OOMPolicy", "RuntimeMaxSec", "RuntimeRandomizedExtraSec", "TimeoutStopSec"];
+
+/// Settings of systemd.exec(5), shared by every unit type that spawns processes.
+pub const EXEC: &[&str] = &[
+    "WorkingDirectory", "RootDirectory", "RootImage", "RootImageOptions", "RootEphemeral", "RootHash",
+    "RootHashSignature", "RootVerity", "RootImagePolicy", "MountImagePolicy", "ExtensionImagePolicy",
+    "MountAPIVFS", "ProtectProc", "ProcSubset", "BindPaths", "BindReadOnlyPaths", "MountImages",
+    "ExtensionImages", "ExtensionDirectories", "User", "Group", "DynamicUser", "SupplementaryGroups",
+    "SetLoginEnvironment", "PAMName", "CapabilityBoundingSet", "AmbientCapabilities", "NoNewPrivileges",
+    "SecureBits", "SELinuxContext", "AppArmorProfile", "SmackProcessLabel", "LimitCPU", "LimitFSIZE",
+    "LimitDATA", "LimitSTACK", "LimitCORE", "LimitRSS", "LimitNOFILE", "LimitAS", "LimitNPROC",
+    "LimitMEMLOCK", "LimitLOCKS", "LimitSIGPENDING", "LimitMSGQUEUE", "LimitNICE", "LimitRTPRIO",
+    "LimitRTTIME", "UMask", "CoredumpFilter", "KeyringMode", "OOMScoreAdjust", "TimerSlackNSec",
+    "Personality", "IgnoreSIGPIPE", "Nice", "CPUSchedulingPolicy", "CPUSchedulingPriority",
+    "CPUSchedulingResetOnFork", "CPUAffinity", "NUMAPolicy", "NUMAMask", "IOSchedulingClass",
+    "IOSchedulingPriority", "ProtectSystem", "ProtectHome", "RuntimeDirectory", "StateDirectory",
+    "CacheDirectory", "LogsDirectory", "ConfigurationDirectory", "RuntimeDirectoryMode", "StateDirectoryMode",
+    "CacheDirectoryMode", "LogsDirectoryMode", "ConfigurationDirectoryMode", "RuntimeDirectoryPreserve",
+    "TimeoutCleanSec", "ReadWritePaths", "ReadOnlyPaths", "InaccessiblePaths", "ExecPaths", "NoExecPaths",
+    "TemporaryFileSystem", "PrivateTmp", "PrivateDevices", "PrivateNetwork", "NetworkNamespacePath",
+    "PrivateIPC", "IPCNamespacePath", "MemoryKSM", "PrivateUsers", "ProtectHostname", "ProtectClock",
+    "ProtectKernelTunables", "ProtectKernelModules", "ProtectKernelLogs", "ProtectControlGroups",
+    "RestrictAddressFamilies", "RestrictFileSystems", "RestrictNamespaces", "LockPersonality",
+    "MemoryDenyWriteExecute", "RestrictRealtime", "RestrictSUIDSGID", "RemoveIPC", "PrivateMounts",
+    "MountFlags", "SystemCallFilter", "SystemCallErrorNumber", "SystemCallArchitectures", "SystemCallLog",
+    "Environment", "EnvironmentFile", "PassEnvironment", "UnsetEnvironment", "StandardInput",
+    "StandardOutput", "StandardError", "StandardInputText", "StandardInputData", "LogLevelMax",
+    "LogExtraFields", "LogRateLimitIntervalSec", "LogRateLimitBurst", "LogFilterPatterns", "LogNamespace",
+    "SyslogIdentifier", "SyslogFacility", "SyslogLevel", "SyslogLevelPrefix", "TTYPath", "TTYReset",
+    "TTYVHangup", "TTYRows", "TTYColumns", "TTYVTDisallocate", "LoadCredential", "LoadCredentialEncrypted",
+    "ImportCredential", "SetCredential", "SetCredentialEncrypted", "UtmpIdentifier", "UtmpMode",
+];
+
+/// Settings of systemd.kill(5).
+pub const KILL: &[&str] = &[
+    "KillMode", "KillSignal", "RestartKillSignal", "SendSIGHUP", "SendSIGKILL", "FinalKillSignal",
+    "WatchdogSignal",
+];
+
+/// Settings of systemd.resource-control(5), including the deprecated cgroup v1 names.
+pub const RESOURCE_CONTROL: &[&str] = &[
+    "CPUAccounting", "CPUWeight", "StartupCPUWeight", "CPUQuota", "CPUQuotaPeriodSec", "AllowedCPUs",
+    "StartupAllowedCPUs", "AllowedMemoryNodes", "StartupAllowedMemoryNodes", "MemoryAccounting", "MemoryMin",
+    "MemoryLow", "StartupMemoryLow", "DefaultStartupMemoryLow", "MemoryHigh", "StartupMemoryHigh",
+    "MemoryMax", "StartupMemoryMax", "MemorySwapMax", "StartupMemorySwapMax", "MemoryZSwapMax",
+    "StartupMemoryZSwapMax", "MemoryZSwapWriteback", "TasksAccounting", "TasksMax", "IOAccounting",
+    "IOWeight", "StartupIOWeight", "IODeviceWeight", "IOReadBandwidthMax", "IOWriteBandwidthMax",
+    "IOReadIOPSMax", "IOWriteIOPSMax", "IODeviceLatencyTargetSec", "IPAccounting", "IPAddressAllow",
+    "IPAddressDeny", "SocketBindAllow", "SocketBindDeny", "RestrictNetworkInterfaces", "NFTSet",
+    "IPIngressFilterPath", "IPEgressFilterPath", "BPFProgram", "DeviceAllow", "DevicePolicy", "Slice",
+    "Delegate", "DelegateSubgroup", "DisableControllers", "ManagedOOMSwap", "ManagedOOMMemoryPressure",
+    "ManagedOOMMemoryPressureLimit", "ManagedOOMPreference", "MemoryPressureWatch",
+    "MemoryPressureThresholdSec", "CoredumpReceive", "CPUShares", "StartupCPUShares", "MemoryLimit",
+    "BlockIOAccounting", "BlockIOWeight", "StartupBlockIOWeight", "BlockIODeviceWeight",
+    "BlockIOReadBandwidth", "BlockIOWriteBandwidth",
+];
+
+pub const INSTALL: &[&str] = &["Alias", "WantedBy", "RequiredBy", "UpheldBy", "Also", "DefaultInstance"];
+
+/// Directives that may be assigned more than once, each assignment adding to the previous ones. Every
+/// other directive takes a single value and only its last assignment counts.
+pub const LIST: &[&str] = &[
+    "Documentation", "Wants", "Requires", "Requisite", "BindsTo", "PartOf", "Upholds", "Conflicts", "Before",
+    "After", "OnFailure", "OnSuccess", "PropagatesReloadTo", "ReloadPropagatedFrom", "PropagatesStopTo",
+    "StopPropagatedFrom", "JoinsNamespaceOf", "RequiresMountsFor", "WantsMountsFor",
+    "ExecStart", "ExecStartPre", "ExecStartPost", "ExecCondition", "ExecReload", "ExecStop", "ExecStopPost",
+    "SuccessExitStatus", "RestartPreventExitStatus", "RestartForceExitStatus", "Sockets", "OpenFile",
+    "Environment", "EnvironmentFile", "PassEnvironment", "UnsetEnvironment", "RuntimeDirectory",
+    "StateDirectory", "CacheDirectory", "LogsDirectory", "ConfigurationDirectory", "ReadWritePaths",
+    "ReadOnlyPaths", "InaccessiblePaths", "ExecPaths", "NoExecPaths", "BindPaths", "BindReadOnlyPaths",
+    "TemporaryFileSystem", "MountImages", "ExtensionImages", "ExtensionDirectories", "SupplementaryGroups",
+    "CapabilityBoundingSet", "AmbientCapabilities", "SystemCallFilter", "SystemCallArchitectures",
+    "SystemCallLog", "RestrictAddressFamilies", "RestrictNamespaces", "RestrictFileSystems", "CPUAffinity",
+    "LogExtraFields", "LogFilterPatterns", "LoadCredential", "LoadCredentialEncrypted", "ImportCredential",
+    "SetCredential", "SetCredentialEncrypted", "StandardInputText", "StandardInputData",
+    "DeviceAllow", "IPAddressAllow", "IPAddressDeny", "SocketBindAllow", "SocketBindDeny",
+    "RestrictNetworkInterfaces", "IODeviceWeight", "IOReadBandwidthMax", "IOWriteBandwidthMax",
+    "IOReadIOPSMax", "IOWriteIOPSMax", "IODeviceLatencyTargetSec", "BPFProgram", "NFTSet",
+    "IPIngressFilterPath", "IPEgressFilterPath", "DisableControllers", "BlockIODeviceWeight",
+    "BlockIOReadBandwidth", "BlockIOWriteBandwidth",
+    "Alias", "WantedBy", "RequiredBy", "UpheldBy", "Also",
+    "ListenStream", "ListenDatagram", "ListenSequentialPacket", "ListenFIFO", "ListenSpecial", "ListenNetlink",
+    "ListenMessageQueue", "ListenUSBFunction", "ExecStopPre", "Symlinks",
+    "OnActiveSec", "OnBootSec", "OnStartupSec", "OnUnitActiveSec", "OnUnitInactiveSec", "OnCalendar",
+    "PathExists", "PathExistsGlob", "PathChanged", "PathModified", "DirectoryNotEmpty",
+];
+
+/// Returns the groups of directives accepted in `section`, or `None` for sections this catalogue doesn't
+/// cover.
+fn catalogue(section: &str) -> Option<Vec<&'static [&'static str]>> {
+    match section {
+        "Unit" => Some(vec![UNIT, CONDITIONS]),
+        "Service" => Some(vec![SERVICE, EXEC, KILL, RESOURCE_CONTROL]),
+        "Socket" => Some(vec![SOCKET, EXEC, KILL, RESOURCE_CONTROL]),
+        "Mount" => Some(vec![MOUNT, EXEC, KILL, RESOURCE_CONTROL]),
+        "Swap" => Some(vec![SWAP, EXEC, KILL, RESOURCE_CONTROL]),
+        "Timer" => Some(vec![TIMER]),
+        "Automount" => Some(vec![AUTOMOUNT]),
+        "Path" => Some(vec![PATH]),
+        "Slice" => Some(vec![RESOURCE_CONTROL]),
+        "Scope" => Some(vec![SCOPE, KILL, RESOURCE_CONTROL]),
+        "Install" => Some(vec![INSTALL]),
+        _ => None,
+    }
+}
+
+/// Returns whether `key` is a directive of `section`, or `None` when the section isn't catalogued.
+/// Keys prefixed with `X-` are reserved for extensions and always accepted.
+pub fn is_known(section: &str, key: &str) -> Option<bool> {
+    let groups = catalogue(section)?;
+    if key.starts_with("X-") {
+        return Some(true);
+    }
+    // every Condition has an Assert twin
+    let condition = key.strip_prefix("Assert").map(|rest| format!("Condition{rest}"));
+    let key = condition.as_deref().unwrap_or(key);
+
+    Some(groups.iter().any(|group| group.contains(&key)))
+}
+
+/// Returns whether `key` accumulates values over repeated assignments.
+pub fn is_list(key: &str) -> bool {
+    key.starts_with("Condition") || key.starts_with("Assert") || LIST.contains(&key)
+}
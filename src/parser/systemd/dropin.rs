@@ -0,0 +1,194 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::parser::error::Error;
+use super::{parse, Section, SystemdFile};
+
+/// Names of the drop-in directories that apply to `unit_name`, from the least to the most specific. For
+/// `foo-bar@baz.service` these are `service.d`, `foo-.service.d`, `foo-bar@.service.d` and
+/// `foo-bar@baz.service.d`.
+pub fn dropin_dir_names(unit_name: &str) -> Vec<String> {
+    let (stem, suffix) = match unit_name.rsplit_once('.') {
+        Some(parts) => parts,
+        None => return vec![format!("{unit_name}.d")],
+    };
+    let (prefix, instance) = match stem.split_once('@') {
+        Some((prefix, instance)) => (prefix, Some(instance)),
+        None => (stem, None),
+    };
+
+    let mut names = vec![format!("{suffix}.d")];
+    // every dash-separated prefix of the name gets its own directory
+    for (i, _) in prefix.match_indices('-') {
+        names.push(format!("{}-.{suffix}.d", &prefix[..i]));
+    }
+    // instances also pick up the drop-ins of their template
+    if instance.is_some_and(|i| !i.is_empty()) {
+        names.push(format!("{prefix}@.{suffix}.d"));
+    }
+    names.push(format!("{unit_name}.d"));
+
+    names
+}
+
+/// Finds the `.conf` drop-ins for `unit_name` in the drop-in directories under `dir`, in the order they
+/// are applied. Drop-ins are applied in lexical order of their file names; when the same file name shows
+/// up in several directories only the one in the most specific directory is used.
+pub fn find_dropins_in<P: AsRef<Path>>(dir: P, unit_name: &str) -> Vec<PathBuf> {
+    let mut dropins: Vec<PathBuf> = Vec::new();
+    for dir_name in dropin_dir_names(unit_name) {
+        let entries = match fs::read_dir(dir.as_ref().join(dir_name)) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+
+        for path in entries.flatten().map(|e| e.path()) {
+            if path.extension().is_some_and(|ext| ext == "conf") {
+                dropins.retain(|d| d.file_name() != path.file_name());
+                dropins.push(path);
+            }
+        }
+    }
+    dropins.sort_by(|a, b| a.file_name().cmp(&b.file_name()));
+
+    dropins
+}
+
+/// Finds the drop-ins of the unit file at `unit_path` in the directory the unit file is in.
+pub fn find_dropins<P: AsRef<Path>>(unit_path: P) -> Vec<PathBuf> {
+    let unit_path = unit_path.as_ref();
+    let unit_name = unit_path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+    match unit_path.parent() {
+        Some(dir) => find_dropins_in(dir, unit_name),
+        None => vec![],
+    }
+}
+
+/// Parses the unit file at `unit_path` and applies the given drop-ins in the order given. Every entry of
+/// the result records the file it came from.
+pub fn merge<P: AsRef<Path>, D: AsRef<Path>>(unit_path: P, dropins: &[D]) -> Result<SystemdFile, Error> {
+    let mut file = parse(unit_path.as_ref())?;
+    let source = unit_path.as_ref().display().to_string();
+    for entry in file.sections.iter_mut().flat_map(|s| s.entries.iter_mut()) {
+        entry.source.get_or_insert_with(|| source.clone());
+    }
+
+    for dropin_path in dropins {
+        let dropin = parse(dropin_path.as_ref())?;
+        file.apply_dropin(dropin, &dropin_path.as_ref().display().to_string());
+    }
+    file.refresh();
+
+    Ok(file)
+}
+
+/// Parses the unit file at `unit_path` merged with the drop-ins found next to it.
+pub fn parse_with_dropins<P: AsRef<Path>>(unit_path: P) -> Result<SystemdFile, Error> {
+    merge(unit_path.as_ref(), &find_dropins(unit_path.as_ref()))
+}
+
+impl SystemdFile {
+    /// Applies a drop-in on top of this file. Assignments are added to the section of the same name, which
+    /// is created if needed. An empty assignment such as `ExecStart=` removes every earlier assignment of
+    /// that directive instead. Call `refresh` afterwards to update the typed sections.
+    pub fn apply_dropin(&mut self, dropin: SystemdFile, source: &str) {
+        // the merged file no longer reads like the unit file
+        self.cst = None;
+        for dropin_section in dropin.sections {
+            for mut entry in dropin_section.entries {
+                if entry.value.is_empty() {
+                    for section in self.sections.iter_mut().filter(|s| s.name == dropin_section.name) {
+                        section.entries.retain(|e| e.key != entry.key);
+                    }
+                    continue;
+                }
+
+                entry.source.get_or_insert_with(|| source.to_string());
+                match self.sections.iter_mut().rev().find(|s| s.name == dropin_section.name) {
+                    Some(section) => section.entries.push(entry),
+                    None => self.sections.push(Section {
+                        name: dropin_section.name.clone(),
+                        line: dropin_section.line,
+                        entries: vec![entry],
+                        ..Section::default()
+                    }),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::parse_str;
+    use super::super::testing::tree;
+
+    fn merged(unit: &str, dropins: &[&str]) -> SystemdFile {
+        let mut file = parse_str(unit).unwrap();
+        for (i, dropin) in dropins.iter().enumerate() {
+            file.apply_dropin(parse_str(dropin).unwrap(), &format!("{i}.conf"));
+        }
+        file.refresh();
+
+        file
+    }
+
+    fn values<'a>(file: &'a SystemdFile, section: &str, key: &str) -> Vec<&'a str> {
+        file.section(section).map(|s| s.get_all(key)).unwrap_or_default()
+    }
+
+    #[test]
+    fn an_empty_assignment_resets_what_came_before() {
+        let file = merged("[Service]\nExecStart=/bin/a\nExecStartPre=/bin/pre\n", &[
+            "[Service]\nExecStart=\nExecStart=/bin/b\n",
+            "[Service]\nExecStartPre=/bin/pre2\n",
+        ]);
+        assert_eq!(values(&file, "Service", "ExecStart"), ["/bin/b"]);
+        assert_eq!(values(&file, "Service", "ExecStartPre"), ["/bin/pre", "/bin/pre2"]);
+        assert_eq!(file.service.unwrap().exec_start.len(), 1);
+    }
+
+    #[test]
+    fn a_later_dropin_resets_an_earlier_one() {
+        let file = merged("[Unit]\nAfter=a.service\n", &[
+            "[Unit]\nAfter=b.service\n",
+            "[Unit]\nAfter=\nAfter=c.service\n",
+        ]);
+        assert_eq!(values(&file, "Unit", "After"), ["c.service"]);
+    }
+
+    #[test]
+    fn dropins_add_sections_and_record_their_source() {
+        let file = merged("[Unit]\nDescription=a\n", &["[Service]\nRestart=always\n"]);
+        let entry = &file.section("Service").unwrap().entries[0];
+        assert_eq!(entry.source.as_deref(), Some("0.conf"));
+        assert!(file.cst.is_none());
+    }
+
+    #[test]
+    fn dropin_dirs_go_from_the_least_to_the_most_specific() {
+        assert_eq!(dropin_dir_names("foo-bar-baz.service"), [
+            "service.d", "foo-.service.d", "foo-bar-.service.d", "foo-bar-baz.service.d",
+        ]);
+        assert_eq!(dropin_dir_names("foo-bar@baz.service"), [
+            "service.d", "foo-.service.d", "foo-bar@.service.d", "foo-bar@baz.service.d",
+        ]);
+        assert_eq!(dropin_dir_names("foo"), ["foo.d"]);
+    }
+
+    #[test]
+    fn dropins_apply_in_the_order_of_their_names() {
+        let dir = tree("dropin-order", &[
+            ("a.service", "[Service]\nExecStart=/bin/a\n"),
+            ("service.d/20-all.conf", "[Service]\nRestart=always\n"),
+            ("service.d/10-same.conf", "[Service]\nRestart=no\n"),
+            ("a.service.d/10-same.conf", "[Service]\nRestart=on-failure\n"),
+        ]);
+
+        let dropins = find_dropins(dir.join("a.service"));
+        assert_eq!(dropins, [dir.join("a.service.d/10-same.conf"), dir.join("service.d/20-all.conf")]);
+        let file = parse_with_dropins(dir.join("a.service")).unwrap();
+        assert_eq!(file.section("Service").and_then(|s| s.get("Restart")), Some("always"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,374 @@
+use std::fmt::Display;
+use std::ops::Range;
+use crate::parser::error::{Error, Location};
+use super::cst::{Cst, Line, LineKind};
+use super::syntax::continued;
+use super::{parse_str_recovering, SystemdFile};
+
+/// Editing a unit file. Every change is made to the lines of the `cst`, so comments, blank lines and the
+/// formatting of the lines that aren't touched stay exactly as they were; the typed sections are rebuilt
+/// afterwards. A file without a `cst`, e.g. one read from JSON, is edited as it would be written out.
+impl SystemdFile {
+    /// Makes `value` the only assignment of `key` in `section`. The last existing assignment is rewritten
+    /// in place and the others are removed; without one the assignment is appended as by `append`.
+    pub fn set(&mut self, section: &str, key: &str, value: &str) -> Result<(), Error> {
+        check_assignment(section, key, value)?;
+        let cst = self.cst_mut();
+
+        let assignments = assignment_indices(cst, section, key);
+        match assignments.split_last() {
+            Some((last, earlier)) => {
+                cst.lines[*last].text = assignment_text(&cst.lines[*last].text, key, value);
+                for i in earlier.iter().rev() {
+                    cst.lines.remove(*i);
+                }
+            }
+            None => append_line(cst, section, key, value),
+        }
+
+        self.resync();
+        Ok(())
+    }
+
+    /// Adds an assignment after the last directive of the last `section`, which is created at the end of
+    /// the file if there is none. Use this for directives that take more than one value, like `After=`.
+    pub fn append(&mut self, section: &str, key: &str, value: &str) -> Result<(), Error> {
+        check_assignment(section, key, value)?;
+        append_line(self.cst_mut(), section, key, value);
+
+        self.resync();
+        Ok(())
+    }
+
+    /// Removes every assignment of `key` in `section` and returns how many there were.
+    pub fn remove(&mut self, section: &str, key: &str) -> usize {
+        let cst = self.cst_mut();
+        let assignments = assignment_indices(cst, section, key);
+        for i in assignments.iter().rev() {
+            cst.lines.remove(*i);
+        }
+
+        if !assignments.is_empty() {
+            self.resync();
+        }
+        assignments.len()
+    }
+
+    /// Replaces every assignment of `key` in `section` with an empty one, which in a drop-in clears the
+    /// values the unit file and earlier drop-ins gave it.
+    pub fn reset(&mut self, section: &str, key: &str) -> Result<(), Error> {
+        self.set(section, key, "")
+    }
+
+    /// Adds an empty `[name]` section at the end of the file unless it already has one. Returns whether the
+    /// section was added.
+    pub fn insert_section(&mut self, name: &str) -> Result<bool, Error> {
+        check_section_name(name)?;
+        let cst = self.cst_mut();
+        if !section_ranges(cst, name).is_empty() {
+            return Ok(false);
+        }
+        push_section(cst, name);
+
+        self.resync();
+        Ok(true)
+    }
+
+    /// Carries changes made to the typed sections over to the file, which is otherwise written out from the
+    /// `cst` or `sections` as they were. This is what makes editing e.g. `service.restart` in the JSON of a
+    /// parsed file work. Directives whose typed values differ from what the file gives them are rewritten
+    /// with `set`, or with `remove` and `append` when they have several; everything else stays as it is.
+    pub fn apply_typed_edits(&mut self) -> Result<(), Error> {
+        if self.sections.is_empty() && self.cst.is_none() {
+            // written out from the typed sections anyway
+            return Ok(());
+        }
+        let unedited = SystemdFile::from_sections(self.sections.clone());
+
+        let mut edits = Vec::new();
+        for ((name, edited), (_, before)) in typed_sections(self).into_iter().zip(typed_sections(&unedited)) {
+            if edited == before {
+                continue;
+            }
+            let (edited, before) = (rendered_values(name, &edited), rendered_values(name, &before));
+            for (key, values) in &edited {
+                if before.iter().find(|(k, _)| k == key).map(|(_, v)| v) != Some(values) {
+                    edits.push((name, key.clone(), values.clone()));
+                }
+            }
+            for (key, _) in before.iter().filter(|(key, _)| !edited.iter().any(|(k, _)| k == key)) {
+                edits.push((name, key.clone(), vec![]));
+            }
+        }
+
+        for (section, key, values) in edits {
+            match values.as_slice() {
+                [value] => self.set(section, &key, value)?,
+                values => {
+                    self.remove(section, &key);
+                    for value in values {
+                        self.append(section, &key, value)?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Lays the file out the usual way, see `Cst::formatted`.
+    pub fn format(&mut self) {
+        let formatted = self.cst_mut().formatted();
+        self.cst = Some(formatted);
+        self.resync();
+    }
+
+    fn cst_mut(&mut self) -> &mut Cst {
+        if self.cst.is_none() {
+            let (written, _) = parse_str_recovering(&self.to_string());
+            self.cst = written.cst;
+        }
+        self.cst.get_or_insert_with(Cst::default)
+    }
+
+    /// Rebuilds the sections from the edited `cst`. Lines the recovering parser skipped were there before
+    /// the edit, since every edit is checked, so its errors are of no interest here.
+    fn resync(&mut self) {
+        let text = self.cst.as_ref().map(|cst| cst.to_string()).unwrap_or_default();
+        let (mut file, _) = parse_str_recovering(&text);
+        file.unit_type = self.unit_type.or(file.unit_type);
+        *self = file;
+    }
+}
+
+/// Every typed section of `file` by name, written out the way `Display` does. Absent ones are empty.
+fn typed_sections(file: &SystemdFile) -> Vec<(&'static str, String)> {
+    fn rendered<T: Display>(section: &Option<T>) -> String {
+        section.as_ref().map(|s| s.to_string()).unwrap_or_default()
+    }
+
+    vec![
+        ("Unit", file.unit.to_string()),
+        ("Service", rendered(&file.service)),
+        ("Socket", rendered(&file.socket)),
+        ("Timer", rendered(&file.timer)),
+        ("Mount", rendered(&file.mount)),
+        ("Automount", rendered(&file.automount)),
+        ("Path", rendered(&file.path)),
+        ("Swap", rendered(&file.swap)),
+        ("Slice", rendered(&file.slice)),
+        ("Scope", rendered(&file.scope)),
+        ("Install", file.install.to_string()),
+    ]
+}
+
+/// The values of every directive in a typed section written out by `typed_sections`, by key in the order
+/// they first appear.
+fn rendered_values(name: &str, rendered: &str) -> Vec<(String, Vec<String>)> {
+    // the header is missing from typed sections built by hand
+    let (file, _) = parse_str_recovering(&format!("[{name}]\n{rendered}"));
+    let mut values: Vec<(String, Vec<String>)> = Vec::new();
+    for entry in file.sections.iter().flat_map(|s| s.entries.iter()) {
+        match values.iter_mut().find(|(key, _)| *key == entry.key) {
+            Some((_, values)) => values.push(entry.value.clone()),
+            None => values.push((entry.key.clone(), vec![entry.value.clone()])),
+        }
+    }
+
+    values
+}
+
+/// Index ranges of the lines of every section called `name`, each starting at its header.
+fn section_ranges(cst: &Cst, name: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut start: Option<usize> = None;
+    for (i, line) in cst.lines.iter().enumerate() {
+        if let LineKind::SectionHeader(header) = &line.kind {
+            if let Some(start) = start.take() {
+                ranges.push(start..i);
+            }
+            if header == name {
+                start = Some(i);
+            }
+        }
+    }
+    if let Some(start) = start {
+        ranges.push(start..cst.lines.len());
+    }
+
+    ranges
+}
+
+fn assignment_indices(cst: &Cst, section: &str, key: &str) -> Vec<usize> {
+    section_ranges(cst, section).into_iter()
+        .flatten()
+        .filter(|i| cst.lines[*i].kind == LineKind::Entry(key.to_string()))
+        .collect()
+}
+
+fn append_line(cst: &mut Cst, section: &str, key: &str, value: &str) {
+    let range = match section_ranges(cst, section).pop() {
+        Some(range) => range,
+        None => push_section(cst, section),
+    };
+    // after the last directive, so that comments and blank lines ending the section stay where they are
+    let after = range.clone().rev()
+        .find(|i| matches!(cst.lines[*i].kind, LineKind::Entry(_)))
+        .unwrap_or(range.start);
+
+    let text = format!("{key}={value}{}", line_end(cst));
+    cst.lines.insert(after + 1, Line { kind: LineKind::Entry(key.to_string()), text });
+}
+
+/// Adds a `[name]` header at the end, separated from what comes before by a blank line, and returns the
+/// range of the new section.
+fn push_section(cst: &mut Cst, name: &str) -> Range<usize> {
+    let end = line_end(cst);
+    if cst.lines.iter().all(|line| line.kind == LineKind::Blank) {
+        // an empty file gets the usual line break at the end
+        cst.lines.clear();
+        cst.final_newline = true;
+    } else if cst.lines.last().is_some_and(|line| line.kind != LineKind::Blank) {
+        cst.lines.push(Line { kind: LineKind::Blank, text: end.to_string() });
+    }
+    cst.lines.push(Line { kind: LineKind::SectionHeader(name.to_string()), text: format!("[{name}]{end}") });
+
+    cst.lines.len() - 1..cst.lines.len()
+}
+
+/// What new lines of `cst` end in before their line break, see `Cst::uses_crlf`.
+fn line_end(cst: &Cst) -> &'static str {
+    if cst.uses_crlf() {
+        "\r"
+    } else {
+        ""
+    }
+}
+
+/// Rewrites the assignment `text` to give `key` the new value, keeping its indentation, the separator
+/// with the whitespace around it and a `\r` at the end.
+fn assignment_text(text: &str, key: &str, value: &str) -> String {
+    let (text, end) = match text.strip_suffix('\r') {
+        Some(text) => (text, "\r"),
+        None => (text, ""),
+    };
+    let trimmed = text.trim_start();
+    let indent = &text[..text.len() - trimmed.len()];
+    let rest = trimmed.strip_prefix(key).unwrap_or_default();
+    let separator = match rest.find(['=', ':']) {
+        Some(i) => {
+            let after = &rest[i + 1..];
+            &rest[..rest.len() - after.trim_start().len()]
+        }
+        None => "=",
+    };
+    let separator = if value.is_empty() { separator.trim_end() } else { separator };
+
+    format!("{indent}{key}{separator}{value}{end}")
+}
+
+fn check_section_name(name: &str) -> Result<(), Error> {
+    if name.is_empty() || name.contains(['[', ']', '\n', '\r']) {
+        return Err(Error::Syntax {
+            location: Box::new(Location::default()),
+            message: format!("invalid section name `{name}`"),
+        });
+    }
+
+    Ok(())
+}
+
+fn check_assignment(section: &str, key: &str, value: &str) -> Result<(), Error> {
+    check_section_name(section)?;
+    let message = if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c)) {
+        "invalid directive name"
+    } else if value.contains(['\n', '\r']) {
+        "values can't contain line breaks"
+    } else if continued(value).is_some() {
+        // it would continue the assignment onto the next line
+        "values can't end in a backslash, write `\\\\` for a literal one"
+    } else {
+        return Ok(());
+    };
+
+    Err(Error::InvalidValue {
+        location: Box::new(Location::default()),
+        key: key.to_string(),
+        value: value.to_string(),
+        message: message.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{parse_str, RestartPolicy, SystemdFile};
+
+    const UNIT: &str = "[Service]\nExecStart=/bin/a\nType=simple\n";
+
+    #[test]
+    fn set_rejects_a_trailing_backslash() {
+        let mut file = parse_str(UNIT).unwrap();
+        assert!(file.set("Service", "ExecStart", "/bin/b \\").is_err());
+        assert_eq!(file.to_string(), UNIT);
+    }
+
+    #[test]
+    fn set_keeps_an_escaped_trailing_backslash() {
+        let mut file = parse_str(UNIT).unwrap();
+        file.set("Service", "ExecStart", "/bin/b \\\\").unwrap();
+        assert_eq!(file.to_string(), "[Service]\nExecStart=/bin/b \\\\\nType=simple\n");
+        assert_eq!(file.section("Service").and_then(|s| s.get("Type")), Some("simple"));
+    }
+
+    #[test]
+    fn set_rewrites_the_last_assignment_and_removes_the_others() {
+        let mut file = parse_str("[Unit]\nAfter=a.service\n# keep\nAfter = b.service\n").unwrap();
+        file.set("Unit", "After", "c.service").unwrap();
+        assert_eq!(file.to_string(), "[Unit]\n# keep\nAfter = c.service\n");
+    }
+
+    #[test]
+    fn append_adds_a_missing_section() {
+        let mut file = parse_str(UNIT).unwrap();
+        file.append("Install", "WantedBy", "multi-user.target").unwrap();
+        assert_eq!(file.to_string(), format!("{UNIT}\n[Install]\nWantedBy=multi-user.target\n"));
+    }
+    #[test]
+    fn edits_keep_windows_line_breaks() {
+        let mut file = parse_str("[Service]\r\nExecStart=/bin/a\r\nType=simple\r\n").unwrap();
+        file.set("Service", "Type", "oneshot").unwrap();
+        file.append("Service", "ExecStartPost", "/bin/b").unwrap();
+        file.insert_section("Install").unwrap();
+        file.append("Install", "WantedBy", "multi-user.target").unwrap();
+        assert_eq!(
+            file.to_string(),
+            "[Service]\r\nExecStart=/bin/a\r\nType=oneshot\r\nExecStartPost=/bin/b\r\n\r\n[Install]\r\nWantedBy=multi-user.target\r\n",
+        );
+        assert_eq!(file.section("Service").and_then(|s| s.get("Type")), Some("oneshot"));
+    }
+
+    #[test]
+    fn edits_to_the_typed_sections_of_json_are_written_out() {
+        let source = "[Unit]\nAfter=a.service\n\n[Service]\n# restart on crashes\nRestart=on-failure\nX-Custom=1\nExecStart=/bin/a\n";
+        let mut json = serde_json::to_value(parse_str(source).unwrap()).unwrap();
+        json["service"]["restart"] = "always".into();
+        json["unit"]["after"] = serde_json::json!(["a.service", "b.service"]);
+
+        let mut file: SystemdFile = serde_json::from_value(json).unwrap();
+        file.apply_typed_edits().unwrap();
+        assert_eq!(
+            file.to_string(),
+            "[Unit]\nAfter=a.service\nAfter=b.service\n\n[Service]\n# restart on crashes\nRestart=always\nX-Custom=1\nExecStart=/bin/a\n\n",
+        );
+        assert_eq!(file.service.unwrap().restart, Some(RestartPolicy::Always));
+    }
+
+    #[test]
+    fn unedited_json_is_written_out_as_before() {
+        let json = serde_json::to_string(&parse_str("[Service]\nExecStart=/bin/a\nTimeoutSec=90\n").unwrap()).unwrap();
+        let mut file: SystemdFile = serde_json::from_str(&json).unwrap();
+        let before = file.to_string();
+        file.apply_typed_edits().unwrap();
+        assert_eq!(file.to_string(), before);
+    }
+}
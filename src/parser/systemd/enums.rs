@@ -0,0 +1,347 @@
+//! Directives that take one of a fixed set of keywords.
+
+use serde_derive::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// `Type=` of a service, see systemd.service(5).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all="kebab-case")]
+pub enum ServiceType {
+    Simple,
+    Exec,
+    Forking,
+    Oneshot,
+    Dbus,
+    Notify,
+    NotifyReload,
+    Idle,
+}
+
+impl ServiceType {
+    pub const ALL: [ServiceType; 8] = [
+        ServiceType::Simple, ServiceType::Exec, ServiceType::Forking, ServiceType::Oneshot, ServiceType::Dbus,
+        ServiceType::Notify, ServiceType::NotifyReload, ServiceType::Idle,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ServiceType::Simple => "simple",
+            ServiceType::Exec => "exec",
+            ServiceType::Forking => "forking",
+            ServiceType::Oneshot => "oneshot",
+            ServiceType::Dbus => "dbus",
+            ServiceType::Notify => "notify",
+            ServiceType::NotifyReload => "notify-reload",
+            ServiceType::Idle => "idle",
+        }
+    }
+}
+
+impl Display for ServiceType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for ServiceType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ServiceType::ALL.into_iter()
+            .find(|t| t.as_str() == s)
+            .ok_or_else(|| unknown_keyword("service type", s, ServiceType::ALL.map(|t| t.as_str())))
+    }
+}
+
+/// `Restart=` of a service, see systemd.service(5).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all="kebab-case")]
+pub enum RestartPolicy {
+    No,
+    OnSuccess,
+    OnFailure,
+    OnAbnormal,
+    OnWatchdog,
+    OnAbort,
+    Always,
+}
+
+impl RestartPolicy {
+    pub const ALL: [RestartPolicy; 7] = [
+        RestartPolicy::No, RestartPolicy::OnSuccess, RestartPolicy::OnFailure, RestartPolicy::OnAbnormal,
+        RestartPolicy::OnWatchdog, RestartPolicy::OnAbort, RestartPolicy::Always,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RestartPolicy::No => "no",
+            RestartPolicy::OnSuccess => "on-success",
+            RestartPolicy::OnFailure => "on-failure",
+            RestartPolicy::OnAbnormal => "on-abnormal",
+            RestartPolicy::OnWatchdog => "on-watchdog",
+            RestartPolicy::OnAbort => "on-abort",
+            RestartPolicy::Always => "always",
+        }
+    }
+}
+
+impl Display for RestartPolicy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for RestartPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        RestartPolicy::ALL.into_iter()
+            .find(|p| p.as_str() == s)
+            .ok_or_else(|| unknown_keyword("restart policy", s, RestartPolicy::ALL.map(|p| p.as_str())))
+    }
+}
+
+/// `KillMode=`, see systemd.kill(5).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all="kebab-case")]
+pub enum KillMode {
+    ControlGroup,
+    Mixed,
+    Process,
+    None,
+}
+
+impl KillMode {
+    pub const ALL: [KillMode; 4] = [KillMode::ControlGroup, KillMode::Mixed, KillMode::Process, KillMode::None];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KillMode::ControlGroup => "control-group",
+            KillMode::Mixed => "mixed",
+            KillMode::Process => "process",
+            KillMode::None => "none",
+        }
+    }
+}
+
+impl Display for KillMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for KillMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        KillMode::ALL.into_iter()
+            .find(|m| m.as_str() == s)
+            .ok_or_else(|| unknown_keyword("kill mode", s, KillMode::ALL.map(|m| m.as_str())))
+    }
+}
+
+/// Where `StandardOutput=` and `StandardError=` connect a process to, see systemd.exec(5).
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum StandardOutput {
+    Inherit,
+    Null,
+    Tty,
+    Journal,
+    Kmsg,
+    JournalConsole,
+    KmsgConsole,
+    /// `file:path`: the file is opened for writing from its start, without truncating it.
+    File(String),
+    /// `append:path`
+    Append(String),
+    /// `truncate:path`
+    Truncate(String),
+    Socket,
+    /// `fd:name`, a file descriptor passed by a socket unit. `fd` alone is the name `stdout`.
+    Fd(String),
+    /// `syslog`, which systemd treats as `journal` nowadays.
+    Syslog,
+    /// `syslog+console`, which systemd treats as `journal+console` nowadays.
+    SyslogConsole,
+}
+
+/// Keywords of `StandardOutput` that don't take an argument.
+const OUTPUT_KEYWORDS: [(&str, StandardOutput); 10] = [
+    ("inherit", StandardOutput::Inherit),
+    ("null", StandardOutput::Null),
+    ("tty", StandardOutput::Tty),
+    ("journal", StandardOutput::Journal),
+    ("kmsg", StandardOutput::Kmsg),
+    ("journal+console", StandardOutput::JournalConsole),
+    ("kmsg+console", StandardOutput::KmsgConsole),
+    ("socket", StandardOutput::Socket),
+    ("syslog", StandardOutput::Syslog),
+    ("syslog+console", StandardOutput::SyslogConsole),
+];
+
+impl Display for StandardOutput {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StandardOutput::Inherit => write!(f, "inherit"),
+            StandardOutput::Null => write!(f, "null"),
+            StandardOutput::Tty => write!(f, "tty"),
+            StandardOutput::Journal => write!(f, "journal"),
+            StandardOutput::Kmsg => write!(f, "kmsg"),
+            StandardOutput::JournalConsole => write!(f, "journal+console"),
+            StandardOutput::KmsgConsole => write!(f, "kmsg+console"),
+            StandardOutput::File(path) => write!(f, "file:{path}"),
+            StandardOutput::Append(path) => write!(f, "append:{path}"),
+            StandardOutput::Truncate(path) => write!(f, "truncate:{path}"),
+            StandardOutput::Socket => write!(f, "socket"),
+            StandardOutput::Fd(name) => write!(f, "fd:{name}"),
+            StandardOutput::Syslog => write!(f, "syslog"),
+            StandardOutput::SyslogConsole => write!(f, "syslog+console"),
+        }
+    }
+}
+
+impl FromStr for StandardOutput {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some((kind, argument)) = s.split_once(':') {
+            let path = || {
+                // specifiers such as %t expand to absolute paths too
+                if argument.starts_with('/') || argument.starts_with('%') {
+                    Ok(argument.to_string())
+                } else {
+                    Err(format!("`{kind}:` needs an absolute path, got `{argument}`"))
+                }
+            };
+            return match kind {
+                "file" => path().map(StandardOutput::File),
+                "append" => path().map(StandardOutput::Append),
+                "truncate" => path().map(StandardOutput::Truncate),
+                "fd" if !argument.is_empty() => Ok(StandardOutput::Fd(argument.to_string())),
+                "fd" => Err("`fd:` needs a file descriptor name".to_string()),
+                _ => Err(unknown_keyword("output kind", kind, ["file", "append", "truncate", "fd"])),
+            };
+        }
+        if s == "fd" {
+            return Ok(StandardOutput::Fd("stdout".to_string()));
+        }
+
+        OUTPUT_KEYWORDS.iter()
+            .find(|(name, _)| *name == s)
+            .map(|(_, output)| output.clone())
+            .ok_or_else(|| unknown_keyword("output", s, OUTPUT_KEYWORDS.map(|(name, _)| name)))
+    }
+}
+
+impl TryFrom<String> for StandardOutput {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<StandardOutput> for String {
+    fn from(output: StandardOutput) -> Self {
+        output.to_string()
+    }
+}
+
+/// Error message for a keyword that isn't one of `expected`, suggesting the closest one when the value
+/// looks like a typo of it.
+pub(super) fn unknown_keyword<const N: usize>(what: &str, value: &str, expected: [&str; N]) -> String {
+    let closest = expected.iter()
+        .map(|keyword| (edit_distance(value, keyword), keyword))
+        .filter(|(distance, _)| *distance <= 2)
+        .min();
+
+    match closest {
+        Some((_, keyword)) => format!("unknown {what} `{value}`, did you mean `{keyword}`?"),
+        None => format!("unknown {what} `{value}`, expected one of {}", expected.join(", ")),
+    }
+}
+
+/// Levenshtein distance between `a` and `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{parse_str, validate_str};
+
+    #[test]
+    fn keywords_parse_and_display_the_same() {
+        for t in ServiceType::ALL {
+            assert_eq!(t.to_string().parse(), Ok(t));
+        }
+        for p in RestartPolicy::ALL {
+            assert_eq!(p.to_string().parse(), Ok(p));
+        }
+        for m in KillMode::ALL {
+            assert_eq!(m.to_string().parse(), Ok(m));
+        }
+        assert_eq!(serde_json::to_string(&ServiceType::NotifyReload).unwrap(), "\"notify-reload\"");
+        assert_eq!(serde_json::to_string(&RestartPolicy::OnFailure).unwrap(), "\"on-failure\"");
+        assert_eq!(serde_json::to_string(&KillMode::ControlGroup).unwrap(), "\"control-group\"");
+    }
+
+    #[test]
+    fn typos_suggest_the_closest_keyword() {
+        assert_eq!(
+            "on-fialure".parse::<RestartPolicy>(),
+            Err("unknown restart policy `on-fialure`, did you mean `on-failure`?".to_string()),
+        );
+        assert_eq!(
+            "sometimes".parse::<KillMode>(),
+            Err("unknown kill mode `sometimes`, expected one of control-group, mixed, process, none".to_string()),
+        );
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("same", "same"), 0);
+    }
+
+    #[test]
+    fn misspelled_restart_policies_are_reported() {
+        let errors = validate_str("[Service]\nExecStart=/bin/a\nRestart=on-fialure\n");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].location().map(|l| l.line), Some(3));
+        assert!(errors[0].message().contains("did you mean `on-failure`?"));
+
+        let service = parse_str("[Service]\nType=notify\nRestart=on-failure\n").unwrap().service.unwrap();
+        assert_eq!(service.to_string(), "[Service]\nType=notify\nRestart=on-failure\n\n");
+    }
+
+    #[test]
+    fn outputs_take_paths_and_file_descriptors() {
+        let output = |s: &str| s.parse::<StandardOutput>();
+        assert_eq!(output("journal+console"), Ok(StandardOutput::JournalConsole));
+        assert_eq!(output("file:/var/log/a.log"), Ok(StandardOutput::File("/var/log/a.log".to_string())));
+        assert_eq!(output("append:%t/a.log"), Ok(StandardOutput::Append("%t/a.log".to_string())));
+        assert_eq!(output("truncate:/tmp/a"), Ok(StandardOutput::Truncate("/tmp/a".to_string())));
+        assert_eq!(output("fd"), Ok(StandardOutput::Fd("stdout".to_string())));
+        assert_eq!(output("fd:log"), Ok(StandardOutput::Fd("log".to_string())));
+        assert_eq!(output("file:a.log"), Err("`file:` needs an absolute path, got `a.log`".to_string()));
+        assert_eq!(output("fd:"), Err("`fd:` needs a file descriptor name".to_string()));
+        assert_eq!(output("jornal"), Err("unknown output `jornal`, did you mean `journal`?".to_string()));
+        assert!(output("pipe:/a").is_err());
+        for s in ["inherit", "kmsg+console", "file:/a", "append:/a", "fd:log", "syslog+console"] {
+            assert_eq!(output(s).unwrap().to_string(), s);
+        }
+        assert_eq!(serde_json::to_string(&StandardOutput::Append("/a".to_string())).unwrap(), "\"append:/a\"");
+    }
+}
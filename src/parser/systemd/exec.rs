@@ -0,0 +1,290 @@
+use serde_derive::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use super::syntax;
+
+/// Every directive whose value is a command line.
+pub const EXEC_DIRECTIVES: &[&str] = &[
+    "ExecCondition", "ExecStartPre", "ExecStart", "ExecStartPost", "ExecReload", "ExecStopPre", "ExecStop",
+    "ExecStopPost",
+];
+
+/// Special prefixes of the executable path, see "Command lines" in systemd.service(5).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExecPrefix {
+    /// `@`: the second word is passed as argv[0] instead of the path.
+    #[serde(rename = "@")]
+    CustomArgv0,
+    /// `-`: a non-zero exit status is not treated as a failure.
+    #[serde(rename = "-")]
+    IgnoreFailure,
+    /// `:`: environment variables are not substituted.
+    #[serde(rename = ":")]
+    NoEnvironmentExpansion,
+    /// `+`: runs with full privileges, ignoring the sandboxing settings.
+    #[serde(rename = "+")]
+    FullPrivileges,
+    /// `!`: runs with elevated privileges, but the sandboxing settings still apply.
+    #[serde(rename = "!")]
+    ElevatedPrivileges,
+    /// `!!`: like `!`, but only on systems without ambient capability support.
+    #[serde(rename = "!!")]
+    AmbientFallback,
+}
+
+impl Display for ExecPrefix {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let prefix = match self {
+            ExecPrefix::CustomArgv0 => "@",
+            ExecPrefix::IgnoreFailure => "-",
+            ExecPrefix::NoEnvironmentExpansion => ":",
+            ExecPrefix::FullPrivileges => "+",
+            ExecPrefix::ElevatedPrivileges => "!",
+            ExecPrefix::AmbientFallback => "!!",
+        };
+        write!(f, "{prefix}")
+    }
+}
+
+/// A command line of one of the `Exec*` directives.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all="camelCase")]
+pub struct ExecCommand {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub prefixes: Vec<ExecPrefix>,
+    /// The executable to run.
+    pub path: String,
+    /// The arguments passed to the executable, including argv[0].
+    pub argv: Vec<String>,
+}
+
+impl ExecCommand {
+    pub fn has_prefix(&self, prefix: ExecPrefix) -> bool {
+        self.prefixes.contains(&prefix)
+    }
+
+    /// The arguments after argv[0].
+    pub fn args(&self) -> &[String] {
+        self.argv.get(1..).unwrap_or_default()
+    }
+}
+
+impl ExecCommand {
+    /// Builds a command from the words of its command line, the first one with the prefixes.
+    fn from_words(words: Vec<String>) -> Result<Self, String> {
+        let mut words = words.into_iter();
+        let first = words.next().ok_or("missing executable")?;
+
+        let mut prefixes = Vec::new();
+        let mut path = first.as_str();
+        loop {
+            let prefix = if path.starts_with("!!") {
+                ExecPrefix::AmbientFallback
+            } else {
+                match path.chars().next() {
+                    Some('@') => ExecPrefix::CustomArgv0,
+                    Some('-') => ExecPrefix::IgnoreFailure,
+                    Some(':') => ExecPrefix::NoEnvironmentExpansion,
+                    Some('+') => ExecPrefix::FullPrivileges,
+                    Some('!') => ExecPrefix::ElevatedPrivileges,
+                    _ => break,
+                }
+            };
+            if prefixes.contains(&prefix) {
+                return Err(format!("prefix `{prefix}` given more than once"));
+            }
+            path = &path[prefix.to_string().len()..];
+            prefixes.push(prefix);
+        }
+
+        let privileged = prefixes.iter()
+            .filter(|p| matches!(p, ExecPrefix::FullPrivileges | ExecPrefix::ElevatedPrivileges | ExecPrefix::AmbientFallback))
+            .count();
+        if privileged > 1 {
+            return Err("`+`, `!` and `!!` can't be combined".to_string());
+        }
+        if path.is_empty() {
+            return Err("missing executable".to_string());
+        }
+
+        let path = path.to_string();
+        let mut argv: Vec<String> = Vec::new();
+        if prefixes.contains(&ExecPrefix::CustomArgv0) {
+            argv.push(words.next().ok_or("`@` requires an argv[0] after the executable")?);
+        } else {
+            argv.push(path.clone());
+        }
+        argv.extend(words);
+
+        Ok(ExecCommand { prefixes, path, argv })
+    }
+}
+
+impl FromStr for ExecCommand {
+    type Err = String;
+
+    /// Parses a single command line, see `parse_command_lines` for values that may hold several.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut commands = parse_command_lines(s)?.into_iter();
+        match (commands.next(), commands.next()) {
+            (Some(command), None) => Ok(command),
+            (None, _) => Err("missing executable".to_string()),
+            (Some(_), Some(_)) => Err("expected a single command line, not several separated by `;`".to_string()),
+        }
+    }
+}
+
+/// Parses the value of an `Exec*` directive, which may hold several command lines separated by a `;` word.
+/// `\;` and a quoted `;` are an argument rather than a separator, and a `;` at the very end is ignored.
+pub fn parse_command_lines(s: &str) -> Result<Vec<ExecCommand>, String> {
+    let mut commands = Vec::new();
+    let mut words = Vec::new();
+    for raw in raw_words(s) {
+        match raw {
+            ";" => commands.push(ExecCommand::from_words(std::mem::take(&mut words))?),
+            "\\;" => words.push(";".to_string()),
+            raw => words.extend(syntax::split_words(raw)?),
+        }
+    }
+    if !words.is_empty() || commands.is_empty() {
+        commands.push(ExecCommand::from_words(words)?);
+    }
+
+    Ok(commands)
+}
+
+/// Splits a command line into its words as written, with their quotes and escapes.
+fn raw_words(s: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    let mut start: Option<usize> = None;
+    let mut quote: Option<char> = None;
+    let mut chars = s.char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        if c.is_whitespace() && quote.is_none() {
+            if let Some(start) = start.take() {
+                words.push(&s[start..i]);
+            }
+            continue;
+        }
+        start.get_or_insert(i);
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            c if quote == Some(c) => quote = None,
+            '"' | '\'' if quote.is_none() => quote = Some(c),
+            _ => {}
+        }
+    }
+    if let Some(start) = start {
+        words.push(&s[start..]);
+    }
+
+    words
+}
+
+impl Display for ExecCommand {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for prefix in &self.prefixes {
+            write!(f, "{prefix}")?;
+        }
+        write!(f, "{}", syntax::quote_word(&self.path))?;
+
+        let args = if self.has_prefix(ExecPrefix::CustomArgv0) {
+            self.argv.as_slice()
+        } else {
+            self.args()
+        };
+        for arg in args {
+            // a bare `;` would separate command lines
+            match arg.as_str() {
+                ";" => write!(f, " \\;")?,
+                arg => write!(f, " {}", syntax::quote_word(arg))?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::parse_str;
+
+    fn command(s: &str) -> ExecCommand {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn prefixes_are_taken_off_the_path() {
+        use ExecPrefix::*;
+
+        let cases: [(&str, &[ExecPrefix]); 7] = [
+            ("/bin/true", &[]),
+            ("-/bin/true", &[IgnoreFailure]),
+            (":/bin/true", &[NoEnvironmentExpansion]),
+            ("+/bin/true", &[FullPrivileges]),
+            ("!/bin/true", &[ElevatedPrivileges]),
+            ("!!/bin/true", &[AmbientFallback]),
+            ("-:+/bin/true", &[IgnoreFailure, NoEnvironmentExpansion, FullPrivileges]),
+        ];
+        for (s, prefixes) in cases {
+            let command = command(s);
+            assert_eq!((command.prefixes.as_slice(), command.path.as_str()), (prefixes, "/bin/true"), "{s}");
+        }
+
+        let command = command("@-/bin/busybox sh -c true");
+        assert_eq!(command.prefixes, [CustomArgv0, IgnoreFailure]);
+        assert_eq!(command.argv, ["sh", "-c", "true"]);
+        assert_eq!(command.args(), ["-c", "true"]);
+    }
+
+    #[test]
+    fn bad_prefixes_are_rejected() {
+        assert_eq!("--/bin/a".parse::<ExecCommand>(), Err("prefix `-` given more than once".to_string()));
+        assert_eq!("+!/bin/a".parse::<ExecCommand>(), Err("`+`, `!` and `!!` can't be combined".to_string()));
+        assert_eq!("-".parse::<ExecCommand>(), Err("missing executable".to_string()));
+        assert_eq!("".parse::<ExecCommand>(), Err("missing executable".to_string()));
+        assert!("@/bin/a".parse::<ExecCommand>().is_err());
+    }
+
+    #[test]
+    fn quoted_arguments_stay_together() {
+        let command = command(r#"/usr/sbin/syslogd -sl="10.1.1.1" "my arg" 'it''s'"#);
+        assert_eq!(command.path, "/usr/sbin/syslogd");
+        assert_eq!(command.argv, ["/usr/sbin/syslogd", "-sl=10.1.1.1", "my arg", "its"]);
+    }
+
+    #[test]
+    fn commands_display_as_they_parse() {
+        for s in [r#"-/bin/a "my arg" b\\c"#, "@/bin/busybox sh -c true", "!!/bin/a \"\""] {
+            let command = command(s);
+            assert_eq!(command.to_string().parse::<ExecCommand>(), Ok(command), "{s}");
+        }
+        assert_eq!(command(r#"/bin/a -sl="10.1.1.1""#).to_string(), "/bin/a -sl=10.1.1.1");
+    }
+
+    #[test]
+    fn semicolon_words_separate_command_lines() {
+        let commands = parse_command_lines(r#"/bin/a ; -/bin/b x ";" \; y;z ;"#).unwrap();
+        assert_eq!(commands, [command("/bin/a"), command(r#"-/bin/b x ";" \; y;z"#)]);
+        assert_eq!(commands[1].args(), ["x", ";", ";", "y;z"]);
+        assert_eq!(commands[1].to_string(), r"-/bin/b x \; \; y;z");
+        assert_eq!(parse_command_lines("/bin/a ; ; /bin/b"), Err("missing executable".to_string()));
+        assert_eq!(
+            "/bin/a ; /bin/b".parse::<ExecCommand>(),
+            Err("expected a single command line, not several separated by `;`".to_string()),
+        );
+    }
+
+    #[test]
+    fn services_keep_every_command() {
+        let file = parse_str("[Service]\nExecStartPre=/bin/a\nExecStartPre=-/bin/b x ; /bin/d\nExecStart=/bin/c\n").unwrap();
+        let service = file.service.unwrap();
+        let paths: Vec<&str> = service.start_pre.iter().map(|c| c.path.as_str()).collect();
+        assert_eq!(paths, ["/bin/a", "/bin/b", "/bin/d"]);
+        assert_eq!(service.exec_start, [command("/bin/c")]);
+    }
+}
@@ -0,0 +1,167 @@
+//! Drawing a dependency graph, as Graphviz DOT or as a Mermaid flowchart.
+
+use super::graph::{DependencyGraph, DependencyKind, Edge};
+use super::loader::LoadState;
+
+/// Graphviz attributes of the edges of each kind. Requirements are solid, ordering is dashed and
+/// conflicts are red, with the colours `systemd-analyze dot` uses.
+fn dot_style(kind: DependencyKind) -> &'static str {
+    match kind {
+        DependencyKind::Requires | DependencyKind::Requisite | DependencyKind::BindsTo => "color=black",
+        DependencyKind::Wants | DependencyKind::PartOf => "color=grey66",
+        DependencyKind::Conflicts => "color=red",
+        DependencyKind::Before | DependencyKind::After => "color=green, style=dashed",
+    }
+}
+
+/// Mermaid link of the edges of each kind: an arrow for requirements, a dotted arrow for ordering and a
+/// cross for conflicts.
+fn mermaid_link(kind: DependencyKind) -> &'static str {
+    match kind {
+        DependencyKind::Conflicts => "--x",
+        DependencyKind::Before | DependencyKind::After => "-.->",
+        _ => "-->",
+    }
+}
+
+impl DependencyGraph {
+    /// Writes the graph in the DOT language of Graphviz, with every edge labelled by its directive. Units
+    /// that weren't found are drawn dashed and masked ones grey.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph units {\n    rankdir=LR;\n    node [shape=box];\n");
+        for (name, state) in self.units() {
+            let style = match state {
+                LoadState::Loaded => "",
+                LoadState::Masked => " [style=filled, fillcolor=grey80]",
+                LoadState::NotFound => " [style=dashed]",
+            };
+            out.push_str(format!("    {}{style};\n", dot_id(name)).as_str());
+        }
+        for edge in drawn_edges(self) {
+            out.push_str(format!(
+                "    {} -> {} [label={}, {}];\n",
+                dot_id(&edge.from),
+                dot_id(&edge.to),
+                dot_id(edge.kind.as_str()),
+                dot_style(edge.kind),
+            ).as_str());
+        }
+        out.push_str("}\n");
+
+        out
+    }
+
+    /// Writes the graph as a Mermaid flowchart, with every edge labelled by its directive. Units that
+    /// weren't found or are masked get the classes `missing` and `masked`.
+    pub fn to_mermaid(&self) -> String {
+        let mut names: Vec<&str> = self.units().map(|(name, _)| name)
+            .chain(self.edges().flat_map(|e| [e.from.as_str(), e.to.as_str()]))
+            .collect();
+        names.sort();
+        names.dedup();
+        // Mermaid node ids can't contain the dots and dashes of unit names, so units are numbered
+        let node_id = |name: &str| format!("u{}", names.binary_search(&name).unwrap_or_default());
+
+        let mut out = String::from("flowchart LR\n");
+        out.push_str("    classDef missing stroke-dasharray: 5 5\n");
+        out.push_str("    classDef masked fill:#ccc\n");
+        for name in &names {
+            let class = match self.state(name) {
+                Some(LoadState::Loaded) => "",
+                Some(LoadState::Masked) => ":::masked",
+                _ => ":::missing",
+            };
+            out.push_str(format!("    {}[\"{}\"]{class}\n", node_id(name), mermaid_text(name)).as_str());
+        }
+        for Edge { from, to, kind, .. } in drawn_edges(self) {
+            out.push_str(format!("    {} {}|{}| {}\n", node_id(from), mermaid_link(*kind), kind, node_id(to)).as_str());
+        }
+
+        out
+    }
+}
+
+/// The edges to draw: one for each dependency, however many places it is declared in.
+fn drawn_edges(graph: &DependencyGraph) -> impl Iterator<Item = &Edge> + '_ {
+    let mut previous: Option<&Edge> = None;
+    // edges are sorted by units and kind before their source, so repeats are next to each other
+    graph.edges().filter(move |edge| {
+        let repeated = previous.is_some_and(|p| (&p.from, &p.to, p.kind) == (&edge.from, &edge.to, edge.kind));
+        previous = Some(edge);
+        !repeated
+    })
+}
+
+/// Quotes `name` as a DOT identifier.
+fn dot_id(name: &str) -> String {
+    format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Escapes the characters that would end a quoted Mermaid label.
+fn mermaid_text(text: &str) -> String {
+    text.replace('"', "#quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::parse_str;
+
+    fn graph() -> DependencyGraph {
+        let mut graph = DependencyGraph::new();
+        for (name, source) in [
+            ("multi-user.target", "[Unit]\nWants=getty@tty1.service\n"),
+            ("getty@tty1.service", "[Unit]\nRequires=dev-tty1.device\nAfter=dev-tty1.device\nConflicts=rescue.service\n"),
+            ("unrelated.service", "[Unit]\nWants=other.service\n"),
+        ] {
+            graph.add_file(name, &parse_str(source).unwrap());
+        }
+
+        graph
+    }
+
+    #[test]
+    fn dot_styles_edges_by_kind() {
+        let dot = graph().to_dot();
+        assert!(dot.starts_with("digraph units {\n"));
+        assert!(dot.contains("    \"getty@tty1.service\" -> \"dev-tty1.device\" [label=\"Requires\", color=black];\n"));
+        assert!(dot.contains("    \"getty@tty1.service\" -> \"dev-tty1.device\" [label=\"After\", color=green, style=dashed];\n"));
+        assert!(dot.contains("    \"getty@tty1.service\" -> \"rescue.service\" [label=\"Conflicts\", color=red];\n"));
+        assert!(dot.contains("    \"multi-user.target\" -> \"getty@tty1.service\" [label=\"Wants\", color=grey66];\n"));
+        assert!(dot.contains("    \"dev-tty1.device\" [style=dashed];\n"));
+        assert!(dot.contains("    \"multi-user.target\";\n"));
+        assert!(dot.ends_with("}\n"));
+    }
+
+    #[test]
+    fn mermaid_links_edges_by_kind() {
+        let mermaid = graph().reachable_from("multi-user.target").to_mermaid();
+        assert_eq!(mermaid, "\
+flowchart LR
+    classDef missing stroke-dasharray: 5 5
+    classDef masked fill:#ccc
+    u0[\"dev-tty1.device\"]:::missing
+    u1[\"getty@tty1.service\"]
+    u2[\"multi-user.target\"]
+    u1 -->|Requires| u0
+    u1 -.->|After| u0
+    u2 -->|Wants| u1
+");
+    }
+
+    #[test]
+    fn only_what_the_target_pulls_in_is_drawn() {
+        let dot = graph().reachable_from("multi-user.target").to_dot();
+        assert!(dot.contains("\"dev-tty1.device\""));
+        assert!(!dot.contains("unrelated.service"));
+        assert!(!dot.contains("other.service"));
+        assert!(!dot.contains("rescue.service"));
+    }
+
+    #[test]
+    fn names_are_quoted_and_escaped() {
+        assert_eq!(dot_id("foo-bar@a.b.service"), "\"foo-bar@a.b.service\"");
+        assert_eq!(dot_id(r#"a"b\c"#), r#""a\"b\\c""#);
+        assert_eq!(mermaid_text(r#"a"b"#), "a#quot;b");
+    }
+}
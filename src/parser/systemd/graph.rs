@@ -0,0 +1,684 @@
+//! Dependencies between units: who pulls in whom and who starts before whom.
+
+use serde_derive::Serialize;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt::{Display, Formatter};
+use std::fs;
+use crate::parser::error::Error;
+use super::loader::{LoadState, LoadedUnit, UnitLoader};
+use super::name::UnitName;
+use super::specifier::SpecifierContext;
+use super::{syntax, SystemdFile, UnitType};
+
+/// A directive relating one unit to another, see systemd.unit(5).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all="kebab-case")]
+pub enum DependencyKind {
+    Requires,
+    Requisite,
+    Wants,
+    BindsTo,
+    PartOf,
+    Conflicts,
+    Before,
+    After,
+}
+
+impl DependencyKind {
+    pub const ALL: [DependencyKind; 8] = [
+        DependencyKind::Requires, DependencyKind::Requisite, DependencyKind::Wants, DependencyKind::BindsTo,
+        DependencyKind::PartOf, DependencyKind::Conflicts, DependencyKind::Before, DependencyKind::After,
+    ];
+
+    /// The name of the `[Unit]` directive.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DependencyKind::Requires => "Requires",
+            DependencyKind::Requisite => "Requisite",
+            DependencyKind::Wants => "Wants",
+            DependencyKind::BindsTo => "BindsTo",
+            DependencyKind::PartOf => "PartOf",
+            DependencyKind::Conflicts => "Conflicts",
+            DependencyKind::Before => "Before",
+            DependencyKind::After => "After",
+        }
+    }
+
+    /// Whether the dependency is about start order only, rather than about which units are started.
+    pub fn is_ordering(&self) -> bool {
+        matches!(self, DependencyKind::Before | DependencyKind::After)
+    }
+
+    /// Whether starting the unit pulls in or needs the other one.
+    pub fn is_requirement(&self) -> bool {
+        !self.is_ordering() && *self != DependencyKind::Conflicts
+    }
+
+    /// Whether starting the unit starts the other one as well. `Requisite=` only checks that it is already
+    /// started and `PartOf=` only works the other way round.
+    pub fn pulls_in(&self) -> bool {
+        matches!(self, DependencyKind::Requires | DependencyKind::Wants | DependencyKind::BindsTo)
+    }
+}
+
+impl Display for DependencyKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Where an edge of the graph comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all="kebab-case")]
+pub enum EdgeSource {
+    /// A directive in the `[Unit]` section of the unit the edge starts at.
+    Directive,
+    /// `WantedBy=` or `RequiredBy=` in the `[Install]` section of the unit the edge points to. These only
+    /// take effect once the unit is enabled.
+    Install,
+    /// A symlink in a `.wants/` or `.requires/` directory, which is what enabling a unit creates.
+    Symlink,
+    /// Added by systemd itself unless the unit has `DefaultDependencies=no`, see `transaction`.
+    Default,
+}
+
+/// `from` depends on `to` in the way `kind` says, e.g. `from` has `After=to`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all="camelCase")]
+pub struct Edge {
+    pub from: String,
+    pub to: String,
+    pub kind: DependencyKind,
+    pub source: EdgeSource,
+}
+
+impl Edge {
+    /// Whether the edge takes effect at runtime. `[Install]` sections only do something once a unit is
+    /// enabled, and then the symlinks that creates are edges of their own.
+    pub fn is_active(&self) -> bool {
+        self.source != EdgeSource::Install
+    }
+}
+
+/// Something wrong with how the units of a graph relate to each other.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all="kebab-case")]
+pub enum GraphProblem {
+    /// The units are ordered after each other in a circle, each one after the next and the last after the
+    /// first. systemd breaks such cycles by dropping jobs at boot.
+    OrderingCycle { units: Vec<String> },
+    /// A hard requirement on a unit that doesn't exist or is masked, see `DependencyGraph::dangling`.
+    Dangling { edge: Edge, state: LoadState },
+    /// `from` has both dependencies on `to`, which can't both hold: `Before=` and `After=`, or a
+    /// requirement and `Conflicts=`.
+    Contradiction { from: String, to: String, first: DependencyKind, second: DependencyKind },
+}
+
+impl Display for GraphProblem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GraphProblem::OrderingCycle { units } => {
+                write!(f, "ordering cycle: {}", units.join(" -> "))?;
+                match units.first() {
+                    Some(first) => write!(f, " -> {first}"),
+                    None => Ok(()),
+                }
+            }
+            GraphProblem::Dangling { edge, state } => {
+                let state = match state {
+                    LoadState::Masked => "masked",
+                    _ => "not found",
+                };
+                match (edge.source, edge.kind) {
+                    (EdgeSource::Install, DependencyKind::Requires) => {
+                        write!(f, "{} has RequiredBy={} but {} is {state}", edge.to, edge.from, edge.from)
+                    }
+                    (EdgeSource::Install, _) => {
+                        write!(f, "{} has WantedBy={} but {} is {state}", edge.to, edge.from, edge.from)
+                    }
+                    _ => write!(f, "{} has {}={} but {} is {state}", edge.from, edge.kind, edge.to, edge.to),
+                }
+            }
+            GraphProblem::Contradiction { from, to, first, second } => {
+                write!(f, "{from} has both {first}={to} and {second}={to}")
+            }
+        }
+    }
+}
+
+/// Units and the dependencies between them. Ordering edges are kept as written, so `a` with `Before=b`
+/// is an edge from `a` to `b` of kind `Before`; `orderings` gives them all in one direction.
+#[derive(Debug, Default)]
+pub struct DependencyGraph {
+    /// Every unit an edge starts at or points to, by its id.
+    units: BTreeMap<String, LoadState>,
+    /// Alias names of units, mapped to their ids.
+    aliases: BTreeMap<String, String>,
+    edges: BTreeSet<Edge>,
+    /// The files of the units added by `add_unit`, by id.
+    files: BTreeMap<String, SystemdFile>,
+    errors: Vec<Error>,
+}
+
+impl DependencyGraph {
+    pub fn new() -> Self {
+        DependencyGraph::default()
+    }
+
+    /// Builds the graph of every unit on the search path of `loader` and every unit they refer to, the way
+    /// systemd would see it: units are merged with their drop-ins, aliases are followed and the symlinks
+    /// in `.wants/` and `.requires/` directories count as dependencies. Units that can't be parsed are left
+    /// out and reported by `errors`.
+    pub fn from_loader(loader: &UnitLoader) -> DependencyGraph {
+        let mut graph = DependencyGraph::new();
+        let mut found: BTreeSet<String> = BTreeSet::new();
+
+        for dir in loader.unit_dirs() {
+            let entries = match fs::read_dir(&dir) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+            for entry in entries.flatten() {
+                let file_name = entry.file_name().to_string_lossy().into_owned();
+                if let Some((unit, kind)) = dependency_dir(&file_name) {
+                    for link in fs::read_dir(entry.path()).into_iter().flatten().flatten() {
+                        let target = link.file_name().to_string_lossy().into_owned();
+                        if UnitType::from_file_name(&target).is_some() {
+                            found.insert(target.clone());
+                            graph.add_edge(Edge { from: unit.to_string(), to: target, kind, source: EdgeSource::Symlink });
+                        }
+                    }
+                    found.insert(unit.to_string());
+                } else if is_unit_name(&file_name) {
+                    found.insert(file_name);
+                }
+            }
+        }
+
+        let mut queue: VecDeque<String> = found.iter().cloned().collect();
+        while let Some(name) = queue.pop_front() {
+            match loader.load(&name) {
+                Ok(unit) => {
+                    // the units it refers to are loaded in turn
+                    for name in graph.insert_unit(unit) {
+                        if found.insert(name.clone()) {
+                            queue.push_back(name);
+                        }
+                    }
+                }
+                Err(e) => graph.errors.push(e),
+            }
+        }
+        graph.resolve_aliases();
+
+        graph
+    }
+
+    /// Adds a unit found by the loader with the edges of its file, if it was loaded.
+    pub fn add_unit(&mut self, unit: LoadedUnit) {
+        self.insert_unit(unit);
+    }
+
+    /// `add_unit`, returning the names of the other units the edges of its file refer to.
+    fn insert_unit(&mut self, unit: LoadedUnit) -> Vec<String> {
+        if unit.name != unit.id {
+            self.aliases.insert(unit.name.clone(), unit.id.clone());
+        }
+        if self.units.get(&unit.id).is_some_and(|state| *state != LoadState::NotFound) {
+            return vec![];
+        }
+        self.units.insert(unit.id.clone(), unit.state);
+
+        let Some(file) = unit.file else {
+            return vec![];
+        };
+        let edges = file_edges(&unit.id, &file);
+        let referenced = edges.iter()
+            .map(|e| if e.from == unit.id { e.to.clone() } else { e.from.clone() })
+            .collect();
+        for edge in edges {
+            self.add_edge(edge);
+        }
+        self.files.insert(unit.id, file);
+
+        referenced
+    }
+
+    /// Adds the unit `name` as loaded, with an edge for every dependency in its `[Unit]` section and the
+    /// reverse edges of `WantedBy=` and `RequiredBy=` in its `[Install]` section. Specifiers in the names of
+    /// other units are expanded for `name` first; a name with a specifier that can't be expanded, such as
+    /// the unset `%H`, is taken as written.
+    pub fn add_file(&mut self, name: &str, file: &SystemdFile) {
+        self.units.insert(name.to_string(), LoadState::Loaded);
+        for edge in file_edges(name, file) {
+            self.add_edge(edge);
+        }
+    }
+
+    /// Adds an edge. Units it refers to that aren't in the graph yet are added as not found.
+    pub fn add_edge(&mut self, edge: Edge) {
+        for name in [&edge.from, &edge.to] {
+            if !self.units.contains_key(name) && !self.aliases.contains_key(name) {
+                self.units.insert(name.clone(), LoadState::NotFound);
+            }
+        }
+        self.edges.insert(edge);
+    }
+
+    /// Every unit in the graph by id, with whether it could be loaded.
+    pub fn units(&self) -> impl Iterator<Item = (&str, LoadState)> + '_ {
+        self.units.iter().map(|(name, state)| (name.as_str(), *state))
+    }
+
+    /// The state of the unit called `name`, which may be an alias.
+    pub fn state(&self, name: &str) -> Option<LoadState> {
+        self.units.get(self.resolve(name)).copied()
+    }
+
+    /// The file of the unit called `name`, which may be an alias, if it was loaded by `add_unit`.
+    pub fn file(&self, name: &str) -> Option<&SystemdFile> {
+        self.files.get(self.resolve(name))
+    }
+
+    /// The id of the unit `name` is an alias of, or `name` itself.
+    pub fn resolve<'a>(&'a self, name: &'a str) -> &'a str {
+        self.aliases.get(name).map(String::as_str).unwrap_or(name)
+    }
+
+    pub fn edges(&self) -> impl Iterator<Item = &Edge> + '_ {
+        self.edges.iter()
+    }
+
+    pub fn edges_from<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Edge> + 'a {
+        self.edges.iter().filter(move |e| e.from == name)
+    }
+
+    pub fn edges_to<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Edge> + 'a {
+        self.edges.iter().filter(move |e| e.to == name)
+    }
+
+    /// The part of the graph that starting `unit` pulls in: the units reached from it over `Requires=`,
+    /// `Wants=` and `BindsTo=`, including the `.wants/` and `.requires/` symlinks of enabled units, with
+    /// every edge between them. `[Install]` sections of units that aren't enabled don't pull them in.
+    pub fn reachable_from(&self, unit: &str) -> DependencyGraph {
+        let start = self.resolve(unit).to_string();
+        let mut reached: BTreeSet<String> = BTreeSet::from([start.clone()]);
+        let mut queue = VecDeque::from([start]);
+        while let Some(name) = queue.pop_front() {
+            let pulled = self.edges_from(&name).filter(|e| e.is_active() && e.kind.pulls_in());
+            for edge in pulled {
+                if reached.insert(edge.to.clone()) {
+                    queue.push_back(edge.to.clone());
+                }
+            }
+        }
+
+        self.filtered(|e| reached.contains(&e.from) && reached.contains(&e.to), |name| reached.contains(name))
+    }
+
+    /// The graph with only the edges `keep` accepts. Units are kept even if no edge is left to them, their
+    /// files and the errors of the graph aren't.
+    pub fn filter_edges<F: Fn(&Edge) -> bool>(&self, keep: F) -> DependencyGraph {
+        self.filtered(keep, |_| true)
+    }
+
+    fn filtered<E: Fn(&Edge) -> bool, U: Fn(&str) -> bool>(&self, keep_edge: E, keep_unit: U) -> DependencyGraph {
+        DependencyGraph {
+            units: self.units.iter().filter(|(name, _)| keep_unit(name)).map(|(n, s)| (n.clone(), *s)).collect(),
+            aliases: self.aliases.iter().filter(|(_, id)| keep_unit(id)).map(|(a, id)| (a.clone(), id.clone())).collect(),
+            edges: self.edges.iter().filter(|e| keep_edge(e)).cloned().collect(),
+            files: BTreeMap::new(),
+            errors: vec![],
+        }
+    }
+
+    /// Units that couldn't be loaded by `from_loader` because their files are broken.
+    pub fn errors(&self) -> &[Error] {
+        &self.errors
+    }
+
+    /// Every ordering edge as a pair `(earlier, later)`: `a` with `Before=b` and `b` with `After=a` both
+    /// give `(a, b)`.
+    pub fn orderings(&self) -> BTreeSet<(&str, &str)> {
+        self.edges.iter()
+            .filter_map(|e| match e.kind {
+                DependencyKind::Before => Some((e.from.as_str(), e.to.as_str())),
+                DependencyKind::After => Some((e.to.as_str(), e.from.as_str())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Ordering cycles, dangling requirements and contradicting dependencies, in that order. A unit both
+    /// before and after another is only reported as a contradiction, not as a cycle of the two as well.
+    pub fn problems(&self) -> Vec<GraphProblem> {
+        let contradictions = self.contradictions();
+        let contradicting = |units: &[String]| contradictions.iter().any(|c| match c {
+            GraphProblem::Contradiction { from, to, second: DependencyKind::After, .. } => {
+                units.len() == 2 && units.contains(from) && units.contains(to)
+            }
+            _ => false,
+        });
+
+        let mut problems: Vec<GraphProblem> = self.ordering_cycles().into_iter()
+            .filter(|units| !contradicting(units))
+            .map(|units| GraphProblem::OrderingCycle { units })
+            .collect();
+        problems.extend(self.dangling());
+        problems.extend(contradictions);
+
+        problems
+    }
+
+    /// One cycle through each group of units that are ordered after each other in a circle, starting at
+    /// the first unit of the group by name. Each unit is ordered after the next one.
+    pub fn ordering_cycles(&self) -> Vec<Vec<String>> {
+        let mut after: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for (earlier, later) in self.orderings() {
+            after.entry(later).or_default().push(earlier);
+        }
+
+        strongly_connected(&after).into_iter()
+            .filter(|group| group.len() > 1 || after.get(group[0]).is_some_and(|next| next.contains(&group[0])))
+            .filter_map(|group| shortest_cycle(&after, &group))
+            .collect()
+    }
+
+    /// Hard requirements, `Requires=`, `BindsTo=` and `Requisite=` including `RequiredBy=` and `.requires/`
+    /// symlinks, on units that don't exist or are masked. Starting a unit with such a requirement fails.
+    /// `Wants=` on a missing unit is common and harmless, as are ordering and `Conflicts=`, so those aren't
+    /// reported.
+    pub fn dangling(&self) -> Vec<GraphProblem> {
+        self.edges.iter()
+            .filter(|e| matches!(e.kind, DependencyKind::Requires | DependencyKind::BindsTo | DependencyKind::Requisite))
+            .filter_map(|edge| {
+                // a reverse edge from [Install] is dangling if the unit it installs into is missing
+                let missing = if edge.source == EdgeSource::Install { &edge.from } else { &edge.to };
+                match self.state(missing) {
+                    Some(LoadState::Loaded) => None,
+                    state => Some(GraphProblem::Dangling { edge: edge.clone(), state: state.unwrap_or(LoadState::NotFound) }),
+                }
+            })
+            .collect()
+    }
+
+    /// Pairs of units with dependencies on each other that can't both be satisfied: one ordered both before
+    /// and after the other, or one both requiring and conflicting with the other.
+    pub fn contradictions(&self) -> Vec<GraphProblem> {
+        let mut problems = Vec::new();
+        for before in self.edges.iter().filter(|e| e.kind == DependencyKind::Before) {
+            if self.edges_from(&before.from).any(|e| e.to == before.to && e.kind == DependencyKind::After) {
+                problems.push(GraphProblem::Contradiction {
+                    from: before.from.clone(),
+                    to: before.to.clone(),
+                    first: DependencyKind::Before,
+                    second: DependencyKind::After,
+                });
+            }
+        }
+
+        for conflict in self.edges.iter().filter(|e| e.kind == DependencyKind::Conflicts) {
+            let requirement = self.edges_from(&conflict.from)
+                .find(|e| e.to == conflict.to && e.kind.is_requirement());
+            if let Some(requirement) = requirement {
+                problems.push(GraphProblem::Contradiction {
+                    from: conflict.from.clone(),
+                    to: conflict.to.clone(),
+                    first: requirement.kind,
+                    second: DependencyKind::Conflicts,
+                });
+            }
+        }
+
+        problems
+    }
+
+    /// Rewrites edges to refer to units by id rather than by alias.
+    fn resolve_aliases(&mut self) {
+        let edges = std::mem::take(&mut self.edges);
+        self.edges = edges.into_iter()
+            .map(|e| Edge { from: self.resolve(&e.from).to_string(), to: self.resolve(&e.to).to_string(), ..e })
+            .collect();
+        for alias in self.aliases.keys() {
+            if self.units.get(alias) == Some(&LoadState::NotFound) {
+                self.units.remove(alias);
+            }
+        }
+    }
+}
+
+/// The unit and the kind of dependency of a `foo.target.wants` or `foo.target.requires` directory.
+fn dependency_dir(name: &str) -> Option<(&str, DependencyKind)> {
+    let (unit, kind) = match name.strip_suffix(".wants") {
+        Some(unit) => (unit, DependencyKind::Wants),
+        None => (name.strip_suffix(".requires")?, DependencyKind::Requires),
+    };
+
+    is_unit_name(unit).then_some((unit, kind))
+}
+
+/// Whether `name` is the name of a unit, as opposed to a template or some other file.
+fn is_unit_name(name: &str) -> bool {
+    name.parse::<UnitName>().is_ok_and(|n| !n.is_template())
+}
+
+/// The edges of the dependencies of the unit `name` in `file`, see `DependencyGraph::add_file`.
+fn file_edges(name: &str, file: &SystemdFile) -> Vec<Edge> {
+    let context = name.parse::<UnitName>().ok().map(SpecifierContext::system);
+    let expand = |value: String| match &context {
+        Some(context) => context.expand(&value).unwrap_or(value),
+        None => value,
+    };
+
+    let mut edges = Vec::new();
+    for kind in DependencyKind::ALL {
+        for to in unit_list(file, "Unit", kind.as_str()).into_iter().map(expand) {
+            edges.push(Edge { from: name.to_string(), to, kind, source: EdgeSource::Directive });
+        }
+    }
+    let install = &file.install;
+    for (values, kind) in [(&install.wanted_by, DependencyKind::Wants), (&install.required_by, DependencyKind::Requires)] {
+        for from in unit_names(values).into_iter().map(expand) {
+            edges.push(Edge { from, to: name.to_string(), kind, source: EdgeSource::Install });
+        }
+    }
+
+    edges
+}
+
+/// The unit names listed by every `key` in every `section` of `file`. An empty assignment clears the list.
+fn unit_list(file: &SystemdFile, section: &str, key: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    let entries = file.sections.iter()
+        .filter(|s| s.name == section)
+        .flat_map(|s| s.entries.iter())
+        .filter(|e| e.key == key);
+    for entry in entries {
+        if entry.value.is_empty() {
+            names.clear();
+        }
+        names.extend(syntax::split_words(&entry.value).unwrap_or_default());
+    }
+
+    names
+}
+
+/// The unit names in the values of a list directive of a typed section, which may each list several.
+pub(super) fn unit_names(values: &Option<Vec<String>>) -> Vec<String> {
+    values.iter().flatten()
+        .flat_map(|value| syntax::split_words(value).unwrap_or_default())
+        .collect()
+}
+
+/// Tarjan's algorithm: groups of nodes that can all reach each other, each sorted.
+fn strongly_connected<'a>(edges: &BTreeMap<&'a str, Vec<&'a str>>) -> Vec<Vec<&'a str>> {
+    struct State<'a> {
+        index: BTreeMap<&'a str, usize>,
+        low: BTreeMap<&'a str, usize>,
+        stack: Vec<&'a str>,
+        groups: Vec<Vec<&'a str>>,
+    }
+
+    fn visit<'a>(node: &'a str, edges: &BTreeMap<&'a str, Vec<&'a str>>, state: &mut State<'a>) {
+        let index = state.index.len();
+        state.index.insert(node, index);
+        state.low.insert(node, index);
+        state.stack.push(node);
+
+        for next in edges.get(node).into_iter().flatten() {
+            if !state.index.contains_key(next) {
+                visit(next, edges, state);
+                let low = state.low[node].min(state.low[next]);
+                state.low.insert(node, low);
+            } else if state.stack.contains(next) {
+                let low = state.low[node].min(state.index[next]);
+                state.low.insert(node, low);
+            }
+        }
+
+        if state.low[node] == state.index[node] {
+            let mut group = Vec::new();
+            while let Some(member) = state.stack.pop() {
+                group.push(member);
+                if member == node {
+                    break;
+                }
+            }
+            group.sort();
+            state.groups.push(group);
+        }
+    }
+
+    let mut state = State { index: BTreeMap::new(), low: BTreeMap::new(), stack: vec![], groups: vec![] };
+    for node in edges.keys() {
+        if !state.index.contains_key(node) {
+            visit(node, edges, &mut state);
+        }
+    }
+    state.groups.sort();
+
+    state.groups
+}
+
+/// The shortest path from the first node of `group` back to itself, staying within the group.
+fn shortest_cycle(edges: &BTreeMap<&str, Vec<&str>>, group: &[&str]) -> Option<Vec<String>> {
+    let start = *group.first()?;
+    let mut previous: BTreeMap<&str, &str> = BTreeMap::new();
+    let mut queue = VecDeque::from([start]);
+
+    while let Some(node) = queue.pop_front() {
+        for next in edges.get(node).into_iter().flatten().copied().filter(|n| group.contains(n)) {
+            if next == start {
+                let mut cycle = vec![node.to_string()];
+                let mut at = node;
+                while at != start {
+                    at = previous[at];
+                    cycle.push(at.to_string());
+                }
+                cycle.reverse();
+                return Some(cycle);
+            }
+            if !previous.contains_key(next) {
+                previous.insert(next, node);
+                queue.push_back(next);
+            }
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::parse_str;
+
+    fn graph(units: &[(&str, &str)]) -> DependencyGraph {
+        let mut graph = DependencyGraph::new();
+        for (name, source) in units {
+            graph.add_file(name, &parse_str(source).unwrap());
+        }
+
+        graph
+    }
+
+    fn names(graph: &DependencyGraph) -> Vec<&str> {
+        graph.units().map(|(name, _)| name).collect()
+    }
+
+    #[test]
+    fn reachable_from_follows_units_that_are_pulled_in() {
+        let graph = graph(&[
+            ("a.target", "[Unit]\nWants=b.service\nRequisite=c.service\n"),
+            ("b.service", "[Unit]\nRequires=d.service\n"),
+            ("c.service", "[Unit]\nDescription=c\n"),
+            ("d.service", "[Unit]\nDescription=d\n"),
+            ("e.service", "[Install]\nWantedBy=a.target\n"),
+        ]);
+        assert_eq!(names(&graph.reachable_from("a.target")), ["a.target", "b.service", "d.service"]);
+    }
+
+    #[test]
+    fn reachable_from_follows_the_symlinks_of_enabled_units() {
+        let mut graph = graph(&[
+            ("a.target", "[Unit]\nDescription=a\n"),
+            ("e.service", "[Install]\nWantedBy=a.target\n"),
+        ]);
+        graph.add_edge(Edge {
+            from: "a.target".to_string(),
+            to: "e.service".to_string(),
+            kind: DependencyKind::Wants,
+            source: EdgeSource::Symlink,
+        });
+        assert_eq!(names(&graph.reachable_from("a.target")), ["a.target", "e.service"]);
+    }
+
+    #[test]
+    fn finds_ordering_cycles_and_contradictions() {
+        let graph = graph(&[
+            ("a.service", "[Unit]\nAfter=b.service\n"),
+            ("b.service", "[Unit]\nAfter=c.service\n"),
+            ("c.service", "[Unit]\nAfter=a.service\nRequires=d.service\nConflicts=d.service\n"),
+            ("d.service", "[Unit]\nDescription=d\n"),
+        ]);
+        assert_eq!(graph.ordering_cycles(), [vec!["a.service", "b.service", "c.service"]]);
+        assert_eq!(graph.contradictions().len(), 1);
+    }
+
+    #[test]
+    fn references_to_missing_units_dangle() {
+        let graph = graph(&[("a.service", "[Unit]\nRequires=gone.service\n")]);
+        assert_eq!(graph.state("gone.service"), Some(LoadState::NotFound));
+        assert_eq!(graph.dangling().len(), 1);
+    }
+
+    #[test]
+    fn only_hard_requirements_on_missing_units_dangle() {
+        let graph = graph(&[
+            ("a.service", "[Unit]\nWants=w.service\nPartOf=p.service\nAfter=x.service\nBindsTo=b.service\n"),
+            ("c.service", "[Unit]\nRequisite=r.service\n[Install]\nWantedBy=gone.target\nRequiredBy=other.target\n"),
+        ]);
+        let dangling: Vec<String> = graph.dangling().iter().map(|p| p.to_string()).collect();
+        assert_eq!(dangling, [
+            "a.service has BindsTo=b.service but b.service is not found",
+            "c.service has Requisite=r.service but r.service is not found",
+            "c.service has RequiredBy=other.target but other.target is not found",
+        ]);
+    }
+
+    #[test]
+    fn specifiers_in_unit_names_are_expanded_for_instances() {
+        let graph = graph(&[(
+            "worker@eu.service",
+            "[Unit]\nRequires=queue@%i.service\nAfter=queue@%i.service\n\
+             [Service]\nExecStart=/bin/worker --host %H\n[Install]\nWantedBy=workers-%i.target\n",
+        )]);
+        let edges: Vec<_> = graph.edges().map(|e| (e.from.as_str(), e.kind, e.to.as_str())).collect();
+        assert_eq!(edges, [
+            ("worker@eu.service", DependencyKind::Requires, "queue@eu.service"),
+            ("worker@eu.service", DependencyKind::After, "queue@eu.service"),
+            ("workers-eu.target", DependencyKind::Wants, "worker@eu.service"),
+        ]);
+        assert!(graph.state("queue@%i.service").is_none());
+    }
+}
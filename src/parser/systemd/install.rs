@@ -0,0 +1,647 @@
+//! Enabling and disabling units below a root directory the way `systemctl enable` and `systemctl disable`
+//! do, by creating and removing the symlinks their `[Install]` sections ask for, and telling which state
+//! that leaves a unit file in.
+
+use serde_derive::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use crate::parser::error::{Error, Location};
+use super::enums::unknown_keyword;
+use super::graph::unit_names;
+use super::loader::{is_masked, LoadState, UnitLoader};
+use super::name::UnitName;
+use super::specifier::SpecifierContext;
+
+/// Where `systemctl enable` puts the symlinks of system units.
+pub const SYSTEM_CONFIG_DIR: &str = "/etc/systemd/system";
+
+/// Where `systemctl --global enable` puts the symlinks of user units.
+pub const USER_CONFIG_DIR: &str = "/etc/systemd/user";
+
+/// Suffixes of the directories the symlinks of `WantedBy=`, `RequiredBy=` and `UpheldBy=` are put in,
+/// named after the unit they list.
+const DEPENDENCY_SUFFIXES: [&str; 3] = ["wants", "requires", "upholds"];
+
+/// A symlink created or removed. Paths are absolute paths as seen from inside the root.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all="camelCase")]
+pub enum InstallChange {
+    Symlink { path: PathBuf, target: PathBuf },
+    Unlink { path: PathBuf },
+}
+
+impl Display for InstallChange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InstallChange::Symlink { path, target } => {
+                write!(f, "Created symlink {} → {}.", path.display(), target.display())
+            }
+            InstallChange::Unlink { path } => write!(f, "Removed \"{}\".", path.display()),
+        }
+    }
+}
+
+/// The state of a unit file as `systemctl list-unit-files` reports it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all="kebab-case")]
+pub enum UnitFileState {
+    /// Symlinks in `/etc` enable the unit.
+    Enabled,
+    /// Symlinks in `/run` enable the unit until the next reboot.
+    EnabledRuntime,
+    /// The unit file is a symlink in `/etc` to a file outside the search path.
+    Linked,
+    /// The unit file is a symlink in `/run` to a file outside the search path.
+    LinkedRuntime,
+    /// The name is a symlink to a unit file of another name.
+    Alias,
+    /// The unit file is linked to `/dev/null` or empty.
+    Masked,
+    /// The unit file in `/run` is linked to `/dev/null` or empty.
+    MaskedRuntime,
+    /// The unit has no `[Install]` directives and can't be enabled.
+    Static,
+    /// The unit could be enabled but isn't.
+    Disabled,
+    /// The unit is only enabled through `Also=` of other units, or only as instances other than its
+    /// `DefaultInstance=`.
+    Indirect,
+    /// The unit file was written by a generator.
+    Generated,
+    /// The unit file was created at runtime through the manager's API.
+    Transient,
+    /// The unit file can't be loaded.
+    Bad,
+}
+
+impl UnitFileState {
+    pub const ALL: [UnitFileState; 13] = [
+        UnitFileState::Enabled, UnitFileState::EnabledRuntime, UnitFileState::Linked, UnitFileState::LinkedRuntime,
+        UnitFileState::Alias, UnitFileState::Masked, UnitFileState::MaskedRuntime, UnitFileState::Static,
+        UnitFileState::Disabled, UnitFileState::Indirect, UnitFileState::Generated, UnitFileState::Transient,
+        UnitFileState::Bad,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UnitFileState::Enabled => "enabled",
+            UnitFileState::EnabledRuntime => "enabled-runtime",
+            UnitFileState::Linked => "linked",
+            UnitFileState::LinkedRuntime => "linked-runtime",
+            UnitFileState::Alias => "alias",
+            UnitFileState::Masked => "masked",
+            UnitFileState::MaskedRuntime => "masked-runtime",
+            UnitFileState::Static => "static",
+            UnitFileState::Disabled => "disabled",
+            UnitFileState::Indirect => "indirect",
+            UnitFileState::Generated => "generated",
+            UnitFileState::Transient => "transient",
+            UnitFileState::Bad => "bad",
+        }
+    }
+}
+
+impl Display for UnitFileState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for UnitFileState {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        UnitFileState::ALL.into_iter()
+            .find(|state| state.as_str() == s)
+            .ok_or_else(|| unknown_keyword("unit file state", s, UnitFileState::ALL.map(|state| state.as_str())))
+    }
+}
+
+/// A unit file on the search path with its state.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all="camelCase")]
+pub struct UnitFile {
+    pub name: String,
+    /// Where the file was found, as seen from inside the root.
+    pub path: PathBuf,
+    pub state: UnitFileState,
+}
+
+/// Enables and disables units found by a loader, writing to a configuration directory on its search path.
+#[derive(Debug, Clone)]
+pub struct Installer<'a> {
+    loader: &'a UnitLoader,
+    config_dir: PathBuf,
+}
+
+/// A symlink `enable` creates, with the unit file it points to.
+struct Link {
+    path: PathBuf,
+    target: PathBuf,
+}
+
+/// What enabling one unit amounts to.
+struct Plan {
+    /// The unit file, as seen from inside the root.
+    fragment: PathBuf,
+    /// The instance, if an instance name was asked for rather than the template.
+    instance: Option<String>,
+    links: Vec<Link>,
+}
+
+impl<'a> Installer<'a> {
+    /// An installer writing to `config_dir`, an absolute path as seen from inside the root of `loader`,
+    /// usually `SYSTEM_CONFIG_DIR`.
+    pub fn new<P: AsRef<Path>>(loader: &'a UnitLoader, config_dir: P) -> Self {
+        Installer { loader, config_dir: config_dir.as_ref().to_path_buf() }
+    }
+
+    /// Creates the symlinks for `WantedBy=`, `RequiredBy=`, `UpheldBy=` and `Alias=` of every unit in
+    /// `names` and, recursively, of the units their `Also=` lists. A template is enabled with its
+    /// `DefaultInstance=` unless an instance name is given; a template without either only gets its
+    /// aliases. Links that already point to the right unit file are left alone, and nothing is changed if
+    /// any link is in the way of another file.
+    pub fn enable(&self, names: &[&str]) -> Result<Vec<InstallChange>, Error> {
+        let mut links = Vec::new();
+        for plan in self.plans(names)? {
+            links.extend(plan.links);
+        }
+
+        let mut pending = Vec::new();
+        let mut seen: BTreeSet<PathBuf> = BTreeSet::new();
+        for link in links {
+            if !seen.insert(link.path.clone()) {
+                continue;
+            }
+            let host_path = self.loader.rooted(&link.path);
+            match fs::read_link(&host_path) {
+                Ok(existing) if existing == link.target => continue,
+                Err(_) if fs::symlink_metadata(&host_path).is_err() => pending.push(link),
+                _ => {
+                    let e = io::Error::new(io::ErrorKind::AlreadyExists, format!("{} is in the way", link.path.display()));
+                    return Err(Error::from(e).with_file(&link.path.display().to_string()));
+                }
+            }
+        }
+
+        let mut changes = Vec::new();
+        for Link { path, target } in pending {
+            let host_path = self.loader.rooted(&path);
+            if let Some(dir) = host_path.parent() {
+                fs::create_dir_all(dir).map_err(|e| Error::from(e).with_file(&dir.display().to_string()))?;
+            }
+            symlink(&target, &host_path).map_err(|e| Error::from(e).with_file(&path.display().to_string()))?;
+            changes.push(InstallChange::Symlink { path, target });
+        }
+
+        Ok(changes)
+    }
+
+    /// Removes the symlinks `enable` would create for `names` and the units they name in `Also=`, and any
+    /// other symlink in the configuration directory and its `.wants/`, `.requires/` and `.upholds/`
+    /// directories that points to their unit files. Disabling an instance only removes the links of that
+    /// instance, disabling a template those of all its instances.
+    pub fn disable(&self, names: &[&str]) -> Result<Vec<InstallChange>, Error> {
+        let plans = self.plans(names)?;
+        let mut doomed: BTreeSet<PathBuf> = plans.iter()
+            .flat_map(|plan| plan.links.iter().map(|link| link.path.clone()))
+            .collect();
+
+        for (path, target) in self.config_links() {
+            let matches = plans.iter().any(|plan| {
+                let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+                target == plan.fragment && match (&plan.instance, file_name.parse::<UnitName>()) {
+                    (Some(instance), Ok(name)) if name.is_instance() => name.instance.as_deref() == Some(instance.as_str()),
+                    _ => true,
+                }
+            });
+            if matches {
+                doomed.insert(path);
+            }
+        }
+
+        let mut changes = Vec::new();
+        for path in doomed {
+            let host_path = self.loader.rooted(&path);
+            // only symlinks are ever removed, a unit file with the name of an alias stays
+            if fs::symlink_metadata(&host_path).is_ok_and(|m| m.file_type().is_symlink()) {
+                fs::remove_file(&host_path).map_err(|e| Error::from(e).with_file(&path.display().to_string()))?;
+                changes.push(InstallChange::Unlink { path });
+            }
+        }
+
+        Ok(changes)
+    }
+
+    /// Plans every unit in `names` and those reached through `Also=`, each once.
+    fn plans(&self, names: &[&str]) -> Result<Vec<Plan>, Error> {
+        let mut plans = Vec::new();
+        let mut seen: BTreeSet<String> = BTreeSet::new();
+        let mut queue: Vec<String> = names.iter().rev().map(|n| n.to_string()).collect();
+        while let Some(name) = queue.pop() {
+            if !seen.insert(name.clone()) {
+                continue;
+            }
+            let (plan, also) = self.plan(&name)?;
+            plans.push(plan);
+            queue.extend(also.into_iter().rev());
+        }
+
+        Ok(plans)
+    }
+
+    /// The links enabling `name` creates, and the units its `Also=` lists.
+    fn plan(&self, name: &str) -> Result<(Plan, Vec<String>), Error> {
+        let unit_name: UnitName = name.parse().map_err(|message| invalid("unit", name, message))?;
+        let unit = self.loader.load(name)?;
+        let file = match (unit.state, unit.file) {
+            (LoadState::Loaded, Some(file)) => file,
+            (LoadState::Masked, _) => {
+                let e = io::Error::other("the unit is masked");
+                return Err(Error::from(e).with_file(name));
+            }
+            _ => {
+                let e = io::Error::new(io::ErrorKind::NotFound, "no such unit on the search path");
+                return Err(Error::from(e).with_file(name));
+            }
+        };
+        let fragment = unit.fragment_path.as_deref()
+            .map(|path| Path::new("/").join(path.strip_prefix(self.loader.root()).unwrap_or(path)))
+            .unwrap_or_default();
+
+        let instance = match unit_name.instance.as_deref() {
+            Some("") => file.install.default_instance.clone(),
+            instance => instance.map(str::to_string),
+        };
+        // the names the links are given: an instance of a template keeps its name, aliases are followed
+        let link_name = match &instance {
+            Some(instance) => unit_name.with_instance(instance),
+            None => unit.id.parse().unwrap_or(unit_name.clone()),
+        };
+        let context = SpecifierContext::system(link_name.clone());
+        let install = &file.install;
+        let expand = |key: &str, values: &Option<Vec<String>>| -> Result<Vec<String>, Error> {
+            unit_names(values).iter()
+                .map(|value| context.expand(value).map_err(|message| invalid(key, value, message)))
+                .collect()
+        };
+
+        let mut links = Vec::new();
+        if !link_name.is_template() {
+            let dependencies = [
+                ("WantedBy", &install.wanted_by),
+                ("RequiredBy", &install.required_by),
+                ("UpheldBy", &install.upheld_by),
+            ];
+            for ((key, values), suffix) in dependencies.into_iter().zip(DEPENDENCY_SUFFIXES) {
+                for target in expand(key, values)? {
+                    let path = self.config_dir.join(format!("{target}.{suffix}")).join(link_name.to_string());
+                    links.push(Link { path, target: fragment.clone() });
+                }
+            }
+        }
+        for alias in expand("Alias", &install.alias)? {
+            // an instance is aliased as the same instance of a template alias
+            let alias = match (alias.parse::<UnitName>(), &instance) {
+                (Ok(alias), Some(instance)) if alias.is_template() => alias.with_instance(instance).to_string(),
+                _ => alias,
+            };
+            let path = self.config_dir.join(&alias);
+            if path != fragment {
+                links.push(Link { path, target: fragment.clone() });
+            }
+        }
+
+        let instance = instance.filter(|_| unit_name.is_instance());
+        Ok((Plan { fragment, instance, links }, expand("Also", &install.also)?))
+    }
+
+    /// Every symlink directly in the configuration directory or one of its dependency directories, with
+    /// what it points to.
+    fn config_links(&self) -> Vec<(PathBuf, PathBuf)> {
+        symlinks_in(self.loader, &self.config_dir)
+    }
+}
+
+/// Where a directory on the search path puts the unit files in it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DirKind {
+    /// Configuration of the administrator, where units are enabled, linked and masked.
+    Config { runtime: bool },
+    Generator,
+    Transient,
+    /// Unit files shipped with the OS.
+    Vendor,
+}
+
+fn dir_kind(dir: &Path) -> DirKind {
+    let name = dir.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+    if name.starts_with("generator") {
+        DirKind::Generator
+    } else if name == "transient" {
+        DirKind::Transient
+    } else if dir.starts_with("/run") {
+        DirKind::Config { runtime: true }
+    } else if dir.starts_with("/etc") || dir.ends_with(".config/systemd/user") {
+        DirKind::Config { runtime: false }
+    } else {
+        DirKind::Vendor
+    }
+}
+
+/// Works out the state of the unit file `name` from where on the search path of `loader` it is found, what
+/// it links to and which symlinks in the configuration directories point to it. An instance without a unit
+/// file of its own is looked up as its template, but only links to the instance enable it.
+pub fn unit_file_state(loader: &UnitLoader, name: &str) -> Result<UnitFileState, Error> {
+    let unit_name: UnitName = name.parse().map_err(|message| invalid("unit", name, message))?;
+    let template = unit_name.template().filter(|_| unit_name.is_instance()).map(|t| t.to_string());
+    let found = [Some(name), template.as_deref()].into_iter().flatten()
+        .find_map(|file_name| {
+            loader.search_paths().iter()
+                .map(|dir| (dir, dir.join(file_name)))
+                .find(|(_, path)| fs::symlink_metadata(loader.rooted(path)).is_ok())
+        });
+    let (dir, path) = match found {
+        Some(found) => found,
+        None => {
+            let e = io::Error::new(io::ErrorKind::NotFound, "no such unit on the search path");
+            return Err(Error::from(e).with_file(name));
+        }
+    };
+
+    file_state(loader, &unit_name, dir, &path)
+}
+
+/// Lists every unit file on the search path of `loader` by name, each found where it has the highest
+/// priority, with its state. Files that can't be loaded are `Bad`.
+pub fn list_unit_files(loader: &UnitLoader) -> Vec<UnitFile> {
+    let mut found: BTreeMap<String, (&PathBuf, PathBuf)> = BTreeMap::new();
+    for dir in loader.search_paths() {
+        for entry in fs::read_dir(loader.rooted(dir)).into_iter().flatten().flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.parse::<UnitName>().is_err() || entry.path().is_dir() {
+                continue;
+            }
+            found.entry(name).or_insert_with_key(|name| (dir, dir.join(name)));
+        }
+    }
+
+    found.into_iter()
+        .map(|(name, (dir, path))| {
+            let state = name.parse()
+                .map_err(|message| invalid("unit", &name, message))
+                .and_then(|unit_name| file_state(loader, &unit_name, dir, &path))
+                .unwrap_or(UnitFileState::Bad);
+            UnitFile { name, path, state }
+        })
+        .collect()
+}
+
+/// The state of the unit `name`, whose file is `path` in the search path directory `dir`.
+fn file_state(loader: &UnitLoader, name: &UnitName, dir: &Path, path: &Path) -> Result<UnitFileState, Error> {
+    let kind = dir_kind(dir);
+    let runtime = dir.starts_with("/run");
+    let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+
+    if is_masked(&loader.rooted(path)) {
+        return Ok(if runtime { UnitFileState::MaskedRuntime } else { UnitFileState::Masked });
+    }
+    if let Ok(target) = fs::read_link(loader.rooted(path)) {
+        if target.file_name().is_some_and(|n| n != file_name) {
+            return Ok(UnitFileState::Alias);
+        }
+        let target_dir = dir.join(&target).parent().map(Path::to_path_buf).unwrap_or_default();
+        if matches!(kind, DirKind::Config { .. }) && !loader.search_paths().contains(&target_dir) {
+            return Ok(if runtime { UnitFileState::LinkedRuntime } else { UnitFileState::Linked });
+        }
+    }
+    match kind {
+        DirKind::Generator => return Ok(UnitFileState::Generated),
+        DirKind::Transient => return Ok(UnitFileState::Transient),
+        _ => {}
+    }
+
+    let name_str = name.to_string();
+    let unit = loader.load(&name_str)?;
+    let file = match (unit.state, unit.file) {
+        (LoadState::Loaded, Some(file)) => file,
+        _ => return Ok(UnitFileState::Bad),
+    };
+
+    // symlinks named after the unit, or pointing to its file, in the directories units are enabled in
+    let default_instance = file.install.default_instance.clone();
+    let mut enabled: Option<UnitFileState> = None;
+    for config_dir in loader.search_paths() {
+        let runtime = match dir_kind(config_dir) {
+            DirKind::Config { runtime } => runtime,
+            _ => continue,
+        };
+        for (link, target) in symlinks_in(loader, config_dir) {
+            let link_name = link.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+            let points_here = target.file_name().is_some_and(|n| n == file_name);
+            let relevant = link != path && if name.is_instance() {
+                link_name == name_str
+            } else {
+                link_name == name_str || points_here
+            };
+            if !relevant {
+                continue;
+            }
+
+            // a template is enabled by links to its default instance or aliases of the template itself
+            let direct = !name.is_template() || match link_name.parse::<UnitName>() {
+                Ok(link_name) if link_name.is_instance() => link_name.instance == default_instance,
+                _ => true,
+            };
+            let state = match (direct, runtime) {
+                (true, false) => UnitFileState::Enabled,
+                (true, true) => UnitFileState::EnabledRuntime,
+                (false, _) => UnitFileState::Indirect,
+            };
+            enabled = Some(enabled.map_or(state, |e| e.min(state)));
+        }
+    }
+    if let Some(state) = enabled {
+        return Ok(state);
+    }
+
+    let install = &file.install;
+    let installable = [&install.wanted_by, &install.required_by, &install.upheld_by, &install.alias].into_iter()
+        .any(|values| !unit_names(values).is_empty());
+    Ok(if installable {
+        UnitFileState::Disabled
+    } else if !unit_names(&install.also).is_empty() {
+        UnitFileState::Indirect
+    } else {
+        UnitFileState::Static
+    })
+}
+
+/// Every symlink directly in `dir` or one of its dependency directories, with what it points to. Both are
+/// absolute paths as seen from inside the root of `loader`.
+fn symlinks_in(loader: &UnitLoader, dir: &Path) -> Vec<(PathBuf, PathBuf)> {
+    let mut dirs = vec![dir.to_path_buf()];
+    let mut links = Vec::new();
+    while let Some(current) = dirs.pop() {
+        let entries = match fs::read_dir(loader.rooted(&current)) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for entry in entries.flatten() {
+            let path = current.join(entry.file_name());
+            let is_dependency_dir = DEPENDENCY_SUFFIXES.iter()
+                .any(|suffix| path.extension().is_some_and(|ext| ext == *suffix));
+            if current == dir && is_dependency_dir && entry.path().is_dir() {
+                dirs.push(path);
+            } else if let Ok(target) = fs::read_link(entry.path()) {
+                links.push((path, current.join(target)));
+            }
+        }
+    }
+
+    links
+}
+
+fn invalid(key: &str, value: &str, message: String) -> Error {
+    Error::InvalidValue { location: Box::new(Location::default()), key: key.to_string(), value: value.to_string(), message }
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(not(unix))]
+fn symlink(_target: &Path, _link: &Path) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "symlinks are only supported on unix"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::loader::Scope;
+    use super::super::testing::tree;
+
+    #[test]
+    fn states_of_unit_files() {
+        let root = tree("install-states", &[
+            ("usr/lib/systemd/system/static.service", "[Service]\nExecStart=/bin/s\n"),
+            ("usr/lib/systemd/system/disabled.service", "[Service]\nExecStart=/bin/d\n[Install]\nWantedBy=multi-user.target\n"),
+            ("usr/lib/systemd/system/also.service", "[Service]\nExecStart=/bin/a\n[Install]\nAlso=disabled.service\n"),
+            ("usr/lib/systemd/system/empty.service", ""),
+            ("run/systemd/system/empty-runtime.service", ""),
+            ("run/systemd/generator/generated.service", "[Service]\nExecStart=/bin/g\n"),
+        ]);
+        let loader = UnitLoader::new(&root, Scope::System);
+        let states: Vec<(String, UnitFileState)> = list_unit_files(&loader).into_iter()
+            .map(|unit_file| (unit_file.name, unit_file.state))
+            .collect();
+        assert_eq!(states, [
+            ("also.service".to_string(), UnitFileState::Indirect),
+            ("disabled.service".to_string(), UnitFileState::Disabled),
+            ("empty-runtime.service".to_string(), UnitFileState::MaskedRuntime),
+            ("empty.service".to_string(), UnitFileState::Masked),
+            ("generated.service".to_string(), UnitFileState::Generated),
+            ("static.service".to_string(), UnitFileState::Static),
+        ]);
+        assert!(unit_file_state(&loader, "missing.service").is_err());
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    /// The symlinks below `dir` in `root`, with what they point to.
+    fn links(root: &Path, dir: &str) -> Vec<(String, PathBuf)> {
+        let dir = root.join(dir);
+        let mut links = Vec::new();
+        let mut queue = vec![dir.clone()];
+        while let Some(current) = queue.pop() {
+            for path in fs::read_dir(&current).unwrap().flatten().map(|e| e.path()) {
+                match fs::read_link(&path) {
+                    Ok(target) => links.push((path.strip_prefix(&dir).unwrap().display().to_string(), target)),
+                    Err(_) if path.is_dir() => queue.push(path),
+                    Err(_) => {}
+                }
+            }
+        }
+        links.sort();
+
+        links
+    }
+
+    #[test]
+    fn enable_and_disable_create_and_remove_links() {
+        let root = tree("install-enable", &[
+            ("usr/lib/systemd/system/a.service", "[Service]\nExecStart=/bin/a\n[Install]\nWantedBy=multi-user.target\nRequiredBy=b.target\nAlias=alias.service\nAlso=b.service\n"),
+            ("usr/lib/systemd/system/b.service", "[Service]\nExecStart=/bin/b\n[Install]\nUpheldBy=c.target\n"),
+        ]);
+        let loader = UnitLoader::new(&root, Scope::System);
+        let installer = Installer::new(&loader, SYSTEM_CONFIG_DIR);
+        let a = PathBuf::from("/usr/lib/systemd/system/a.service");
+        let b = PathBuf::from("/usr/lib/systemd/system/b.service");
+
+        let changes = installer.enable(&["a.service"]).unwrap();
+        assert_eq!(changes.len(), 4);
+        assert_eq!(links(&root, "etc/systemd/system"), [
+            ("alias.service".to_string(), a.clone()),
+            ("b.target.requires/a.service".to_string(), a.clone()),
+            ("c.target.upholds/b.service".to_string(), b.clone()),
+            ("multi-user.target.wants/a.service".to_string(), a.clone()),
+        ]);
+        assert_eq!(unit_file_state(&loader, "a.service").unwrap(), UnitFileState::Enabled);
+        assert_eq!(installer.enable(&["a.service"]).unwrap(), []);
+
+        let changes = installer.disable(&["a.service"]).unwrap();
+        assert_eq!(changes.len(), 4);
+        assert!(changes.iter().all(|change| matches!(change, InstallChange::Unlink { .. })));
+        assert_eq!(links(&root, "etc/systemd/system"), []);
+        assert_eq!(unit_file_state(&loader, "a.service").unwrap(), UnitFileState::Disabled);
+        assert_eq!(installer.disable(&["a.service"]).unwrap(), []);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn enable_stops_at_a_file_in_the_way() {
+        let root = tree("install-in-the-way", &[
+            ("usr/lib/systemd/system/a.service", "[Service]\nExecStart=/bin/a\n[Install]\nWantedBy=multi-user.target\nAlias=alias.service\n"),
+            ("etc/systemd/system/alias.service", "[Service]\nExecStart=/bin/other\n"),
+        ]);
+        let loader = UnitLoader::new(&root, Scope::System);
+        let installer = Installer::new(&loader, SYSTEM_CONFIG_DIR);
+
+        assert!(installer.enable(&["a.service"]).is_err());
+        assert!(!root.join("etc/systemd/system/multi-user.target.wants").exists());
+        // disabling leaves the unit file with the name of the alias alone
+        assert_eq!(installer.disable(&["a.service"]).unwrap(), []);
+        assert!(root.join("etc/systemd/system/alias.service").is_file());
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn enable_and_disable_instances() {
+        let root = tree("install-instances", &[
+            ("usr/lib/systemd/system/getty@.service", "[Service]\nExecStart=/bin/getty %i\n[Install]\nWantedBy=getty.target\nDefaultInstance=tty1\n"),
+        ]);
+        let loader = UnitLoader::new(&root, Scope::System);
+        let installer = Installer::new(&loader, SYSTEM_CONFIG_DIR);
+        let template = PathBuf::from("/usr/lib/systemd/system/getty@.service");
+
+        installer.enable(&["getty@.service", "getty@tty2.service"]).unwrap();
+        assert_eq!(links(&root, "etc/systemd/system"), [
+            ("getty.target.wants/getty@tty1.service".to_string(), template.clone()),
+            ("getty.target.wants/getty@tty2.service".to_string(), template.clone()),
+        ]);
+
+        installer.disable(&["getty@tty2.service"]).unwrap();
+        assert_eq!(links(&root, "etc/systemd/system"), [
+            ("getty.target.wants/getty@tty1.service".to_string(), template.clone()),
+        ]);
+
+        installer.enable(&["getty@tty2.service"]).unwrap();
+        installer.disable(&["getty@.service"]).unwrap();
+        assert_eq!(links(&root, "etc/systemd/system"), []);
+        fs::remove_dir_all(root).unwrap();
+    }
+}
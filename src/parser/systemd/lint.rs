@@ -0,0 +1,386 @@
+//! Best-practice checks on unit files that are valid but probably not what their author meant.
+
+use serde_derive::Serialize;
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use crate::parser::error::{render_diagnostic, Error, Location};
+use super::exec::{parse_command_lines, EXEC_DIRECTIVES};
+use super::{Entry, RestartPolicy, Section, ServiceType, SystemdFile};
+
+/// How much a lint finding matters. The names are those of SARIF result levels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all="lowercase")]
+pub enum Severity {
+    Note,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Note => "note",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// A check the linter knows about.
+#[derive(Debug, Clone, Copy)]
+pub struct LintRule {
+    /// Stable identifier used to enable and disable the rule and in reports.
+    pub id: &'static str,
+    pub severity: Severity,
+    /// One sentence on what the rule looks for.
+    pub summary: &'static str,
+    check: fn(&FileContext<'_>, &mut Vec<Finding>),
+}
+
+/// Every rule, all of them enabled by default.
+pub const RULES: [LintRule; 6] = [
+    LintRule {
+        id: "missing-description",
+        severity: Severity::Note,
+        summary: "units should have a Description= for systemctl status and the journal",
+        check: missing_description,
+    },
+    LintRule {
+        id: "forking-without-pidfile",
+        severity: Severity::Warning,
+        summary: "Type=forking services should set PIDFile= so systemd can find the main process",
+        check: forking_without_pidfile,
+    },
+    LintRule {
+        id: "restart-without-delay",
+        severity: Severity::Warning,
+        summary: "Restart=always without RestartSec= restarts after 100ms and soon hits the start limit",
+        check: restart_without_delay,
+    },
+    LintRule {
+        id: "network-target-ordering",
+        severity: Severity::Warning,
+        summary: "After=network.target doesn't wait for the network to be up, network-online.target does",
+        check: network_target_ordering,
+    },
+    LintRule {
+        id: "install-without-target",
+        severity: Severity::Warning,
+        summary: "an [Install] section without WantedBy=, RequiredBy=, UpheldBy=, Alias= or Also= makes enable do nothing",
+        check: install_without_target,
+    },
+    LintRule {
+        id: "relative-exec-path",
+        severity: Severity::Warning,
+        summary: "commands should be given by absolute path instead of relying on systemd's search path",
+        check: relative_exec_path,
+    },
+];
+
+/// A problem a lint rule found. Validation errors convert into findings with the error `code` as the rule,
+/// so both can be reported together.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all="camelCase")]
+pub struct Finding {
+    /// The `id` of the rule.
+    pub rule: &'static str,
+    pub severity: Severity,
+    pub message: String,
+    /// Where the problem is. Findings about something missing from the file point at the section it
+    /// belongs in, or have a line of 0 if there is no such section.
+    pub location: Location,
+}
+
+impl Finding {
+    /// Records the file the finding is about.
+    pub fn with_file(mut self, name: &str) -> Self {
+        self.location.file = Some(name.to_string());
+        self
+    }
+
+    /// Renders the finding the way rustc does, like `Error::render`.
+    pub fn render(&self) -> String {
+        render_diagnostic(self.severity.as_str(), self.rule, &self.message, Some(&self.location), self.location.file.as_deref())
+    }
+}
+
+impl From<Error> for Finding {
+    fn from(e: Error) -> Self {
+        let location = match e.location() {
+            Some(location) => location.clone(),
+            None => Location { file: e.file().map(str::to_string), ..Location::default() },
+        };
+
+        Finding { rule: e.code(), severity: Severity::Error, message: e.message(), location }
+    }
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match (self.location.line, self.location.file.as_deref()) {
+            (0, Some(file)) => write!(f, "{file}: {}: {}", self.severity, self.message),
+            (0, None) => write!(f, "{}: {}", self.severity, self.message),
+            _ => write!(f, "{}: {}: {}", self.location, self.severity, self.message),
+        }
+    }
+}
+
+/// Runs the enabled rules over unit files.
+#[derive(Debug, Clone, Default)]
+pub struct Linter {
+    disabled: HashSet<&'static str>,
+}
+
+impl Linter {
+    /// A linter with every rule enabled.
+    pub fn new() -> Self {
+        Linter::default()
+    }
+
+    pub fn enable(&mut self, id: &str) -> Result<(), String> {
+        let rule = find_rule(id)?;
+        self.disabled.remove(rule.id);
+        Ok(())
+    }
+
+    pub fn disable(&mut self, id: &str) -> Result<(), String> {
+        let rule = find_rule(id)?;
+        self.disabled.insert(rule.id);
+        Ok(())
+    }
+
+    pub fn is_enabled(&self, id: &str) -> bool {
+        !self.disabled.contains(id)
+    }
+
+    /// The rules that are enabled, in the order of `RULES`.
+    pub fn rules(&self) -> impl Iterator<Item = &'static LintRule> + '_ {
+        RULES.iter().filter(|rule| self.is_enabled(rule.id))
+    }
+
+    /// Runs every enabled rule over `file` and returns what they found in file order. The lines findings
+    /// point at are quoted from the `cst` when the file has one.
+    pub fn lint(&self, file: &SystemdFile) -> Vec<Finding> {
+        let source = match file.cst.as_ref() {
+            Some(cst) => cst.to_string(),
+            None => file.to_string(),
+        };
+        let context = FileContext { file, source: &source };
+
+        let mut findings = Vec::new();
+        for rule in self.rules() {
+            let start = findings.len();
+            (rule.check)(&context, &mut findings);
+            for finding in &mut findings[start..] {
+                finding.severity = rule.severity;
+            }
+        }
+        findings.sort_by_key(|f| (f.location.line, f.location.column));
+
+        findings
+    }
+}
+
+fn find_rule(id: &str) -> Result<&'static LintRule, String> {
+    RULES.iter()
+        .find(|rule| rule.id == id)
+        .ok_or_else(|| format!("unknown lint rule `{id}`"))
+}
+
+/// What the rules look at: the file and the text its line numbers refer to.
+struct FileContext<'a> {
+    file: &'a SystemdFile,
+    source: &'a str,
+}
+
+impl<'a> FileContext<'a> {
+    fn sections(&self, name: &'a str) -> impl Iterator<Item = &'a Section> + 'a {
+        self.file.sections.iter().filter(move |s| s.name == name)
+    }
+
+    fn entries(&self, section: &'a str, key: &'a str) -> impl Iterator<Item = &'a Entry> + 'a {
+        self.sections(section).flat_map(|s| s.entries.iter()).filter(move |e| e.key == key)
+    }
+
+    /// Whether `key` is set in `section`, i.e. assigned and not reset by a later empty assignment.
+    fn has(&self, section: &'a str, key: &'a str) -> bool {
+        self.entries(section, key).last().is_some_and(|e| !e.value.is_empty())
+    }
+
+    /// A finding pointing at the whole of `entry`.
+    fn at_entry(&self, rule: &'static str, entry: &Entry, message: String) -> Finding {
+        let length = entry.to_string().chars().count();
+        self.finding(rule, Location::from_source(self.source, entry.line, entry.column, length), message)
+    }
+
+    /// A finding pointing at the header of the first `section`, or at no line without one.
+    fn at_section(&self, rule: &'static str, section: &str, message: String) -> Finding {
+        let location = match self.file.sections.iter().find(|s| s.name == section) {
+            Some(section) => Location::from_source(self.source, section.line, 1, section.name.chars().count() + 2),
+            None => Location::default(),
+        };
+        self.finding(rule, location, message)
+    }
+
+    fn finding(&self, rule: &'static str, location: Location, message: String) -> Finding {
+        // the severity is filled in by `Linter::lint` from the rule
+        Finding { rule, severity: Severity::Warning, message, location }
+    }
+}
+
+fn missing_description(cx: &FileContext<'_>, findings: &mut Vec<Finding>) {
+    if !cx.has("Unit", "Description") {
+        findings.push(cx.at_section("missing-description", "Unit", "the unit has no Description=".to_string()));
+    }
+}
+
+fn forking_without_pidfile(cx: &FileContext<'_>, findings: &mut Vec<Finding>) {
+    let service_type = match cx.entries("Service", "Type").last() {
+        Some(entry) => entry,
+        None => return,
+    };
+    if service_type.value.parse() == Ok(ServiceType::Forking) && !cx.has("Service", "PIDFile") {
+        findings.push(cx.at_entry(
+            "forking-without-pidfile",
+            service_type,
+            "Type=forking without PIDFile=, systemd has to guess which process is the main one".to_string(),
+        ));
+    }
+}
+
+fn restart_without_delay(cx: &FileContext<'_>, findings: &mut Vec<Finding>) {
+    let restart = match cx.entries("Service", "Restart").last() {
+        Some(entry) => entry,
+        None => return,
+    };
+    if restart.value.parse() == Ok(RestartPolicy::Always) && !cx.has("Service", "RestartSec") {
+        findings.push(cx.at_entry(
+            "restart-without-delay",
+            restart,
+            "Restart=always without RestartSec= restarts 100ms after every exit".to_string(),
+        ));
+    }
+}
+
+fn network_target_ordering(cx: &FileContext<'_>, findings: &mut Vec<Finding>) {
+    let ordered_after = |unit: &str| {
+        cx.entries("Unit", "After").any(|e| e.words().unwrap_or_default().iter().any(|w| w == unit))
+    };
+    if ordered_after("network-online.target") {
+        return;
+    }
+
+    for entry in cx.entries("Unit", "After") {
+        if entry.words().unwrap_or_default().iter().any(|w| w == "network.target") {
+            findings.push(cx.at_entry(
+                "network-target-ordering",
+                entry,
+                "After=network.target doesn't wait for the network to be configured, use \
+                 Wants= and After=network-online.target for that".to_string(),
+            ));
+        }
+    }
+}
+
+fn install_without_target(cx: &FileContext<'_>, findings: &mut Vec<Finding>) {
+    if cx.sections("Install").next().is_none() {
+        return;
+    }
+    let installs = ["WantedBy", "RequiredBy", "UpheldBy", "Alias", "Also"].iter()
+        .any(|key| cx.has("Install", key));
+    if !installs {
+        findings.push(cx.at_section(
+            "install-without-target",
+            "Install",
+            "the [Install] section has no WantedBy=, RequiredBy=, UpheldBy=, Alias= or Also=, enabling \
+             the unit does nothing".to_string(),
+        ));
+    }
+}
+
+fn relative_exec_path(cx: &FileContext<'_>, findings: &mut Vec<Finding>) {
+    let exec_entries = cx.file.sections.iter()
+        .flat_map(|s| s.entries.iter())
+        .filter(|e| EXEC_DIRECTIVES.contains(&e.key.as_str()) && !e.value.is_empty());
+
+    for entry in exec_entries {
+        let commands = parse_command_lines(&entry.value).unwrap_or_default();
+        // specifiers such as %h expand to absolute paths
+        for command in commands.iter().filter(|c| !c.path.starts_with('/') && !c.path.starts_with('%')) {
+            findings.push(cx.at_entry(
+                "relative-exec-path",
+                entry,
+                format!("{}= runs `{}` by a relative name, give its absolute path", entry.key, command.path),
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::parse_str;
+
+    fn rules(linter: &Linter, source: &str) -> Vec<(&'static str, usize)> {
+        linter.lint(&parse_str(source).unwrap()).into_iter()
+            .map(|finding| (finding.rule, finding.location.line))
+            .collect()
+    }
+
+    #[test]
+    fn a_clean_unit_has_no_findings() {
+        let source = "[Unit]\nDescription=a\nWants=network-online.target\nAfter=network-online.target\n\
+                      [Service]\nType=forking\nPIDFile=/run/a.pid\nExecStart=/bin/a\nRestart=always\nRestartSec=5\n\
+                      [Install]\nWantedBy=multi-user.target\n";
+        assert_eq!(rules(&Linter::new(), source), []);
+    }
+
+    #[test]
+    fn findings_come_in_file_order() {
+        let source = "[Unit]\nAfter=network.target\n\
+                      [Service]\nType=forking\nExecStart=a\nExecStartPre=%h/bin/pre\nRestart=always\n\
+                      [Install]\nDescription=misplaced\n";
+        assert_eq!(rules(&Linter::new(), source), [
+            ("missing-description", 1),
+            ("network-target-ordering", 2),
+            ("forking-without-pidfile", 4),
+            ("relative-exec-path", 5),
+            ("restart-without-delay", 7),
+            ("install-without-target", 8),
+        ]);
+    }
+
+    #[test]
+    fn an_empty_assignment_unsets_a_directive() {
+        let source = "[Unit]\nDescription=a\nDescription=\n";
+        assert_eq!(rules(&Linter::new(), source), [("missing-description", 1)]);
+        assert_eq!(rules(&Linter::new(), "[Service]\nExecStart=/bin/a\n"), [("missing-description", 0)]);
+    }
+
+    #[test]
+    fn rules_can_be_disabled() {
+        let mut linter = Linter::new();
+        linter.disable("missing-description").unwrap();
+        assert!(!linter.is_enabled("missing-description"));
+        assert_eq!(linter.rules().count(), RULES.len() - 1);
+        assert_eq!(rules(&linter, "[Service]\nExecStart=/bin/a\n"), []);
+
+        linter.enable("missing-description").unwrap();
+        assert_eq!(rules(&linter, "[Service]\nExecStart=/bin/a\n"), [("missing-description", 0)]);
+        assert_eq!(linter.disable("no-such-rule"), Err("unknown lint rule `no-such-rule`".to_string()));
+    }
+
+    #[test]
+    fn findings_take_the_severity_of_their_rule() {
+        let findings = Linter::new().lint(&parse_str("[Service]\nExecStart=a\n").unwrap());
+        let severities: Vec<Severity> = findings.iter().map(|finding| finding.severity).collect();
+        assert_eq!(severities, [Severity::Note, Severity::Warning]);
+        assert_eq!(findings[1].location.column, 1);
+    }
+}
@@ -0,0 +1,281 @@
+use serde_derive::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::parser::error::Error;
+use super::dropin;
+use super::name::UnitName;
+use super::SystemdFile;
+
+/// Unit search path of the system manager, from the highest to the lowest priority.
+pub const SYSTEM_UNIT_PATH: &[&str] = &[
+    "/etc/systemd/system.control",
+    "/run/systemd/system.control",
+    "/run/systemd/transient",
+    "/run/systemd/generator.early",
+    "/etc/systemd/system",
+    "/etc/systemd/system.attached",
+    "/run/systemd/system",
+    "/run/systemd/system.attached",
+    "/run/systemd/generator",
+    "/usr/local/lib/systemd/system",
+    "/usr/lib/systemd/system",
+    // distributions that haven't merged /usr still ship units here
+    "/lib/systemd/system",
+    "/run/systemd/generator.late",
+];
+
+/// Unit search path of user managers that doesn't depend on the user's home directory, from the highest to
+/// the lowest priority.
+pub const USER_UNIT_PATH: &[&str] = &[
+    "/etc/systemd/user",
+    "/run/systemd/user",
+    "/usr/local/share/systemd/user",
+    "/usr/share/systemd/user",
+    "/usr/local/lib/systemd/user",
+    "/usr/lib/systemd/user",
+];
+
+/// Which service manager's search path to use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    System,
+    User,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all="kebab-case")]
+pub enum LoadState {
+    Loaded,
+    /// The unit file is a symlink to `/dev/null` or empty.
+    Masked,
+    NotFound,
+}
+
+/// The result of looking a unit up on the search path.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all="camelCase")]
+pub struct LoadedUnit {
+    /// The name the unit was looked up by.
+    pub name: String,
+    /// The name of the unit file the lookup ended at after following alias symlinks.
+    pub id: String,
+    pub state: LoadState,
+    /// Path of the unit file below the root.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fragment_path: Option<PathBuf>,
+    /// Drop-ins applied to the unit, in the order they were applied.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub dropins: Vec<PathBuf>,
+    /// The unit merged with its drop-ins, if it was loaded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<SystemdFile>,
+}
+
+/// Looks units up the way systemd's loader does, below a root directory that stands in for `/`.
+#[derive(Debug, Clone)]
+pub struct UnitLoader {
+    root: PathBuf,
+    search_paths: Vec<PathBuf>,
+}
+
+impl UnitLoader {
+    /// A loader using the standard search path of `scope` below `root`.
+    pub fn new<P: AsRef<Path>>(root: P, scope: Scope) -> Self {
+        let search_paths = match scope {
+            Scope::System => SYSTEM_UNIT_PATH,
+            Scope::User => USER_UNIT_PATH,
+        };
+
+        UnitLoader::with_search_paths(root, search_paths.iter().map(PathBuf::from).collect())
+    }
+
+    /// A loader using the given search path, highest priority first. The paths are absolute paths as seen
+    /// from inside `root`.
+    pub fn with_search_paths<P: AsRef<Path>>(root: P, search_paths: Vec<PathBuf>) -> Self {
+        UnitLoader { root: root.as_ref().to_path_buf(), search_paths }
+    }
+
+    /// A user manager loader that also searches the per-user directories below `home`, which is given as
+    /// seen from inside the root.
+    pub fn user<P: AsRef<Path>, H: AsRef<Path>>(root: P, home: H) -> Self {
+        let home = home.as_ref();
+        let mut search_paths = vec![home.join(".config/systemd/user")];
+        search_paths.extend(USER_UNIT_PATH.iter().map(PathBuf::from));
+        search_paths.push(home.join(".local/share/systemd/user"));
+
+        UnitLoader::with_search_paths(root, search_paths)
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn search_paths(&self) -> &[PathBuf] {
+        &self.search_paths
+    }
+
+    /// The search path directories below the root, highest priority first.
+    pub fn unit_dirs(&self) -> Vec<PathBuf> {
+        self.search_paths.iter().map(|p| self.rooted(p)).collect()
+    }
+
+    /// Maps an absolute path as seen from inside the root to a path on the host.
+    pub fn rooted<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        let path = path.as_ref();
+        self.root.join(path.strip_prefix("/").unwrap_or(path))
+    }
+
+    /// Returns the first file named `name` on the search path. The file may be a symlink.
+    pub fn find(&self, name: &str) -> Option<PathBuf> {
+        self.unit_dirs().into_iter()
+            .map(|dir| dir.join(name))
+            .find(|path| fs::symlink_metadata(path).is_ok())
+    }
+
+    /// Looks up `name` on the search path, follows alias symlinks and merges the unit with the drop-ins of
+    /// every name it is known by. An instance such as `getty@tty1.service` without a unit file of its own
+    /// is loaded from its template `getty@.service`. Specifiers are left as they are, see
+    /// `SystemdFile::expand_specifiers`.
+    pub fn load(&self, name: &str) -> Result<LoadedUnit, Error> {
+        let mut unit = LoadedUnit {
+            name: name.to_string(),
+            id: name.to_string(),
+            state: LoadState::NotFound,
+            fragment_path: None,
+            dropins: vec![],
+            file: None,
+        };
+
+        let template = name.parse::<UnitName>().ok()
+            .filter(|n| n.is_instance())
+            .and_then(|n| n.template())
+            .map(|t| t.to_string());
+        let mut path = match self.find(name).or_else(|| template.as_deref().and_then(|t| self.find(t))) {
+            Some(path) => path,
+            None => return Ok(unit),
+        };
+        let from_template = template.is_some_and(|t| path.file_name().is_some_and(|n| n == t.as_str()));
+        let mut names = vec![name.to_string()];
+
+        // symlinks within the search path are aliases, one pointing to /dev/null masks the unit
+        for _ in 0..32 {
+            if is_masked(&path) {
+                unit.state = LoadState::Masked;
+                unit.fragment_path = Some(path);
+                return Ok(unit);
+            }
+            let target = match fs::read_link(&path) {
+                Ok(target) => target,
+                Err(_) => break,
+            };
+
+            path = if target.is_absolute() {
+                self.rooted(&target)
+            } else {
+                path.parent().unwrap_or(&self.root).join(&target)
+            };
+            // an instance keeps its name when its template is an alias
+            if let Some(target_name) = path.file_name().and_then(|n| n.to_str()).filter(|_| !from_template) {
+                if !names.iter().any(|n| n == target_name) {
+                    names.push(target_name.to_string());
+                }
+            }
+        }
+        unit.id = names.last().cloned().unwrap_or_default();
+
+        if !path.is_file() {
+            return Ok(unit);
+        }
+
+        unit.dropins = self.find_dropins(&names);
+        unit.file = Some(dropin::merge(&path, &unit.dropins)?);
+        unit.fragment_path = Some(path);
+        unit.state = LoadState::Loaded;
+
+        Ok(unit)
+    }
+
+    /// Collects the drop-ins of a unit known by `names` from every directory on the search path. A drop-in
+    /// overrides one of the same file name in a lower priority directory, and one that is masked disables
+    /// it.
+    pub fn find_dropins(&self, names: &[String]) -> Vec<PathBuf> {
+        let mut dropins: Vec<PathBuf> = Vec::new();
+        let mut masked: Vec<PathBuf> = Vec::new();
+
+        for dir in self.unit_dirs() {
+            for path in names.iter().flat_map(|name| dropin::find_dropins_in(&dir, name)) {
+                let file_name = PathBuf::from(path.file_name().unwrap_or_default());
+                if dropins.iter().any(|d| d.file_name() == path.file_name()) || masked.contains(&file_name) {
+                    continue;
+                }
+                if is_masked(&path) {
+                    masked.push(file_name);
+                    continue;
+                }
+                dropins.push(path);
+            }
+        }
+        dropins.sort_by(|a, b| a.file_name().cmp(&b.file_name()));
+
+        dropins
+    }
+}
+
+/// Whether the unit file or drop-in at `path` is masked: a symlink to `/dev/null` or an empty file, see
+/// systemd.unit(5). Symlinks aren't followed, their targets are paths inside the root.
+pub(super) fn is_masked(path: &Path) -> bool {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_symlink() => fs::read_link(path).is_ok_and(|target| target == Path::new("/dev/null")),
+        Ok(metadata) => metadata.is_file() && metadata.len() == 0,
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::testing::unit_root;
+
+    #[test]
+    fn loads_a_unit_file() {
+        let root = unit_root("loader-loads", &[("a.service", "[Service]\nExecStart=/bin/a\n")]);
+        let unit = UnitLoader::new(&root, Scope::System).load("a.service").unwrap();
+        assert_eq!(unit.state, LoadState::Loaded);
+        assert!(unit.file.is_some());
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn an_empty_unit_file_is_masked() {
+        let root = unit_root("loader-empty", &[("empty.service", "")]);
+        let unit = UnitLoader::new(&root, Scope::System).load("empty.service").unwrap();
+        assert_eq!(unit.state, LoadState::Masked);
+        assert!(unit.file.is_none());
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn a_link_to_dev_null_masks_a_unit_file() {
+        let root = unit_root("loader-null", &[("a.service", "[Service]\nExecStart=/bin/a\n")]);
+        fs::create_dir_all(root.join("etc/systemd/system")).unwrap();
+        std::os::unix::fs::symlink("/dev/null", root.join("etc/systemd/system/a.service")).unwrap();
+        let unit = UnitLoader::new(&root, Scope::System).load("a.service").unwrap();
+        assert_eq!(unit.state, LoadState::Masked);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn an_empty_dropin_masks_one_of_the_same_name() {
+        let root = unit_root("loader-dropin", &[("a.service", "[Service]\nExecStart=/bin/a\n")]);
+        let vendor = root.join("usr/lib/systemd/system/a.service.d");
+        let admin = root.join("etc/systemd/system/a.service.d");
+        fs::create_dir_all(&vendor).unwrap();
+        fs::create_dir_all(&admin).unwrap();
+        fs::write(vendor.join("10-limit.conf"), "[Service]\nLimitNOFILE=1024\n").unwrap();
+        fs::write(admin.join("10-limit.conf"), "").unwrap();
+        let unit = UnitLoader::new(&root, Scope::System).load("a.service").unwrap();
+        assert!(unit.dropins.is_empty());
+        fs::remove_dir_all(root).unwrap();
+    }
+}
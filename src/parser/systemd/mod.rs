@@ -1,8 +1,49 @@
 use serde_derive::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter};
+use std::collections::HashMap;
 use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+use std::str::FromStr;
+use pest::iterators::Pair;
 use pest::Parser;
 use pest_derive::Parser;
+use crate::parser::error::{Error, Location};
+
+pub mod bulk;
+pub mod cst;
+pub mod directives;
+pub mod dropin;
+pub mod edit;
+pub mod enums;
+pub mod exec;
+pub mod export;
+pub mod graph;
+pub mod install;
+pub mod lint;
+pub mod loader;
+pub mod name;
+pub mod report;
+pub mod specifier;
+pub mod syntax;
+#[cfg(test)]
+mod testing;
+pub mod time;
+pub mod transaction;
+pub mod unit_types;
+pub mod values;
+
+pub use cst::Cst;
+pub use enums::{KillMode, RestartPolicy, ServiceType, StandardOutput};
+pub use exec::{ExecCommand, ExecPrefix};
+pub use name::UnitName;
+pub use specifier::SpecifierContext;
+pub use time::TimeSpan;
+pub use values::{ByteSize, MemoryLimit, Percent, RLimit, RLimitValue, SdBool};
+pub use unit_types::{
+    AutomountSection, MountSection, PathSection, ScopeSection, SliceSection, SocketSection, SwapSection,
+    TimerSection, UnitType,
+};
 
 #[derive(Debug, Clone)]
 /// Represents a variant type of Systemd unit file values.
@@ -37,7 +78,7 @@ pub struct UnitSection {
     #[serde(skip_serializing_if = "Option::is_none")]
     wants: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    binds_to: Option<String>, // not sure if this can be repeated
+    binds_to: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     before: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -54,56 +95,23 @@ pub struct UnitSection {
 
 impl Display for UnitSection {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let mut s = String::new();
-
         if let Some(comment) = self.comments.as_ref() {
-            s.push_str(format!("{}\n", comment.join("\n").as_str()).as_str());
-        }
-        s.push_str(format!("{}\n", self.head.as_str()).as_str());
-        s.push_str(format!("{}\n", self.description.as_str()).as_str());
-
-        if let Some(after) = self.after.as_ref() {
-            s.push_str(format!("{}\n", after.join("\n").as_str()).as_str()); // add a space once the line ending chars are removed from parsing
-        }
-
-        if let Some(doc) = self.documentation.as_ref() {
-            s.push_str(format!("{}\n", doc.join("\n")).as_str());
-        }
-
-        if let Some(req) = self.requires.as_ref() {
-            s.push_str(format!("{}\n", req.join(" ").as_str()).as_str());
-        }
-
-        if let Some(wants) = self.wants.as_ref() {
-            s.push_str(format!("{}\n", wants.join(" ").as_str()).as_str());
-        }
-
-        if let Some(binds) = self.binds_to.as_ref() {
-            s.push_str(format!("{}\n", binds).as_str());
-        }
-
-        if let Some(before) = self.before.as_ref() {
-            s.push_str(format!("{}\n", before.join(" ").as_str()).as_str());
+            writeln!(f, "{}", comment.join("\n"))?;
         }
+        writeln!(f, "{}", self.head)?;
+        write_directive(f, "Description", &self.description)?;
+        write_directives(f, "After", &self.after)?;
+        write_directives(f, "Documentation", &self.documentation)?;
+        write_directives(f, "Requires", &self.requires)?;
+        write_directives(f, "Wants", &self.wants)?;
+        write_directives(f, "BindsTo", &self.binds_to)?;
+        write_directives(f, "Before", &self.before)?;
+        write_directives(f, "Conflicts", &self.conflicts)?;
+        write_optional_directive(f, "Condition", &self.condition)?;
+        write_directives(f, "Assert", &self.assert)?;
+        write_optional_directive(f, "ConditionPathExists", &self.condition_path_exists)?;
 
-        if let Some(conflicts) = self.conflicts.as_ref() {
-            s.push_str(format!("{}\n", conflicts.join(" ").as_str()).as_str());
-        }
-
-        if let Some(condition) = self.condition.as_ref() {
-            s.push_str(format!("{}\n", condition).as_str());
-        }
-
-        if let Some(assert) = self.assert.as_ref() {
-            s.push_str(format!("{}\n", assert.join(" ").as_str()).as_str());
-        }
-
-        if let Some(condition_path) = self.condition_path_exists.as_ref() {
-            s.push_str(format!("{}\n", condition_path).as_str());
-        }
-
-        writeln!(f, "{s}")
-
+        writeln!(f)
     }
 }
 
@@ -111,40 +119,44 @@ impl Display for UnitSection {
 #[serde(rename_all="camelCase")]
 pub struct ServiceSection {
     pub head: String,
-    pub service_type: String,
-    pub exec_start: Vec<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub start_pre: Option<String>,
+    pub service_type: Option<ServiceType>,
+    #[serde(default)]
+    pub exec_start: Vec<ExecCommand>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exec_condition: Vec<ExecCommand>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub start_pre: Vec<ExecCommand>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub start_post: Vec<ExecCommand>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reload: Vec<ExecCommand>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exec_stop: Vec<ExecCommand>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stop_post: Vec<ExecCommand>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub start_post: Option<String>,
+    pub restart_sec: Option<TimeSpan>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub reload: Option<Vec<String>>,
+    pub restart: Option<RestartPolicy>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub exec_stop: Option<String>,
+    pub timeout_sec: Option<TimeSpan>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub stop_post: Option<String>,
+    pub environment_file: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub restart_sec: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub restart: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub timeout_sec: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub environment_file: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub kill_mode: Option<String>,
+    pub kill_mode: Option<KillMode>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub restart_prevent_exit_status: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub runtime_directory: Option<String>,
+    pub runtime_directory: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub runtime_directory_mode: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub environment: Option<String>,
+    pub environment: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub standard_output: Option<String>,
+    pub standard_output: Option<StandardOutput>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub limit_no_file: Option<String>,
+    pub limit_no_file: Option<RLimit>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub comments: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -152,79 +164,51 @@ pub struct ServiceSection {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub user: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub start_limit_interval: Option<String>
+    pub start_limit_interval: Option<TimeSpan>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remain_after_exit: Option<SdBool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu_quota: Option<Percent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory_high: Option<MemoryLimit>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory_max: Option<MemoryLimit>,
 }
 
 impl Display for ServiceSection {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let mut s = String::new();
-
-        s.push_str(format!("{}\n", self.head.as_str()).as_str());
+        writeln!(f, "{}", self.head)?;
         if let Some(comment) = self.comments.as_ref() {
-            s.push_str(format!("{}\n", comment.join("\n")).as_str());
+            writeln!(f, "{}", comment.join("\n"))?;
         }
-        s.push_str(format!("{}\n", self.service_type.as_str()).as_str());
-        s.push_str(format!("{}\n", self.exec_start.join(" ").as_str()).as_str());
+        write_optional_directive(f, "Type", &self.service_type)?;
+        write_commands(f, "ExecCondition", &self.exec_condition)?;
+        write_commands(f, "ExecStartPre", &self.start_pre)?;
+        write_commands(f, "ExecStart", &self.exec_start)?;
+        write_commands(f, "ExecStartPost", &self.start_post)?;
+        write_commands(f, "ExecReload", &self.reload)?;
+        write_commands(f, "ExecStop", &self.exec_stop)?;
+        write_commands(f, "ExecStopPost", &self.stop_post)?;
+        write_optional_directive(f, "RestartSec", &self.restart_sec)?;
+        write_optional_directive(f, "Restart", &self.restart)?;
+        write_optional_directive(f, "TimeoutSec", &self.timeout_sec)?;
+        write_directives(f, "EnvironmentFile", &self.environment_file)?;
+        write_optional_directive(f, "KillMode", &self.kill_mode)?;
+        write_optional_directive(f, "RestartPreventExitStatus", &self.restart_prevent_exit_status)?;
+        write_directives(f, "RuntimeDirectory", &self.runtime_directory)?;
+        write_optional_directive(f, "RuntimeDirectoryMode", &self.runtime_directory_mode)?;
+        write_directives(f, "Environment", &self.environment)?;
+        write_optional_directive(f, "StandardOutput", &self.standard_output)?;
+        write_optional_directive(f, "LimitNOFILE", &self.limit_no_file)?;
+        write_optional_directive(f, "WorkingDirectory", &self.working_directory)?;
+        write_optional_directive(f, "User", &self.user)?;
+        write_optional_directive(f, "StartLimitInterval", &self.start_limit_interval)?;
+        write_optional_directive(f, "RemainAfterExit", &self.remain_after_exit)?;
+        write_optional_directive(f, "CPUQuota", &self.cpu_quota)?;
+        write_optional_directive(f, "MemoryHigh", &self.memory_high)?;
+        write_optional_directive(f, "MemoryMax", &self.memory_max)?;
 
-        if let Some(start_pre) = self.start_pre.as_ref() {
-            s.push_str(format!("{}\n", start_pre).as_str());
-        }
-        if let Some(start_post) = self.start_post.as_ref() {
-            s.push_str(format!("{}\n", start_post).as_str());
-        }
-        if let Some(reload) = self.reload.as_ref() {
-            s.push_str(format!("{}\n", reload.join("\n")).as_str());
-        }
-        if let Some(exec_stop) = self.exec_stop.as_ref() {
-            s.push_str(format!("{}\n", exec_stop).as_str());
-        }
-        if let Some(stop_post) = self.stop_post.as_ref() {
-            s.push_str(format!("{}\n", stop_post).as_str());
-        }
-        if let Some(restart_sec) = self.restart_sec.as_ref() {
-            s.push_str(format!("{}\n", restart_sec).as_str());
-        }
-        if let Some(restart) = self.restart.as_ref() {
-            s.push_str(format!("{}\n", restart).as_str());
-        }
-        if let Some(timeout_sec) = self.timeout_sec.as_ref() {
-            s.push_str(format!("{}\n", timeout_sec).as_str());
-        }
-        if let Some(environment_file) = self.environment_file.as_ref() {
-            s.push_str(format!("{}\n", environment_file).as_str());
-        }
-        if let Some(kill_mode) = self.kill_mode.as_ref() {
-            s.push_str(format!("{}\n", kill_mode).as_str());
-        }
-        if let Some(restart_prevent_exit_status) = self.restart_prevent_exit_status.as_ref() {
-            s.push_str(format!("{}\n", restart_prevent_exit_status).as_str());
-        }
-        if let Some(runtime_directory) = self.runtime_directory.as_ref() {
-            s.push_str(format!("{}\n", runtime_directory).as_str());
-        }
-        if let Some(runtime_directory_mode) = self.runtime_directory_mode.as_ref() {
-            s.push_str(format!("{}\n", runtime_directory_mode).as_str());
-        }
-        if let Some(environment) = self.environment.as_ref() {
-            s.push_str(format!("{}\n", environment).as_str());
-        }
-        if let Some(standard_output) = self.standard_output.as_ref() {
-            s.push_str(format!("{}\n", standard_output).as_str());
-        }
-        if let Some(limit_no_file) = self.limit_no_file.as_ref() {
-            s.push_str(format!("{}\n", limit_no_file).as_str());
-        }
-        if let Some(working_directory) = self.working_directory.as_ref() {
-            s.push_str(format!("{}\n", working_directory).as_str());
-        }
-        if let Some(user) = self.user.as_ref() {
-            s.push_str(format!("{}\n", user).as_str());
-        }
-        if let Some(start_limit_interval) = self.start_limit_interval.as_ref() {
-            s.push_str(format!("{}\n", start_limit_interval).as_str());
-        }
-
-        writeln!(f, "{s}")
+        writeln!(f)
     }
 }
 
@@ -233,13 +217,15 @@ impl Display for ServiceSection {
 pub struct InstallSection {
     pub head: String,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub wanted_by: Option<String>,
+    pub wanted_by: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub required_by: Option<String>,
+    pub required_by: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub alias: Option<String>,
+    pub upheld_by: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub also: Option<String>,
+    pub alias: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub also: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub default_instance: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -248,316 +234,794 @@ pub struct InstallSection {
 
 impl Display for InstallSection {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let mut s = String::new();
-
-        s.push_str(format!("{}\n", self.head.as_str()).as_str());
+        writeln!(f, "{}", self.head)?;
         if let Some(comment) = self.comments.as_ref() {
-            s.push_str(format!("{}\n", comment.join(" ").as_str()).as_str());
+            writeln!(f, "{}", comment.join("\n"))?;
+        }
+        write_directives(f, "WantedBy", &self.wanted_by)?;
+        write_directives(f, "RequiredBy", &self.required_by)?;
+        write_directives(f, "UpheldBy", &self.upheld_by)?;
+        write_directives(f, "Alias", &self.alias)?;
+        write_directives(f, "Also", &self.also)?;
+        write_optional_directive(f, "DefaultInstance", &self.default_instance)?;
+
+        writeln!(f)
+    }
+}
+
+/// Writes `key=value` on its own line. The typed sections only hold values, so this is where the directive
+/// names are put back when a file is written out. Empty values are skipped, as an empty assignment would
+/// reset the directive rather than leave it unset.
+fn write_directive(f: &mut Formatter<'_>, key: &str, value: &str) -> std::fmt::Result {
+    if value.is_empty() {
+        return Ok(());
+    }
+    writeln!(f, "{key}={value}")
+}
+
+fn write_optional_directive<T: Display>(f: &mut Formatter<'_>, key: &str, value: &Option<T>) -> std::fmt::Result {
+    match value {
+        Some(value) => write_directive(f, key, &value.to_string()),
+        None => Ok(()),
+    }
+}
+
+fn write_commands(f: &mut Formatter<'_>, key: &str, commands: &[ExecCommand]) -> std::fmt::Result {
+    for command in commands {
+        write_directive(f, key, &command.to_string())?;
+    }
+
+    Ok(())
+}
+
+/// Writes one `key=value` line per value, in order.
+fn write_directives<T: Display>(f: &mut Formatter<'_>, key: &str, values: &Option<Vec<T>>) -> std::fmt::Result {
+    for value in values.iter().flatten() {
+        write_directive(f, key, &value.to_string())?;
+    }
+
+    Ok(())
+}
+
+/// A single `Key=Value` assignment, kept exactly as it appeared in the unit file. Keys that none of the
+/// typed sections know about are kept as well.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all="camelCase")]
+pub struct Entry {
+    pub key: String,
+    /// The logical value: continuation lines joined and surrounding whitespace removed. Quotes and escapes
+    /// are kept as written.
+    pub value: String,
+    /// The value exactly as it appears in the file, including any continuation lines.
+    #[serde(default)]
+    pub raw: String,
+    /// The value with quotes removed and escapes resolved, see `syntax::decode`.
+    #[serde(default)]
+    pub decoded: String,
+    /// 1-based line of the key in the source file.
+    pub line: usize,
+    /// 1-based column of the key in the source file.
+    pub column: usize,
+    /// The file the entry was read from, recorded when a unit is merged with its drop-ins.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// Comments on the lines right above the assignment, after the one before it.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub comments: Vec<String>,
+}
+
+impl Display for Entry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}={}", self.key, self.value)
+    }
+}
+
+/// A `[Name]` section of a unit file with its entries in source order.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all="camelCase")]
+pub struct Section {
+    /// Section name without the surrounding brackets, e.g. `Service`.
+    pub name: String,
+    /// 1-based line of the section header in the source file.
+    pub line: usize,
+    pub entries: Vec<Entry>,
+    /// Comments on the lines right above the section header.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub comments: Vec<String>,
+    /// Comments after the last assignment that no section header follows, at the end of the file.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub trailing_comments: Vec<String>,
+}
+
+impl Entry {
+    /// Splits the value into words following systemd's quoting rules, for directives that take a list.
+    pub fn words(&self) -> Result<Vec<String>, String> {
+        syntax::split_words(&self.value)
+    }
+}
+
+impl Section {
+    /// Returns the value of the last assignment of `key`, which is the one systemd uses for single valued
+    /// directives.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries.iter().rev().find(|e| e.key == key).map(|e| e.value.as_str())
+    }
+
+    /// Returns the values of every assignment of `key` in source order.
+    pub fn get_all(&self, key: &str) -> Vec<&str> {
+        self.entries.iter().filter(|e| e.key == key).map(|e| e.value.as_str()).collect()
+    }
+}
+
+impl Display for Section {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for comment in &self.comments {
+            writeln!(f, "{comment}")?;
         }
-        if let Some(wanted_by) = self.wanted_by.as_ref() {
-            s.push_str(format!("{}\n", wanted_by).as_str());
+        writeln!(f, "[{}]", self.name)?;
+        for entry in &self.entries {
+            for comment in &entry.comments {
+                writeln!(f, "{comment}")?;
+            }
+            writeln!(f, "{entry}")?;
         }
-        if let Some(required_by) = self.required_by.as_ref() {
-            s.push_str(format!("{}\n", required_by).as_str());
+        for comment in &self.trailing_comments {
+            writeln!(f, "{comment}")?;
         }
-        if let Some(alias) = self.alias.as_ref() {
-            s.push_str(format!("{}\n", alias).as_str());
+
+        Ok(())
+    }
+}
+
+/// All entries and comments of the sections sharing one name. systemd merges repeated section headers,
+/// so the typed sections are built from this rather than from a single `Section`.
+struct SectionView<'a> {
+    name: &'a str,
+    present: bool,
+    entries: Vec<&'a Entry>,
+    comments: Vec<&'a str>,
+}
+
+impl<'a> SectionView<'a> {
+    fn new(sections: &'a [Section], name: &'a str) -> Self {
+        let mut view = SectionView { name, present: false, entries: vec![], comments: vec![] };
+        for section in sections.iter().filter(|s| s.name == name) {
+            view.present = true;
+            view.entries.extend(section.entries.iter());
+            view.comments.extend(section.comments.iter().map(|c| c.as_str()));
+            view.comments.extend(section.entries.iter().flat_map(|e| e.comments.iter()).map(|c| c.as_str()));
+            view.comments.extend(section.trailing_comments.iter().map(|c| c.as_str()));
         }
-        if let Some(also) = self.also.as_ref() {
-            s.push_str(format!("{}\n", also).as_str());
+
+        view
+    }
+
+    fn head(&self) -> String {
+        if self.present {
+            format!("[{}]", self.name)
+        } else {
+            String::new()
         }
-        if let Some(default_instance) = self.default_instance.as_ref() {
-            s.push_str(format!("{}\n", default_instance).as_str());
+    }
+
+    fn last(&self, key: &str) -> Option<String> {
+        self.entries.iter().rev().find(|e| e.key == key).map(|e| e.value.clone())
+    }
+
+    /// Every value of `key`, for directives that may be given more than once. An empty assignment clears
+    /// the values given before it.
+    fn all(&self, key: &str) -> Option<Vec<String>> {
+        self.all_parsed(key)
+    }
+
+    /// Parses the last value of `key`. A value that doesn't parse leaves the directive unset, `validate`
+    /// reports it.
+    fn parsed<T: FromStr>(&self, key: &str) -> Option<T> {
+        self.last(key).and_then(|value| value.parse().ok())
+    }
+
+    /// Parses every value of `key`, for directives that may be given more than once. An empty assignment
+    /// clears the values given before it; values that don't parse are left out.
+    fn all_parsed<T: FromStr>(&self, key: &str) -> Option<Vec<T>> {
+        let mut values = Vec::new();
+        for entry in self.entries.iter().filter(|e| e.key == key) {
+            if entry.value.is_empty() {
+                values.clear();
+            } else if let Ok(value) = entry.value.parse() {
+                values.push(value);
+            }
         }
 
-        writeln!(f, "{s}")
+        if values.is_empty() {
+            None
+        } else {
+            Some(values)
+        }
+    }
+
+    /// Collects the command lines of an `Exec*` directive. An empty assignment clears the commands given
+    /// before it; command lines that don't parse are left out and reported by `validate`.
+    fn commands(&self, key: &str) -> Vec<ExecCommand> {
+        let mut commands = Vec::new();
+        for entry in self.entries.iter().filter(|e| e.key == key) {
+            if entry.value.is_empty() {
+                commands.clear();
+            } else if let Ok(lines) = exec::parse_command_lines(&entry.value) {
+                commands.extend(lines);
+            }
+        }
+
+        commands
+    }
+
+    fn comments(&self) -> Option<Vec<String>> {
+        if self.comments.is_empty() {
+            None
+        } else {
+            Some(self.comments.iter().map(|c| c.to_string()).collect())
+        }
+    }
+}
+
+impl From<&SectionView<'_>> for UnitSection {
+    fn from(view: &SectionView<'_>) -> Self {
+        UnitSection {
+            head: view.head(),
+            description: view.last("Description").unwrap_or_default(),
+            after: view.all("After"),
+            documentation: view.all("Documentation"),
+            requires: view.all("Requires"),
+            wants: view.all("Wants"),
+            binds_to: view.all("BindsTo"),
+            before: view.all("Before"),
+            conflicts: view.all("Conflicts"),
+            condition: view.last("Condition"),
+            assert: view.all("Assert"),
+            condition_path_exists: view.last("ConditionPathExists"),
+            comments: view.comments(),
+        }
+    }
+}
+
+impl From<&SectionView<'_>> for ServiceSection {
+    fn from(view: &SectionView<'_>) -> Self {
+        ServiceSection {
+            head: view.head(),
+            service_type: view.parsed("Type"),
+            exec_start: view.commands("ExecStart"),
+            exec_condition: view.commands("ExecCondition"),
+            start_pre: view.commands("ExecStartPre"),
+            start_post: view.commands("ExecStartPost"),
+            reload: view.commands("ExecReload"),
+            exec_stop: view.commands("ExecStop"),
+            stop_post: view.commands("ExecStopPost"),
+            restart_sec: view.parsed("RestartSec"),
+            restart: view.parsed("Restart"),
+            timeout_sec: view.parsed("TimeoutSec"),
+            environment_file: view.all("EnvironmentFile"),
+            kill_mode: view.parsed("KillMode"),
+            restart_prevent_exit_status: view.last("RestartPreventExitStatus"),
+            runtime_directory: view.all("RuntimeDirectory"),
+            runtime_directory_mode: view.last("RuntimeDirectoryMode"),
+            environment: view.all("Environment"),
+            standard_output: view.parsed("StandardOutput"),
+            limit_no_file: view.parsed("LimitNOFILE"),
+            comments: view.comments(),
+            working_directory: view.last("WorkingDirectory"),
+            user: view.last("User"),
+            start_limit_interval: view.parsed("StartLimitInterval"),
+            remain_after_exit: view.parsed("RemainAfterExit"),
+            cpu_quota: view.parsed("CPUQuota"),
+            memory_high: view.parsed("MemoryHigh"),
+            memory_max: view.parsed("MemoryMax"),
+        }
+    }
+}
+
+impl From<&SectionView<'_>> for InstallSection {
+    fn from(view: &SectionView<'_>) -> Self {
+        InstallSection {
+            head: view.head(),
+            wanted_by: view.all("WantedBy"),
+            required_by: view.all("RequiredBy"),
+            upheld_by: view.all("UpheldBy"),
+            alias: view.all("Alias"),
+            also: view.all("Also"),
+            default_instance: view.last("DefaultInstance"),
+            comments: view.comments(),
+        }
     }
 }
 
 #[derive(Default, Debug, Serialize, Deserialize)]
 #[serde(rename_all="camelCase")]
 pub struct SystemdFile {
-    // section: SectionType
+    /// Taken from the file suffix when parsing a path, otherwise from the type specific section present.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unit_type: Option<UnitType>,
     pub unit: UnitSection,
-    pub service: ServiceSection,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service: Option<ServiceSection>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub socket: Option<SocketSection>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timer: Option<TimerSection>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mount: Option<MountSection>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub automount: Option<AutomountSection>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathSection>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub swap: Option<SwapSection>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slice: Option<SliceSection>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<ScopeSection>,
     pub install: InstallSection,
+    /// Every section of the file in source order, including directives the typed sections above don't
+    /// cover. When present this is what gets written back out; changes to the typed sections only make it
+    /// into the output through `apply_typed_edits`, which `render` does.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sections: Vec<Section>,
+    /// The file exactly as it was parsed. Takes precedence over `sections` when writing the file back out,
+    /// and is dropped when `sections` are changed and no longer match it.
+    #[serde(skip)]
+    pub cst: Option<Cst>,
+}
+
+impl SystemdFile {
+    /// Builds the typed sections as a view on top of the generic `sections`. Type specific sections are
+    /// only built when the file has them.
+    pub fn from_sections(sections: Vec<Section>) -> Self {
+        fn typed<'a, T: From<&'a SectionView<'a>>>(view: &'a SectionView<'a>) -> Option<T> {
+            view.present.then(|| T::from(view))
+        }
+        let view = |name| SectionView::new(&sections, name);
+
+        let unit_type = UnitType::ALL.into_iter()
+            .find(|t| t.section_name().is_some_and(|name| sections.iter().any(|s| s.name == name)));
+
+        SystemdFile {
+            unit_type,
+            unit: UnitSection::from(&view("Unit")),
+            service: typed(&view("Service")),
+            socket: typed(&view("Socket")),
+            timer: typed(&view("Timer")),
+            mount: typed(&view("Mount")),
+            automount: typed(&view("Automount")),
+            path: typed(&view("Path")),
+            swap: typed(&view("Swap")),
+            slice: typed(&view("Slice")),
+            scope: typed(&view("Scope")),
+            install: InstallSection::from(&view("Install")),
+            sections,
+            cst: None,
+        }
+    }
+
+    /// Rebuilds the typed sections after `sections` was changed. A unit type that is already known is kept,
+    /// the `cst` is dropped.
+    pub fn refresh(&mut self) {
+        let unit_type = self.unit_type;
+        *self = SystemdFile::from_sections(std::mem::take(&mut self.sections));
+        self.unit_type = unit_type.or(self.unit_type);
+    }
+
+    /// Returns the first section called `name`.
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|s| s.name == name)
+    }
 }
 
 impl Display for SystemdFile {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}{}{}", self.unit, self.service, self.install)
+        if let Some(cst) = self.cst.as_ref() {
+            return write!(f, "{cst}");
+        }
+        if !self.sections.is_empty() {
+            for section in &self.sections {
+                writeln!(f, "{section}")?;
+            }
+            return Ok(());
+        }
+
+        write!(f, "{}", self.unit)?;
+        if let Some(service) = self.service.as_ref() {
+            write!(f, "{service}")?;
+        }
+        if let Some(socket) = self.socket.as_ref() {
+            write!(f, "{socket}")?;
+        }
+        if let Some(timer) = self.timer.as_ref() {
+            write!(f, "{timer}")?;
+        }
+        if let Some(mount) = self.mount.as_ref() {
+            write!(f, "{mount}")?;
+        }
+        if let Some(automount) = self.automount.as_ref() {
+            write!(f, "{automount}")?;
+        }
+        if let Some(path) = self.path.as_ref() {
+            write!(f, "{path}")?;
+        }
+        if let Some(swap) = self.swap.as_ref() {
+            write!(f, "{swap}")?;
+        }
+        if let Some(slice) = self.slice.as_ref() {
+            write!(f, "{slice}")?;
+        }
+        if let Some(scope) = self.scope.as_ref() {
+            write!(f, "{scope}")?;
+        }
+        write!(f, "{}", self.install)
+    }
+}
+
+/// Reads and parses the unit file at `path`.
+pub fn parse<P: AsRef<Path>>(path: P) -> Result<SystemdFile, Error> {
+    let file_name = path.as_ref().display().to_string();
+    let unparsed_file = fs::read_to_string(path.as_ref())
+        .map_err(|e| Error::from(e).with_file(&file_name))?;
+
+    let mut file = parse_str(&unparsed_file).map_err(|e| e.with_file(&file_name))?;
+    if let Some(unit_type) = UnitType::from_file_name(path.as_ref()) {
+        file.unit_type = Some(unit_type);
     }
+
+    Ok(file)
+}
+
+/// Parses a unit file from anything readable, e.g. a git blob or an entry of a container image layer.
+pub fn parse_reader<R: Read>(mut reader: R) -> Result<SystemdFile, Error> {
+    let mut unparsed_file = String::new();
+    reader.read_to_string(&mut unparsed_file)?;
+
+    parse_str(&unparsed_file)
 }
 
-pub fn parse(file_name: &str) -> Result<SystemdFile, String> {
-    let unparsed_file = fs::read_to_string(file_name)
-        .map_err(|e| format!("{} {}", file_name, e.to_string()))?;
+/// Parses the contents of a unit file held in memory.
+pub fn parse_bytes(bytes: &[u8]) -> Result<SystemdFile, Error> {
+    let unparsed_file = std::str::from_utf8(bytes)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    parse_str(unparsed_file)
+}
 
-    let file = SystemDParser::parse(Rule::file, &unparsed_file)
-        .map_err(|e| e.to_string())?
+/// Parses the contents of a unit file.
+pub fn parse_str(unparsed_file: &str) -> Result<SystemdFile, Error> {
+    let file = SystemDParser::parse(Rule::file, unparsed_file)
+        .map_err(|e| Error::from_pest(e.renamed_rules(rule_name)))?
         .next()
         // this is documented as never panics but i don't want to use expect or unwrap
-        .ok_or("unable to convert Option to Result")?;
-    let mut file_struct = SystemdFile::default();
+        .ok_or_else(|| Error::Syntax { location: Box::new(Location::default()), message: "empty parse tree".to_string() })?;
 
+    let mut errors = Vec::new();
+    let mut file_struct = assemble(file.clone(), unparsed_file, &mut errors);
+    file_struct.cst = Some(Cst::from_parse(file, unparsed_file));
+    match errors.into_iter().next() {
+        Some(e) => Err(e),
+        None => Ok(file_struct),
+    }
+}
+
+/// Reads and parses the unit file at `path` without stopping at the first problem. See `parse_str_recovering`.
+pub fn parse_recovering<P: AsRef<Path>>(path: P) -> Result<(SystemdFile, Vec<Error>), Error> {
+    let file_name = path.as_ref().display().to_string();
+    let unparsed_file = fs::read_to_string(path.as_ref())
+        .map_err(|e| Error::from(e).with_file(&file_name))?;
+
+    let (mut file_struct, errors) = parse_str_recovering(&unparsed_file);
+    if let Some(unit_type) = UnitType::from_file_name(path.as_ref()) {
+        file_struct.unit_type = Some(unit_type);
+    }
+    Ok((file_struct, errors.into_iter().map(|e| e.with_file(&file_name)).collect()))
+}
+
+/// Parses the contents of a unit file, skipping every line that can't be parsed instead of giving up.
+/// Returns whatever could be parsed together with a diagnostic for each skipped line.
+pub fn parse_str_recovering(unparsed_file: &str) -> (SystemdFile, Vec<Error>) {
+    let mut errors = Vec::new();
+    // lenient_file accepts any input, so this only fails if pest itself misbehaves
+    let file = match SystemDParser::parse(Rule::lenient_file, unparsed_file) {
+        Ok(mut pairs) => pairs.next(),
+        Err(e) => {
+            errors.push(Error::from_pest(e.renamed_rules(rule_name)));
+            None
+        }
+    };
+
+    match file {
+        Some(file) => {
+            let mut file_struct = assemble(file.clone(), unparsed_file, &mut errors);
+            file_struct.cst = Some(Cst::from_parse(file, unparsed_file));
+            (file_struct, errors)
+        }
+        None => (SystemdFile::default(), errors),
+    }
+}
+
+/// Builds a `SystemdFile` from the lines matched by the grammar. Lines that can't be used are reported to
+/// `errors` and skipped.
+fn assemble(file: Pair<'_, Rule>, unparsed_file: &str, errors: &mut Vec<Error>) -> SystemdFile {
+    let mut sections: Vec<Section> = Vec::new();
+    // comments belong to the header or assignment below them
+    let mut comments: Vec<String> = Vec::new();
 
     for line in file.into_inner() {
         match line.as_rule() {
-            Rule::unit => {
-                for item in line.into_inner() {
-                    match item.as_rule() {
-                        Rule::unit_tag => {
-                            file_struct.unit.head = item.as_span().as_str().to_string();
-                        }
-
-                        Rule::unit_prop => {
-                            for prop in item.into_inner() {
-                                match prop.as_rule() {
-                                    Rule::description => {
-                                        file_struct.unit.description = prop.as_span().as_str().to_string()
-                                    },
-                                    Rule::documentation => {
-                                        match file_struct.unit.documentation {
-                                            None => file_struct.unit.documentation = Some(vec![prop.as_span().as_str().to_string()]),
-                                            Some(mut x) => {
-                                                x.push(prop.as_str().to_string());
-                                                file_struct.unit.documentation = Some(x);
-                                            }
-                                        }
-                                    },
-                                    Rule::requires => {
-                                        match file_struct.unit.requires {
-                                            None => { file_struct.unit.requires = Some(vec![prop.as_str().to_string()]); }
-                                            Some(mut x) => {
-                                                x.push(prop.as_str().to_string());
-                                                file_struct.unit.requires = Some(x);
-                                            }
-                                        }
-                                    },
-                                    Rule::wants => {
-                                        match file_struct.unit.wants {
-                                            None => file_struct.unit.wants = Some(vec![prop.as_str().to_string()]),
-                                            Some(mut x) => {
-                                                x.push(prop.as_str().to_string());
-                                                file_struct.unit.wants = Some(x);
-                                            }
-                                        }
-                                    },
-                                    Rule::binds_to => {
-                                        file_struct.unit.binds_to = Some(prop.as_span().as_str().to_string())
-                                    },
-                                    Rule::before => {
-                                        match file_struct.unit.before {
-                                            None => file_struct.unit.before = Some(vec![prop.as_str().to_string()]),
-                                            Some(mut x) => {
-                                                x.push(prop.as_str().to_string());
-                                                file_struct.unit.before = Some(x);
-
-                                            }
-                                        }
-                                    },
-                                    Rule::after => {
-                                        match file_struct.unit.after {
-                                            None => { file_struct.unit.after = Some(vec![prop.as_str().to_string()]); }
-                                            Some(mut x) => {
-                                                x.push(prop.as_str().to_string());
-                                                file_struct.unit.after = Some(x);
-                                            }
-                                        }
-                                    },
-                                    Rule::conflicts => {
-                                        match file_struct.unit.conflicts {
-                                            None => file_struct.unit.comments = Some(vec![prop.as_str().to_string()]),
-                                            Some(mut x) => {
-                                                x.push(prop.as_str().to_string());
-                                                file_struct.unit.conflicts = Some(x);
-                                            }
-                                        }
-                                    },
-                                    Rule::condition => {
-                                        file_struct.unit.condition = Some(prop.as_str().to_string());
-                                    },
-                                    Rule::assert => {
-                                        match file_struct.unit.assert {
-                                            None => file_struct.unit.assert = Some(vec![prop.as_str().to_string()]),
-                                            Some(mut x) => {
-                                                x.push(prop.as_str().to_string());
-                                                file_struct.unit.assert = Some(x);
-                                            }
-                                        }
-                                    }
-                                    Rule::condition_path_exists => {
-                                        file_struct.unit.condition_path_exists = Some(prop.as_str().to_string())
-                                    },
-                                    Rule::comment => {
-                                        match file_struct.unit.comments {
-                                            None => file_struct.unit.comments = Some(vec![prop.as_str().to_string()]),
-                                            Some(mut x) => {
-                                                x.push(prop.as_str().to_string());
-                                                file_struct.unit.comments = Some(x);
-                                            }
-                                        }
-                                    },
-
-                                    _ => {}
-                                }
-                            }
-                        }
+            Rule::section_header => {
+                let (line_no, _) = line.line_col();
+                let name = line.into_inner()
+                    .find(|p| p.as_rule() == Rule::section_name)
+                    .map(|p| p.as_str().to_string())
+                    .unwrap_or_default();
 
-                        _ => {println!("other: {item:?}")}
-                    }
-                }
+                sections.push(Section {
+                    name,
+                    line: line_no,
+                    entries: vec![],
+                    comments: std::mem::take(&mut comments),
+                    trailing_comments: vec![],
+                });
             }
-            Rule::service => {
-                for item in line.into_inner() {
-                    match item.as_rule() {
-                        Rule::service_tag => {
-                            file_struct.service.head = item.as_span().as_str().to_string()
-                        },
-                        Rule::service_prop => {
-                            for prop in item.into_inner() {
-                                match prop.as_rule() {
-                                    Rule::service_type => {
-                                        file_struct.service.service_type = prop.as_span().as_str().to_string()
-                                    },
-                                    Rule::exec_start => {
-                                        file_struct.service.exec_start = prop.as_span().as_str()
-                                            .to_string()
-                                            .split_whitespace()
-                                            .map(|v| v.to_string())
-                                            .collect();
-                                    },
-                                    Rule::start_pre => {
-                                        file_struct.service.start_pre = Some(prop.as_span().as_str().to_string())
-                                    },
-                                    Rule::start_post => {
-                                        file_struct.service.start_post = Some(prop.as_span().as_str().to_string())
-                                    },
-                                    Rule::reload => {
-                                        // file_struct.service.reload = Some(prop.as_span().as_str().to_string())
-                                        match file_struct.service.reload {
-                                            None => file_struct.service.reload = Some(vec![prop.as_span().as_str().to_string()]),
-                                            Some(mut x) => {
-                                                x.push(prop.as_str().to_string());
-                                                file_struct.service.reload = Some(x);
-                                            }
-                                        }
-                                    },
-                                    Rule::exec_stop => {
-                                        file_struct.service.exec_stop = Some(prop.as_span().as_str().to_string())
-                                    },
-                                    Rule::stop_post => {
-                                        file_struct.service.stop_post = Some(prop.as_span().as_str().to_string())
-                                    },
-                                    Rule::restart_sec => {
-                                        file_struct.service.restart_sec = Some(prop.as_span().as_str().to_string())
-                                    },
-                                    Rule::restart => {
-                                        file_struct.service.restart = Some(prop.as_span().as_str().to_string())
-                                    },
-                                    Rule::timeout_sec => {
-                                        file_struct.service.timeout_sec = Some(prop.as_span().as_str().to_string())
-                                    },
-                                    Rule::environment_file => {
-                                        file_struct.service.environment_file = Some(prop.as_span().as_str().to_string())
-                                    },
-                                    Rule::kill_mode => {
-                                        file_struct.service.kill_mode = Some(prop.as_span().as_str().to_string())
-                                    },
-                                    Rule::restart_prevent_exit_status => {
-                                        file_struct.service.restart_prevent_exit_status = Some(prop.as_span().as_str().to_string())
-                                    },
-                                    Rule::runtime_directory => {
-                                        file_struct.service.runtime_directory = Some(prop.as_span().as_str().to_string())
-                                    },
-                                    Rule::runtime_directory_mode => {
-                                        file_struct.service.runtime_directory_mode = Some(prop.as_span().as_str().to_string())
-                                    },
-                                    Rule::environment => {
-                                        file_struct.service.environment = Some(prop.as_span().as_str().to_string())
-                                    },
-                                    Rule::standard_output => {
-                                        file_struct.service.standard_output = Some(prop.as_span().as_str().to_string())
-                                    },
-                                    Rule::limit_no_file => {
-                                        file_struct.service.limit_no_file = Some(prop.as_span().as_str().to_string())
-                                    },
-                                    Rule::working_directory => {
-                                        file_struct.service.working_directory = Some(prop.as_span().as_str().to_string())
-                                    },
-                                    Rule::user => {
-                                        file_struct.service.user = Some(prop.as_span().as_str().to_string())
-                                    },
-                                    Rule::start_limit_interval => {
-                                        file_struct.service.start_limit_interval = Some(prop.as_span().as_str().to_string())
-                                    },
-                                    Rule::comment => {
-                                        match file_struct.service.comments {
-                                            None => file_struct.service.comments = Some(vec![prop.as_str().to_string()]),
-                                            Some(mut x) => {
-                                                x.push(prop.as_str().to_string());
-                                                file_struct.service.comments = Some(x);
-                                            }
-                                        }
-                                    },
-                                    // Rule::unknown_symbols => {
-                                    //     println!("********** UNKNOWN SYMBOL FOUND *****************");
-                                    //     println!("{}", item.as_span().as_str());
-                                    // }
-                                    _ => {}
-                                }
-                            }
+            Rule::entry => {
+                let raw = line.as_str();
+                let mut entry = Entry::default();
+                for part in line.into_inner() {
+                    match part.as_rule() {
+                        Rule::key => {
+                            (entry.line, entry.column) = part.line_col();
+                            entry.key = part.as_str().to_string();
+                        }
+                        Rule::property_value => {
+                            entry.raw = part.as_str().to_string();
+                            entry.value = syntax::join_continuations(&entry.raw);
+                            entry.decoded = syntax::decode(&entry.value);
                         }
-                        _ => {println!("other: {item:?}")}
+                        _ => {}
                     }
                 }
-            }
-            Rule::install => {
-                // file_struct.install.head = line.as_span().as_str().to_string();
-                for item in line.into_inner() {
-                    match item.as_rule() {
-                        Rule::install_tag => {
-                            file_struct.install.head = item.as_span().as_str().to_string()
-                        }
-                        Rule::install_prop => {
-                            for prop in item.into_inner() {
-                                match prop.as_rule() {
-                                    Rule::wanted_by => {
-                                        file_struct.install.wanted_by = Some(prop.as_span().as_str().to_string())
-                                    }
-                                    Rule::required_by => {
-                                        file_struct.install.required_by = Some(prop.as_span().as_str().to_string())
-                                    }
-                                    Rule::alias => {
-                                        file_struct.install.alias = Some(prop.as_span().as_str().to_string())
-                                    }
-                                    Rule::also => {
-                                        file_struct.install.also = Some(prop.as_span().as_str().to_string())
-                                    }
-                                    Rule::default_instance => {
-                                        file_struct.install.default_instance = Some(prop.as_span().as_str().to_string())
-                                    }
-                                    Rule::comment => {
-                                        match file_struct.install.comments {
-                                            None => file_struct.install.comments = Some(vec![prop.as_str().to_string()]),
-                                            Some(mut x) => {
-                                                x.push(prop.as_str().to_string());
-                                                file_struct.install.comments = Some(x);
-                                            }
-                                        }
-                                    },
-                                    _ => {}
-                                }
-                            }
-                        }
-                        _ => {println!("other: {item:?}")}
+
+                match sections.last_mut() {
+                    Some(section) => {
+                        entry.comments = std::mem::take(&mut comments);
+                        section.entries.push(entry);
                     }
+                    None => errors.push(Error::Syntax {
+                        location: Box::new(Location::from_source(unparsed_file, entry.line, entry.column, raw.trim().chars().count())),
+                        message: "assignment outside of a section".to_string(),
+                    }),
                 }
             }
+            Rule::comment => comments.push(line.as_str().trim().to_string()),
+            Rule::invalid => {
+                let (line_no, _) = line.line_col();
+                let text = line.as_str().trim_end();
+                let indent = text.len() - text.trim_start().len();
+                let message = if text.trim_start().starts_with('[') {
+                    "malformed section header"
+                } else if text.contains('=') {
+                    "invalid directive name"
+                } else {
+                    "expected a [Section] header, a Key=Value assignment or a comment"
+                };
+
+                errors.push(Error::Syntax {
+                    location: Box::new(Location::from_source(unparsed_file, line_no, indent + 1, text.trim_start().chars().count())),
+                    message: message.to_string(),
+                });
+            }
 
             _ => {}
         }
     }
 
-    Ok(file_struct)
+    if let Some(section) = sections.last_mut() {
+        section.trailing_comments = comments;
+    }
+
+    SystemdFile::from_sections(sections)
+}
+
+/// Parses the unit file at `path` and checks it for problems the grammar can't see, such as unknown
+/// directives and repeated single valued directives. Returns every problem found.
+pub fn validate<P: AsRef<Path>>(path: P) -> Vec<Error> {
+    let file_name = path.as_ref().display().to_string();
+    match fs::read_to_string(path.as_ref()) {
+        Ok(unparsed_file) => validate_str(&unparsed_file)
+            .into_iter()
+            .map(|e| e.with_file(&file_name))
+            .collect(),
+        Err(e) => vec![Error::from(e).with_file(&file_name)],
+    }
+}
+
+/// Same as `validate` for a unit file held in memory.
+pub fn validate_str(unparsed_file: &str) -> Vec<Error> {
+    parse_and_check(unparsed_file).1
+}
+
+/// Parses the contents of a unit file like `parse_str_recovering` and adds the problems `validate` finds.
+fn parse_and_check(unparsed_file: &str) -> (SystemdFile, Vec<Error>) {
+    let (file, mut errors) = parse_str_recovering(unparsed_file);
+    errors.extend(check(&file, unparsed_file));
+    // report in file order rather than grouped by the pass that found them
+    errors.sort_by_key(|e| e.location().map(|l| (l.line, l.column)));
+
+    (file, errors)
+}
+
+fn check(file: &SystemdFile, unparsed_file: &str) -> Vec<Error> {
+    let mut errors = Vec::new();
+    // line of the first assignment of every single valued directive, per section
+    let mut seen: HashMap<(&str, &str), usize> = HashMap::new();
+
+    for section in &file.sections {
+        for entry in &section.entries {
+            let location = Box::new(Location::from_source(unparsed_file, entry.line, entry.column, entry.key.chars().count()));
+
+            if directives::is_known(&section.name, &entry.key) == Some(false) {
+                errors.push(Error::UnknownDirective {
+                    location,
+                    section: section.name.clone(),
+                    key: entry.key.clone(),
+                });
+                continue;
+            }
+
+            if let Err(message) = check_value(&section.name, &entry.key, &entry.value) {
+                errors.push(Error::InvalidValue {
+                    location: Box::new(Location::from_source(unparsed_file, entry.line, entry.column, entry.to_string().chars().count())),
+                    key: entry.key.clone(),
+                    value: entry.value.clone(),
+                    message,
+                });
+            }
+
+            if directives::is_list(&entry.key) {
+                continue;
+            }
+            // an empty assignment resets the directive, so whatever follows it isn't a duplicate
+            if entry.value.is_empty() {
+                seen.remove(&(section.name.as_str(), entry.key.as_str()));
+                continue;
+            }
+            match seen.get(&(section.name.as_str(), entry.key.as_str())) {
+                Some(first_line) => errors.push(Error::DuplicateKey {
+                    location,
+                    section: section.name.clone(),
+                    key: entry.key.clone(),
+                    first_line: *first_line,
+                }),
+                None => {
+                    seen.insert((section.name.as_str(), entry.key.as_str()), entry.line);
+                }
+            }
+        }
+    }
+
+    errors
+}
+
+/// Checks that the value of a directive with a known format can be interpreted. Empty values are resets and
+/// always valid.
+fn check_value(section: &str, key: &str, value: &str) -> Result<(), String> {
+    if value.is_empty() {
+        return Ok(());
+    }
+    if exec::EXEC_DIRECTIVES.contains(&key) {
+        exec::parse_command_lines(value)?;
+    }
+    if time::TIME_SPAN_DIRECTIVES.contains(&key) {
+        value.parse::<TimeSpan>()?;
+    }
+    if values::BOOLEAN_DIRECTIVES.contains(&key) {
+        value.parse::<SdBool>()?;
+    }
+    if values::MEMORY_LIMIT_DIRECTIVES.contains(&key) {
+        value.parse::<MemoryLimit>()?;
+    }
+    if values::PERCENT_DIRECTIVES.contains(&key) {
+        value.parse::<Percent>()?;
+    }
+    if values::RLIMIT_DIRECTIVES.contains(&key) {
+        RLimit::parse_for(key, value)?;
+    }
+    // `Type=` of a mount is the file system type
+    match (section, key) {
+        ("Service", "Type") => { value.parse::<ServiceType>()?; }
+        ("Service", "Restart") => { value.parse::<RestartPolicy>()?; }
+        (_, "KillMode") => { value.parse::<KillMode>()?; }
+        (_, "StandardOutput" | "StandardError") => { value.parse::<StandardOutput>()?; }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Names used for grammar rules in syntax errors.
+fn rule_name(rule: &Rule) -> String {
+    match rule {
+        Rule::section_header => "a [Section] header",
+        Rule::entry => "a Key=Value assignment",
+        Rule::key => "a directive name",
+        Rule::directive_separator => "`=`",
+        Rule::comment => "a comment",
+        Rule::EOI => "end of file",
+        _ => return format!("{rule:?}"),
+    }.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(errors: &[Error]) -> Vec<usize> {
+        errors.iter().map(|e| e.location().map_or(0, |l| l.line)).collect()
+    }
+
+    #[test]
+    fn the_recovering_parser_reports_every_malformed_line() {
+        let source = "[Unit]\nDescription=x\nnot an assignment\n[Service\nExecStart=/bin/a\n=value\n";
+        let (file, errors) = parse_str_recovering(source);
+        assert_eq!(lines(&errors), [3, 4, 6]);
+        // what could be parsed is kept
+        assert_eq!(file.unit.description, "x");
+        assert!(parse_str(source).is_err());
+    }
+
+    #[test]
+    fn validation_reports_syntax_and_directive_problems_in_file_order() {
+        let source = "[Service]\nType=simple\nbroken\nType=forking\nBogus=1\n";
+        let errors = validate_str(source);
+        assert_eq!(lines(&errors), [3, 4, 5]);
+        let codes: Vec<&str> = errors.iter().map(Error::code).collect();
+        assert_eq!(codes, ["syntax", "duplicate-key", "unknown-directive"]);
+        assert!(validate_str("[Service]\nType=simple\nExecStart=/bin/a\n").is_empty());
+    }
+
+    #[test]
+    fn unknown_sections_and_directives_are_kept() {
+        let file = parse_str("[X-Custom]\nFoo=bar\n[Service]\nX-Option=1\n").unwrap();
+        assert_eq!(file.section("X-Custom").and_then(|s| s.get("Foo")), Some("bar"));
+        assert_eq!(file.section("Service").and_then(|s| s.get("X-Option")), Some("1"));
+    }
+
+    #[test]
+    fn list_directives_are_cleared_by_an_empty_assignment() {
+        let file = parse_str("[Unit]\nAfter=a.service\nAfter=\nAfter=b.service\nWants=c.service\nWants=\n").unwrap();
+        assert_eq!(file.unit.after, Some(vec!["b.service".to_string()]));
+        assert_eq!(file.unit.wants, None);
+    }
+
+    #[test]
+    fn comments_stay_where_they_were_without_the_cst() {
+        let source = "# top\n[Unit]\nDescription=x\n\n[Service]\nType=simple\n# why\nLimitNOFILE=16384\n# end\n";
+        let json = serde_json::to_string(&parse_str(source).unwrap()).unwrap();
+        let file: SystemdFile = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            file.to_string(),
+            "# top\n[Unit]\nDescription=x\n\n[Service]\nType=simple\n# why\nLimitNOFILE=16384\n# end\n\n",
+        );
+    }
+
+    #[test]
+    fn install_keeps_every_value_of_its_list_directives() {
+        let file = parse_str("[Install]\nWantedBy=a.target\nWantedBy=b.target\nAlias=x.service\n").unwrap();
+        assert_eq!(file.install.wanted_by, Some(vec!["a.target".to_string(), "b.target".to_string()]));
+        assert_eq!(file.install.to_string(), "[Install]\nWantedBy=a.target\nWantedBy=b.target\nAlias=x.service\n\n");
+    }
+    #[test]
+    fn repeatable_directives_keep_every_value() {
+        let source = "[Unit]\nBindsTo=a.service\nBindsTo=b.service\n[Service]\nEnvironment=A=1\nEnvironment=\"B=2 3\"\n\
+                      EnvironmentFile=/etc/a\nEnvironmentFile=-/etc/b\nRuntimeDirectory=a\nRuntimeDirectory=b/c\n";
+        let file = parse_str(source).unwrap();
+        assert_eq!(file.unit.binds_to, Some(vec!["a.service".to_string(), "b.service".to_string()]));
+        let service = file.service.unwrap();
+        assert_eq!(service.environment, Some(vec!["A=1".to_string(), "\"B=2 3\"".to_string()]));
+        assert_eq!(service.environment_file, Some(vec!["/etc/a".to_string(), "-/etc/b".to_string()]));
+        assert_eq!(service.runtime_directory, Some(vec!["a".to_string(), "b/c".to_string()]));
+        assert!(service.to_string().contains("EnvironmentFile=/etc/a\nEnvironmentFile=-/etc/b\n"));
+    }
 }
@@ -0,0 +1,143 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use super::UnitType;
+
+/// A unit name split into its parts. `getty@tty1.service` has the prefix `getty`, the instance `tty1` and
+/// the type `service`; the template it is instantiated from is `getty@.service`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct UnitName {
+    pub prefix: String,
+    /// `None` for plain units, `Some("")` for templates.
+    pub instance: Option<String>,
+    pub unit_type: UnitType,
+}
+
+impl UnitName {
+    pub fn is_template(&self) -> bool {
+        self.instance.as_deref() == Some("")
+    }
+
+    pub fn is_instance(&self) -> bool {
+        self.instance.as_deref().is_some_and(|i| !i.is_empty())
+    }
+
+    /// The name of the template this unit is an instance of, e.g. `getty@.service`.
+    pub fn template(&self) -> Option<UnitName> {
+        self.instance.as_ref()?;
+        Some(UnitName { prefix: self.prefix.clone(), instance: Some(String::new()), unit_type: self.unit_type })
+    }
+
+    /// Instantiates a template, or replaces the instance of an instance name.
+    pub fn with_instance(&self, instance: &str) -> UnitName {
+        UnitName { prefix: self.prefix.clone(), instance: Some(instance.to_string()), unit_type: self.unit_type }
+    }
+
+    /// The name without the type suffix, e.g. `getty@tty1`.
+    pub fn stem(&self) -> String {
+        match self.instance.as_ref() {
+            Some(instance) => format!("{}@{instance}", self.prefix),
+            None => self.prefix.clone(),
+        }
+    }
+}
+
+impl Display for UnitName {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}", self.stem(), self.unit_type)
+    }
+}
+
+impl FromStr for UnitName {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() > 255 {
+            return Err(format!("unit name `{s}` is longer than 255 characters"));
+        }
+        let (stem, suffix) = s.rsplit_once('.').ok_or_else(|| format!("unit name `{s}` has no type suffix"))?;
+        let unit_type: UnitType = suffix.parse()?;
+        if let Some(c) = stem.chars().find(|c| !(c.is_ascii_alphanumeric() || ":-_.\\@".contains(*c))) {
+            return Err(format!("unit name `{s}` contains the invalid character `{c}`"));
+        }
+
+        let (prefix, instance) = match stem.split_once('@') {
+            Some((prefix, instance)) => (prefix, Some(instance.to_string())),
+            None => (stem, None),
+        };
+        if prefix.is_empty() {
+            return Err(format!("unit name `{s}` has an empty prefix"));
+        }
+
+        Ok(UnitName { prefix: prefix.to_string(), instance, unit_type })
+    }
+}
+
+/// Reverses the escaping of systemd-escape(1): `-` stands for `/` and `\xNN` for the byte `NN`.
+pub fn unescape(s: &str) -> String {
+    let mut bytes = Vec::new();
+    let mut rest = s.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        rest = tail;
+        if b == b'-' {
+            bytes.push(b'/');
+            continue;
+        }
+        if b == b'\\' && rest.first() == Some(&b'x') {
+            let hex = rest.get(1..3).and_then(|h| std::str::from_utf8(h).ok());
+            if let Some(byte) = hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                bytes.push(byte);
+                rest = &rest[3..];
+                continue;
+            }
+        }
+        bytes.push(b);
+    }
+
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Like `unescape`, for strings that name a path: the result always starts with a single `/`, so the root
+/// directory escaped as `-` comes back as `/`.
+pub fn unescape_path(s: &str) -> String {
+    let unescaped = unescape(s);
+    format!("/{}", unescaped.trim_start_matches('/'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_split_into_prefix_instance_and_type() {
+        let name: UnitName = "getty@tty1.service".parse().unwrap();
+        assert_eq!((name.prefix.as_str(), name.instance.as_deref(), name.unit_type), ("getty", Some("tty1"), UnitType::Service));
+        assert!(name.is_instance() && !name.is_template());
+        assert_eq!(name.stem(), "getty@tty1");
+        assert_eq!(name.template().unwrap().to_string(), "getty@.service");
+        assert_eq!(name.with_instance("tty2").to_string(), "getty@tty2.service");
+
+        let template: UnitName = "getty@.service".parse().unwrap();
+        assert!(template.is_template() && !template.is_instance());
+        assert_eq!(template.with_instance("tty1"), name);
+
+        let plain: UnitName = "sshd.socket".parse().unwrap();
+        assert_eq!((plain.instance.as_deref(), plain.template()), (None, None));
+    }
+
+    #[test]
+    fn invalid_names_are_rejected() {
+        assert!("sshd".parse::<UnitName>().is_err());
+        assert!("sshd.conf".parse::<UnitName>().is_err());
+        assert!("@tty1.service".parse::<UnitName>().is_err());
+        assert!("a b.service".parse::<UnitName>().is_err());
+        assert!(format!("{}.service", "a".repeat(250)).parse::<UnitName>().is_err());
+    }
+
+    #[test]
+    fn escaped_names_unescape_to_paths() {
+        assert_eq!(unescape(r"home-user\x2dname"), "home/user-name");
+        assert_eq!(unescape(r"a\x2"), r"a\x2");
+        assert_eq!(unescape_path("dev-sda1"), "/dev/sda1");
+        assert_eq!(unescape_path("-"), "/");
+    }
+}
@@ -0,0 +1,289 @@
+//! Machine readable reports of validation and lint results, for code review tools and CI.
+
+use serde_derive::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use super::lint::{Finding, Severity, RULES};
+
+/// The error codes `validate` reports, with what they mean.
+const VALIDATION_RULES: [(&str, &str); 5] = [
+    ("io", "the file could not be read"),
+    ("syntax", "the file doesn't follow the unit file syntax"),
+    ("unknown-directive", "systemd doesn't know the directive in the section it appears in"),
+    ("duplicate-key", "a directive that takes a single value is assigned more than once"),
+    ("invalid-value", "the value of a directive can't be interpreted"),
+];
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+/// What relative paths in a SARIF log are relative to, the directory the tool ran in.
+const SRCROOT: &str = "%SRCROOT%";
+
+#[derive(Serialize)]
+struct SarifLog<'a> {
+    #[serde(rename = "$schema")]
+    schema: &'static str,
+    version: &'static str,
+    runs: Vec<SarifRun<'a>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all="camelCase")]
+struct SarifRun<'a> {
+    tool: SarifTool,
+    /// Columns are counted in characters rather than the UTF-16 code units SARIF assumes otherwise.
+    column_kind: &'static str,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    original_uri_base_ids: BTreeMap<&'static str, SarifArtifactLocation>,
+    results: Vec<SarifResult<'a>>,
+}
+
+#[derive(Serialize)]
+struct SarifTool {
+    driver: SarifDriver,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all="camelCase")]
+struct SarifDriver {
+    name: &'static str,
+    version: &'static str,
+    rules: Vec<SarifRule>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all="camelCase")]
+struct SarifRule {
+    id: &'static str,
+    short_description: SarifMessage,
+    default_configuration: SarifConfiguration,
+}
+
+#[derive(Serialize)]
+struct SarifConfiguration {
+    level: Severity,
+}
+
+#[derive(Serialize)]
+struct SarifMessage {
+    text: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all="camelCase")]
+struct SarifResult<'a> {
+    rule_id: &'static str,
+    level: Severity,
+    message: SarifMessage,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    locations: Vec<SarifLocation<'a>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all="camelCase")]
+struct SarifLocation<'a> {
+    physical_location: SarifPhysicalLocation<'a>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all="camelCase")]
+struct SarifPhysicalLocation<'a> {
+    artifact_location: SarifArtifactLocation,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    region: Option<SarifRegion<'a>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all="camelCase")]
+struct SarifArtifactLocation {
+    uri: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uri_base_id: Option<&'static str>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all="camelCase")]
+struct SarifRegion<'a> {
+    start_line: usize,
+    start_column: usize,
+    /// One past the last column, as SARIF counts.
+    end_column: usize,
+    snippet: SarifSnippet<'a>,
+}
+
+#[derive(Serialize)]
+struct SarifSnippet<'a> {
+    text: &'a str,
+}
+
+/// Writes `findings` as a SARIF 2.1.0 log with a single run. Every lint rule and validation error code is
+/// described in the tool's rules, and findings without a file have no location. Absolute paths become
+/// `file://` URIs, relative ones stay relative to the current directory, which is given as `%SRCROOT%`.
+pub fn sarif(findings: &[Finding]) -> String {
+    let mut rules: Vec<SarifRule> = VALIDATION_RULES.iter()
+        .map(|(id, description)| sarif_rule(id, description, Severity::Error))
+        .collect();
+    rules.extend(RULES.iter().map(|rule| sarif_rule(rule.id, rule.summary, rule.severity)));
+
+    let results: Vec<SarifResult> = findings.iter()
+        .map(|finding| SarifResult {
+            rule_id: finding.rule,
+            level: finding.severity,
+            message: SarifMessage { text: finding.message.clone() },
+            locations: sarif_location(finding).into_iter().collect(),
+        })
+        .collect();
+
+    let mut original_uri_base_ids = BTreeMap::new();
+    let relative = results.iter()
+        .flat_map(|result| &result.locations)
+        .any(|location| location.physical_location.artifact_location.uri_base_id.is_some());
+    if let Some(current_dir) = std::env::current_dir().ok().filter(|_| relative) {
+        // a base URI ends in a slash
+        let uri = format!("{}/", file_uri(&current_dir).trim_end_matches('/'));
+        original_uri_base_ids.insert(SRCROOT, SarifArtifactLocation { uri, uri_base_id: None });
+    }
+
+    let log = SarifLog {
+        schema: SARIF_SCHEMA,
+        version: "2.1.0",
+        runs: vec![SarifRun {
+            tool: SarifTool {
+                driver: SarifDriver { name: env!("CARGO_PKG_NAME"), version: env!("CARGO_PKG_VERSION"), rules },
+            },
+            column_kind: "unicodeCodePoints",
+            original_uri_base_ids,
+            results,
+        }],
+    };
+
+    serde_json::to_string_pretty(&log).unwrap_or_default()
+}
+
+fn sarif_rule(id: &'static str, description: &str, level: Severity) -> SarifRule {
+    SarifRule {
+        id,
+        short_description: SarifMessage { text: description.to_string() },
+        default_configuration: SarifConfiguration { level },
+    }
+}
+
+fn sarif_location(finding: &Finding) -> Option<SarifLocation<'_>> {
+    let location = &finding.location;
+    let file = location.file.as_deref()?;
+    let region = (location.line > 0).then(|| SarifRegion {
+        start_line: location.line,
+        start_column: location.column.max(1),
+        end_column: location.column.max(1) + location.length.max(1),
+        snippet: SarifSnippet { text: &location.snippet },
+    });
+
+    Some(SarifLocation {
+        physical_location: SarifPhysicalLocation {
+            artifact_location: artifact_location(file),
+            region,
+        },
+    })
+}
+
+fn artifact_location(file: &str) -> SarifArtifactLocation {
+    let path = Path::new(file);
+    if path.is_absolute() {
+        SarifArtifactLocation { uri: file_uri(path), uri_base_id: None }
+    } else {
+        let relative = path.strip_prefix("./").unwrap_or(path);
+        SarifArtifactLocation { uri: uri_reference(&relative.to_string_lossy()), uri_base_id: Some(SRCROOT) }
+    }
+}
+
+/// The `file://` URI of an absolute path.
+fn file_uri(path: &Path) -> String {
+    format!("file://{}", uri_reference(&path.to_string_lossy()))
+}
+
+/// Turns a path into a URI reference by percent-encoding what a URI can't contain.
+fn uri_reference(path: &str) -> String {
+    let mut uri = String::new();
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b'@' | b'+' => {
+                uri.push(byte as char)
+            }
+            _ => uri.push_str(format!("%{byte:02X}").as_str()),
+        }
+    }
+
+    uri
+}
+
+/// Writes `findings` as GitHub Actions workflow commands, one `::error file=…,line=…::message` line per
+/// finding, which GitHub shows as annotations on the lines of the pull request they point at.
+pub fn github_annotations(findings: &[Finding]) -> String {
+    let mut out = String::new();
+    for finding in findings {
+        let command = match finding.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "notice",
+        };
+
+        let location = &finding.location;
+        let mut properties = Vec::new();
+        if let Some(file) = location.file.as_deref() {
+            properties.push(format!("file={}", escape_property(file)));
+        }
+        if location.line > 0 {
+            properties.push(format!("line={}", location.line));
+            properties.push(format!("col={}", location.column.max(1)));
+            properties.push(format!("endColumn={}", location.column.max(1) + location.length.max(1) - 1));
+        }
+        properties.push(format!("title={}", escape_property(finding.rule)));
+
+        out.push_str(format!("::{command} {}::{}\n", properties.join(","), escape_data(&finding.message)).as_str());
+    }
+
+    out
+}
+
+fn escape_data(text: &str) -> String {
+    text.replace('%', "%25").replace('\r', "%0D").replace('\n', "%0A")
+}
+
+fn escape_property(text: &str) -> String {
+    escape_data(text).replace(':', "%3A").replace(',', "%2C")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::error::Location;
+
+    fn finding(file: &str) -> Finding {
+        Finding {
+            rule: "missing-description",
+            severity: Severity::Note,
+            message: "no Description=".to_string(),
+            location: Location { file: Some(file.to_string()), line: 1, column: 1, length: 6, snippet: "[Unit]".to_string() },
+        }
+    }
+
+    #[test]
+    fn absolute_paths_are_file_uris() {
+        let log: serde_json::Value = serde_json::from_str(&sarif(&[finding("/etc/systemd/system/a b.service")])).unwrap();
+        let run = &log["runs"][0];
+        assert_eq!(run["columnKind"], "unicodeCodePoints");
+        assert!(run.get("originalUriBaseIds").is_none());
+        let location = &run["results"][0]["locations"][0]["physicalLocation"]["artifactLocation"];
+        assert_eq!(location["uri"], "file:///etc/systemd/system/a%20b.service");
+    }
+
+    #[test]
+    fn relative_paths_are_relative_to_srcroot() {
+        let log: serde_json::Value = serde_json::from_str(&sarif(&[finding("./units/a.service")])).unwrap();
+        let run = &log["runs"][0];
+        let location = &run["results"][0]["locations"][0]["physicalLocation"]["artifactLocation"];
+        assert_eq!(location["uri"], "units/a.service");
+        assert_eq!(location["uriBaseId"], SRCROOT);
+        assert!(run["originalUriBaseIds"][SRCROOT]["uri"].as_str().unwrap().starts_with("file:///"));
+    }
+}
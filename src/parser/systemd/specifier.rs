@@ -0,0 +1,234 @@
+use crate::parser::error::{Error, Location};
+use super::name::{self, UnitName};
+use super::SystemdFile;
+
+/// The values `%` specifiers expand to, see "Specifiers" in systemd.unit(5). Specifiers derived from the
+/// unit name are computed from `unit_name`; everything else is taken from the fields, which default to what
+/// the system manager would use. Fields left at `None` make the matching specifier an error.
+#[derive(Debug, Clone)]
+pub struct SpecifierContext {
+    pub unit_name: UnitName,
+    /// Path of the unit file, for `%y` and `%Y`.
+    pub fragment_path: Option<String>,
+    pub architecture: Option<String>,
+    pub boot_id: Option<String>,
+    pub machine_id: Option<String>,
+    pub hostname: Option<String>,
+    pub pretty_hostname: Option<String>,
+    pub kernel_release: Option<String>,
+    pub os_id: Option<String>,
+    pub os_version_id: Option<String>,
+    pub os_build_id: Option<String>,
+    pub os_variant_id: Option<String>,
+    pub os_image_id: Option<String>,
+    pub os_image_version: Option<String>,
+    pub user_name: Option<String>,
+    pub uid: Option<String>,
+    pub group_name: Option<String>,
+    pub gid: Option<String>,
+    pub home: Option<String>,
+    pub shell: Option<String>,
+    pub runtime_dir: String,
+    pub state_dir: String,
+    pub cache_dir: String,
+    pub logs_dir: String,
+    pub config_dir: String,
+    pub credentials_dir: Option<String>,
+    pub tmp_dir: String,
+    pub var_tmp_dir: String,
+}
+
+impl SpecifierContext {
+    /// A context for `unit_name` run by the system manager as root. Host specific values such as the host
+    /// name are left unset.
+    pub fn system(unit_name: UnitName) -> Self {
+        SpecifierContext {
+            unit_name,
+            fragment_path: None,
+            architecture: None,
+            boot_id: None,
+            machine_id: None,
+            hostname: None,
+            pretty_hostname: None,
+            kernel_release: None,
+            os_id: None,
+            os_version_id: None,
+            os_build_id: None,
+            os_variant_id: None,
+            os_image_id: None,
+            os_image_version: None,
+            user_name: Some("root".to_string()),
+            uid: Some("0".to_string()),
+            group_name: Some("root".to_string()),
+            gid: Some("0".to_string()),
+            home: Some("/root".to_string()),
+            shell: Some("/bin/sh".to_string()),
+            runtime_dir: "/run".to_string(),
+            state_dir: "/var/lib".to_string(),
+            cache_dir: "/var/cache".to_string(),
+            logs_dir: "/var/log".to_string(),
+            config_dir: "/etc".to_string(),
+            credentials_dir: None,
+            tmp_dir: "/tmp".to_string(),
+            var_tmp_dir: "/var/tmp".to_string(),
+        }
+    }
+
+    /// Returns what `%c` expands to.
+    pub fn resolve(&self, c: char) -> Result<String, String> {
+        let unit = &self.unit_name;
+        let instance = unit.instance.clone().unwrap_or_default();
+        let set = |value: &Option<String>| value.clone().ok_or_else(|| format!("specifier %{c} is not set in the context"));
+
+        let value = match c {
+            '%' => "%".to_string(),
+            'n' => unit.to_string(),
+            'N' => unit.stem(),
+            'p' => unit.prefix.clone(),
+            'P' => name::unescape(&unit.prefix),
+            'i' => instance,
+            'I' => name::unescape(&instance),
+            'j' => unit.prefix.rsplit('-').next().unwrap_or_default().to_string(),
+            'J' => name::unescape(unit.prefix.rsplit('-').next().unwrap_or_default()),
+            'f' => name::unescape_path(if unit.is_instance() { &instance } else { &unit.prefix }),
+            'y' => set(&self.fragment_path)?,
+            'Y' => {
+                let path = set(&self.fragment_path)?;
+                path.rsplit_once('/').map(|(dir, _)| dir.to_string()).unwrap_or_default()
+            }
+            'a' => set(&self.architecture)?,
+            'b' => set(&self.boot_id)?,
+            'm' => set(&self.machine_id)?,
+            'H' => set(&self.hostname)?,
+            'l' => set(&self.hostname)?.split('.').next().unwrap_or_default().to_string(),
+            'q' => set(&self.pretty_hostname)?,
+            'v' => set(&self.kernel_release)?,
+            'o' => set(&self.os_id)?,
+            'w' => set(&self.os_version_id)?,
+            'B' => set(&self.os_build_id)?,
+            'W' => set(&self.os_variant_id)?,
+            'M' => set(&self.os_image_id)?,
+            'A' => set(&self.os_image_version)?,
+            'u' => set(&self.user_name)?,
+            'U' => set(&self.uid)?,
+            'g' => set(&self.group_name)?,
+            'G' => set(&self.gid)?,
+            'h' => set(&self.home)?,
+            's' => set(&self.shell)?,
+            't' => self.runtime_dir.clone(),
+            'S' => self.state_dir.clone(),
+            'C' => self.cache_dir.clone(),
+            'L' => self.logs_dir.clone(),
+            'E' => self.config_dir.clone(),
+            'd' => set(&self.credentials_dir)?,
+            'T' => self.tmp_dir.clone(),
+            'V' => self.var_tmp_dir.clone(),
+            _ => return Err(format!("unknown specifier %{c}")),
+        };
+
+        Ok(value)
+    }
+
+    /// Replaces every specifier in `value`.
+    pub fn expand(&self, value: &str) -> Result<String, String> {
+        let mut expanded = String::new();
+        let mut chars = value.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                expanded.push(c);
+                continue;
+            }
+            let specifier = chars.next().ok_or("value ends in an incomplete specifier")?;
+            expanded.push_str(&self.resolve(specifier)?);
+        }
+
+        Ok(expanded)
+    }
+}
+
+impl SystemdFile {
+    /// Returns a copy of the file with the specifiers in every value expanded against `context`, together
+    /// with an error for each value that can't be expanded. Those values are kept as they are written.
+    pub fn expand_specifiers(&self, context: &SpecifierContext) -> (SystemdFile, Vec<Error>) {
+        let mut sections = self.sections.clone();
+        let mut errors = Vec::new();
+        for entry in sections.iter_mut().flat_map(|s| s.entries.iter_mut()) {
+            match context.expand(&entry.value) {
+                Ok(expanded) => {
+                    entry.decoded = super::syntax::decode(&expanded);
+                    entry.value = expanded;
+                }
+                Err(message) => errors.push(Error::InvalidValue {
+                    location: Box::new(Location {
+                        file: entry.source.clone(),
+                        line: entry.line,
+                        column: entry.column,
+                        length: entry.to_string().chars().count(),
+                        snippet: entry.to_string(),
+                    }),
+                    key: entry.key.clone(),
+                    value: entry.value.clone(),
+                    message,
+                }),
+            }
+        }
+
+        let mut file = SystemdFile { unit_type: self.unit_type, sections, ..Default::default() };
+        file.refresh();
+
+        (file, errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::parse_str;
+
+    fn context(name: &str) -> SpecifierContext {
+        SpecifierContext::system(name.parse().unwrap())
+    }
+
+    #[test]
+    fn specifiers_come_from_the_unit_name() {
+        let context = context(r"backup@home-user\x2dname.service");
+        let expand = |value: &str| context.expand(value).unwrap();
+        assert_eq!(expand("%n %N %p"), r"backup@home-user\x2dname.service backup@home-user\x2dname backup");
+        assert_eq!(expand("%i"), r"home-user\x2dname");
+        assert_eq!(expand("%I"), "home/user-name");
+        assert_eq!(expand("%f"), "/home/user-name");
+        assert_eq!(expand("100%% %u:%U %t"), "100% root:0 /run");
+    }
+
+    #[test]
+    fn unset_and_unknown_specifiers_are_errors() {
+        let context = context("a.service");
+        assert_eq!(context.expand("--host %H"), Err("specifier %H is not set in the context".to_string()));
+        assert_eq!(context.expand("%z"), Err("unknown specifier %z".to_string()));
+        assert_eq!(context.expand("50%"), Err("value ends in an incomplete specifier".to_string()));
+
+        let context = SpecifierContext { hostname: Some("box.example.com".to_string()), ..context };
+        assert_eq!(context.expand("%H %l"), Ok("box.example.com box".to_string()));
+    }
+
+    #[test]
+    fn files_expand_every_value() {
+        let file = parse_str("[Unit]\nAfter=dev-%i.device\n[Service]\nExecStart=/bin/getty %I\n").unwrap();
+        let (expanded, errors) = file.expand_specifiers(&context("getty@tty1.service"));
+        assert!(errors.is_empty());
+        assert_eq!(expanded.unit.after, Some(vec!["dev-tty1.device".to_string()]));
+        assert_eq!(expanded.service.unwrap().exec_start[0].argv, ["/bin/getty", "tty1"]);
+    }
+
+    #[test]
+    fn values_that_cant_be_expanded_are_kept_and_reported() {
+        let source = "[Unit]\nAfter=dev-%i.device\n[Service]\nExecStart=/bin/getty --host %H %I\nUser=%u\n";
+        let (expanded, errors) = parse_str(source).unwrap().expand_specifiers(&context("getty@tty1.service"));
+        assert_eq!(expanded.unit.after, Some(vec!["dev-tty1.device".to_string()]));
+        let service = expanded.service.unwrap();
+        assert_eq!(service.exec_start[0].argv, ["/bin/getty", "--host", "%H", "%I"]);
+        assert_eq!(service.user.as_deref(), Some("root"));
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].to_string(), "4:1: invalid value `/bin/getty --host %H %I` for `ExecStart`: specifier %H is not set in the context");
+    }
+}
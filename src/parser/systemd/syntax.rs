@@ -0,0 +1,238 @@
+//! Lexical rules of systemd.syntax(7): line continuations, quoting and C-style escapes.
+
+/// Turns the raw text of a value that may span several physical lines into its logical value. A backslash
+/// at the end of a line joins it with the next one and is replaced by a space. Comment lines inside a
+/// continuation are dropped, as systemd does.
+pub fn join_continuations(raw: &str) -> String {
+    let mut value = String::new();
+    for (i, line) in raw.split('\n').enumerate() {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let trimmed = line.trim_start();
+        if i > 0 && (trimmed.starts_with('#') || trimmed.starts_with(';')) {
+            continue;
+        }
+
+        match continued(line) {
+            Some(line) => {
+                value.push_str(line);
+                value.push(' ');
+            }
+            None => {
+                value.push_str(line);
+                break;
+            }
+        }
+    }
+
+    value.trim().to_string()
+}
+
+/// Returns the line without its trailing backslash if it ends in one that isn't itself escaped.
+pub(super) fn continued(line: &str) -> Option<&str> {
+    let backslashes = line.chars().rev().take_while(|c| *c == '\\').count();
+    if backslashes % 2 == 1 {
+        line.strip_suffix('\\')
+    } else {
+        None
+    }
+}
+
+/// Resolves quotes and escapes in a value, keeping the whitespace between words as it is. Escapes that
+/// systemd doesn't know are kept verbatim, as is an unterminated quote. Use `split_words` where the
+/// boundaries between quoted words matter.
+pub fn decode(value: &str) -> String {
+    let mut decoded = String::new();
+    let mut chars = value.chars().peekable();
+    // the open quote and where it would go in `decoded`
+    let mut quote: Option<(char, usize)> = None;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match unescape_next(&mut chars) {
+                Ok(unescaped) => decoded.push(unescaped),
+                Err(_) => decoded.push('\\'),
+            },
+            '"' | '\'' if quote.is_some_and(|(q, _)| q == c) => quote = None,
+            '"' | '\'' if quote.is_none() => quote = Some((c, decoded.len())),
+            _ => decoded.push(c),
+        }
+    }
+    if let Some((q, at)) = quote {
+        decoded.insert(at, q);
+    }
+
+    decoded
+}
+
+/// Splits a value into words the way systemd does for settings that take a list: words are separated by
+/// whitespace, single or double quotes group a word, and C-style escapes are resolved.
+pub fn split_words(value: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote: Option<char> = None;
+    let mut chars = value.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, quote) {
+            ('\\', _) => {
+                let unescaped = unescape_next(&mut chars)?;
+                word.get_or_insert_with(String::new).push(unescaped);
+            }
+            (c, Some(q)) if c == q => quote = None,
+            (c, Some(_)) => word.get_or_insert_with(String::new).push(c),
+            ('"' | '\'', None) => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (c, None) if c.is_whitespace() => {
+                if let Some(w) = word.take() {
+                    words.push(w);
+                }
+            }
+            (c, None) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    if let Some(q) = quote {
+        return Err(format!("unterminated {q} quote"));
+    }
+    if let Some(w) = word {
+        words.push(w);
+    }
+
+    Ok(words)
+}
+
+/// Quotes a word if it needs it so that `split_words` gives it back unchanged.
+pub fn quote_word(word: &str) -> String {
+    let needs_quoting = word.is_empty()
+        || word.chars().any(|c| c.is_whitespace() || matches!(c, '"' | '\'' | '\\'));
+    if !needs_quoting {
+        return word.to_string();
+    }
+
+    let mut quoted = String::from("\"");
+    for c in word.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+
+    quoted
+}
+
+/// Resolves the escape sequence following a backslash. On failure nothing but the backslash has been
+/// consumed.
+fn unescape_next<I: Iterator<Item = char> + Clone>(chars: &mut std::iter::Peekable<I>) -> Result<char, String> {
+    let c = *chars.peek().ok_or("trailing backslash")?;
+    let simple = match c {
+        'a' => Some('\u{07}'),
+        'b' => Some('\u{08}'),
+        'f' => Some('\u{0c}'),
+        'n' => Some('\n'),
+        'r' => Some('\r'),
+        't' => Some('\t'),
+        'v' => Some('\u{0b}'),
+        's' => Some(' '),
+        '\\' | '"' | '\'' => Some(c),
+        _ => None,
+    };
+    if let Some(unescaped) = simple {
+        chars.next();
+        return Ok(unescaped);
+    }
+
+    let (skip, digits, radix) = match c {
+        'x' => (1, 2, 16),
+        'u' => (1, 4, 16),
+        'U' => (1, 8, 16),
+        '0'..='7' => (0, 3, 8),
+        _ => return Err(format!("unknown escape sequence \\{c}")),
+    };
+    let code: String = chars.clone().skip(skip).take(digits).collect();
+    if code.chars().count() != digits {
+        return Err(format!("truncated escape sequence \\{c}"));
+    }
+    let sequence = if skip == 1 { format!("{c}{code}") } else { code.clone() };
+    let unescaped = u32::from_str_radix(&code, radix).ok()
+        .and_then(char::from_u32)
+        .ok_or_else(|| format!("invalid escape sequence \\{sequence}"))?;
+
+    for _ in 0..skip + digits {
+        chars.next();
+    }
+
+    Ok(unescaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::parse_str;
+
+    fn words(value: &str) -> Vec<String> {
+        split_words(value).unwrap()
+    }
+
+    #[test]
+    fn continuations_join_lines_and_skip_comments() {
+        assert_eq!(join_continuations("/bin/a \\\n  --flag \\\r\n# comment\n; other\n  arg\nnext"), "/bin/a    --flag    arg");
+        assert_eq!(join_continuations("a\\\\\nb"), "a\\\\");
+        assert_eq!(join_continuations("  value  "), "value");
+    }
+
+    #[test]
+    fn escapes_are_resolved() {
+        assert_eq!(words(r"a\x20b \n\t\s \101\u00e9\U0001F600"), ["a b", "\n\t ", "Aé😀"]);
+        assert_eq!(words(r#"\\ \" \'"#), ["\\", "\"", "'"]);
+        assert_eq!(split_words(r"a\q"), Err("unknown escape sequence \\q".to_string()));
+        assert_eq!(split_words(r"a\x4"), Err("truncated escape sequence \\x".to_string()));
+        assert_eq!(split_words(r"a\ud800"), Err("invalid escape sequence \\ud800".to_string()));
+        assert_eq!(split_words("a\\"), Err("trailing backslash".to_string()));
+    }
+
+    #[test]
+    fn quotes_group_words() {
+        assert_eq!(words(r#"-sl="10.1.1.1" "my arg" 'single "inner"' "" x"#), [
+            "-sl=10.1.1.1", "my arg", "single \"inner\"", "", "x",
+        ]);
+        assert_eq!(words("  a\t b  "), ["a", "b"]);
+        assert_eq!(split_words("\"open"), Err("unterminated \" quote".to_string()));
+        assert_eq!(split_words("it's"), Err("unterminated ' quote".to_string()));
+    }
+
+    #[test]
+    fn decoding_keeps_the_space_between_words() {
+        assert_eq!(decode(r#"a  "b c"\x21 'd'"#), "a  b c! d");
+        // escapes systemd doesn't know stay as they are
+        assert_eq!(decode(r"C:\q"), r"C:\q");
+
+        // and so does a quote that is never closed
+        assert_eq!(decode("it's"), "it's");
+        assert_eq!(decode(r#"say "hi\x21"#), r#"say "hi!"#);
+        assert_eq!(decode(r#""a" "b"#), r#"a "b"#);
+    }
+
+    #[test]
+    fn quoted_words_split_back_unchanged() {
+        for word in ["plain", "", "two words", "quote\"d", "back\\slash", "line\nbreak", "it's"] {
+            assert_eq!(words(&quote_word(word)), [word]);
+        }
+        assert_eq!(quote_word("plain"), "plain");
+    }
+
+    #[test]
+    fn entries_keep_the_raw_and_decoded_value() {
+        let file = parse_str("[Service]\nEnvironment=\"A=1 2\" \\\n  B=\\x33\n").unwrap();
+        let entry = &file.sections[0].entries[0];
+        assert_eq!(entry.raw, "\"A=1 2\" \\\n  B=\\x33");
+        assert_eq!(entry.value, "\"A=1 2\"    B=\\x33");
+        assert_eq!(entry.decoded, "A=1 2    B=3");
+        assert_eq!(entry.words().unwrap(), ["A=1 2", "B=3"]);
+    }
+}
@@ -0,0 +1,31 @@
+//! Fixtures for the tests of the modules that read unit files from disk.
+
+use std::fs;
+use std::path::PathBuf;
+
+/// A fresh directory below the temporary directory with `files` at the given paths. `name` tells the
+/// directories of different tests apart, e.g. `loader-empty`.
+pub fn tree(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("systemd-parser-{}-{name}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+    for (path, content) in files {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    root
+}
+
+/// A root with `files` below `/usr/lib/systemd/system`, see `tree`.
+pub fn unit_root(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let root = tree(name, &[]);
+    let dir = root.join("usr/lib/systemd/system");
+    fs::create_dir_all(&dir).unwrap();
+    for (file, content) in files {
+        fs::write(dir.join(file), content).unwrap();
+    }
+
+    root
+}
@@ -0,0 +1,245 @@
+use serde_derive::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Every directive whose value is a time span.
+pub const TIME_SPAN_DIRECTIVES: &[&str] = &[
+    // [Unit]
+    "JobTimeoutSec", "JobRunningTimeoutSec", "StartLimitIntervalSec", "StartLimitInterval",
+    // [Service]
+    "RestartSec", "RestartMaxDelaySec", "TimeoutSec", "TimeoutStartSec", "TimeoutStopSec", "TimeoutAbortSec",
+    "RuntimeMaxSec", "RuntimeRandomizedExtraSec", "WatchdogSec", "TimeoutCleanSec",
+    // [Socket]
+    "KeepAliveTimeSec", "KeepAliveIntervalSec", "DeferAcceptSec", "TriggerLimitIntervalSec",
+    // [Timer]
+    "OnActiveSec", "OnBootSec", "OnStartupSec", "OnUnitActiveSec", "OnUnitInactiveSec", "AccuracySec",
+    "RandomizedDelaySec",
+    // [Automount]
+    "TimeoutIdleSec",
+];
+
+const USEC: u128 = 1;
+const MSEC: u128 = 1000 * USEC;
+const SEC: u128 = 1000 * MSEC;
+const MINUTE: u128 = 60 * SEC;
+const HOUR: u128 = 60 * MINUTE;
+const DAY: u128 = 24 * HOUR;
+const WEEK: u128 = 7 * DAY;
+const MONTH: u128 = 2_629_800 * SEC;
+const YEAR: u128 = 31_557_600 * SEC;
+
+/// Unit names accepted by systemd, see systemd.time(7).
+const UNITS: &[(&[&str], u128)] = &[
+    (&["usec", "us", "µs", "μs"], USEC),
+    (&["msec", "ms"], MSEC),
+    (&["seconds", "second", "sec", "s"], SEC),
+    (&["minutes", "minute", "min", "m"], MINUTE),
+    (&["hours", "hour", "hr", "h"], HOUR),
+    (&["days", "day", "d"], DAY),
+    (&["weeks", "week", "w"], WEEK),
+    (&["months", "month", "M"], MONTH),
+    (&["years", "year", "y"], YEAR),
+];
+
+/// Units used when writing a time span back out, largest first.
+const CANONICAL_UNITS: &[(&str, u128)] = &[
+    ("y", YEAR), ("month", MONTH), ("w", WEEK), ("d", DAY), ("h", HOUR), ("min", MINUTE), ("s", SEC),
+    ("ms", MSEC), ("us", USEC),
+];
+
+/// A time span such as `1min 30s`, see "Parsing time spans" in systemd.time(7). Spans are kept at the
+/// microsecond resolution systemd uses and compare as durations, with `infinity` greater than all of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum TimeSpan {
+    Finite(Duration),
+    Infinity,
+}
+
+impl TimeSpan {
+    pub fn is_infinite(&self) -> bool {
+        *self == TimeSpan::Infinity
+    }
+
+    /// The span as a `Duration`, `None` for `infinity`.
+    pub fn as_duration(&self) -> Option<Duration> {
+        match self {
+            TimeSpan::Finite(duration) => Some(*duration),
+            TimeSpan::Infinity => None,
+        }
+    }
+
+    /// Parses a span whose bare numbers are in `default_unit`, which must be one of the unit names systemd
+    /// accepts.
+    pub fn parse_with_default_unit(s: &str, default_unit: &str) -> Result<Self, String> {
+        let s = s.trim();
+        if s == "infinity" {
+            return Ok(TimeSpan::Infinity);
+        }
+        let default_unit = unit_factor(default_unit).ok_or_else(|| format!("unknown time unit `{default_unit}`"))?;
+        if s.is_empty() {
+            return Err("empty time span".to_string());
+        }
+
+        let mut usec: u128 = 0;
+        let mut rest = s;
+        while !rest.is_empty() {
+            let number_length = rest.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(rest.len());
+            let number = &rest[..number_length];
+            if number.is_empty() || number == "." {
+                return Err(format!("expected a number in time span `{s}`"));
+            }
+            rest = rest[number_length..].trim_start();
+
+            let unit_length = rest.find(|c: char| !c.is_alphabetic()).unwrap_or(rest.len());
+            let factor = match &rest[..unit_length] {
+                "" => default_unit,
+                unit => unit_factor(unit).ok_or_else(|| format!("unknown time unit `{unit}` in `{s}`"))?,
+            };
+            rest = rest[unit_length..].trim_start();
+
+            let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+            if fraction.contains('.') {
+                return Err(format!("invalid number `{number}` in time span `{s}`"));
+            }
+            let whole: u128 = if whole.is_empty() { 0 } else {
+                whole.parse().map_err(|_| format!("number `{whole}` in time span `{s}` is too large"))?
+            };
+            // fractions below a microsecond are dropped, as systemd does
+            let mut fraction_usec: u128 = 0;
+            let mut scale = factor;
+            for digit in fraction.bytes() {
+                scale /= 10;
+                fraction_usec += u128::from(digit - b'0') * scale;
+            }
+            usec = whole.checked_mul(factor)
+                .and_then(|v| v.checked_add(fraction_usec))
+                .and_then(|v| v.checked_add(usec))
+                .filter(|v| *v < u128::from(u64::MAX))
+                .ok_or_else(|| format!("time span `{s}` is too large"))?;
+        }
+
+        Ok(TimeSpan::Finite(Duration::from_micros(usec as u64)))
+    }
+}
+
+fn unit_factor(unit: &str) -> Option<u128> {
+    UNITS.iter().find(|(names, _)| names.contains(&unit)).map(|(_, factor)| *factor)
+}
+
+impl Default for TimeSpan {
+    fn default() -> Self {
+        TimeSpan::Finite(Duration::ZERO)
+    }
+}
+
+impl From<Duration> for TimeSpan {
+    fn from(duration: Duration) -> Self {
+        TimeSpan::Finite(duration)
+    }
+}
+
+impl FromStr for TimeSpan {
+    type Err = String;
+
+    /// Parses a span whose bare numbers are seconds.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        TimeSpan::parse_with_default_unit(s, "s")
+    }
+}
+
+impl TryFrom<String> for TimeSpan {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<TimeSpan> for String {
+    fn from(span: TimeSpan) -> Self {
+        span.to_string()
+    }
+}
+
+impl Display for TimeSpan {
+    /// Writes the span the way systemd does, e.g. `1min 30s` or `infinity`; zero is written as `0`.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut usec = match self {
+            TimeSpan::Finite(duration) => duration.as_micros(),
+            TimeSpan::Infinity => return write!(f, "infinity"),
+        };
+        if usec == 0 {
+            return write!(f, "0");
+        }
+
+        let mut separator = "";
+        for (unit, factor) in CANONICAL_UNITS {
+            if usec >= *factor {
+                write!(f, "{separator}{}{unit}", usec / factor)?;
+                usec %= factor;
+                separator = " ";
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(s: &str) -> TimeSpan {
+        s.parse().unwrap()
+    }
+
+    fn millis(ms: u64) -> TimeSpan {
+        TimeSpan::Finite(Duration::from_millis(ms))
+    }
+
+    #[test]
+    fn spans_parse_like_systemd() {
+        assert_eq!(span("1min 30s"), millis(90_000));
+        assert_eq!(span("1min30s"), millis(90_000));
+        assert_eq!(span("500ms"), millis(500));
+        assert_eq!(span("90"), millis(90_000));
+        assert_eq!(span("1.5h"), millis(5_400_000));
+        assert_eq!(span("2 weeks 1d"), millis(15 * 24 * 3_600_000));
+        assert_eq!(span(" infinity "), TimeSpan::Infinity);
+        assert_eq!(TimeSpan::parse_with_default_unit("250", "ms"), Ok(millis(250)));
+        assert_eq!(TimeSpan::parse_with_default_unit("250", "ms").unwrap().as_duration(), Some(Duration::from_millis(250)));
+    }
+
+    #[test]
+    fn invalid_spans_are_rejected() {
+        assert_eq!("5 fortnights".parse::<TimeSpan>(), Err("unknown time unit `fortnights` in `5 fortnights`".to_string()));
+        assert_eq!("".parse::<TimeSpan>(), Err("empty time span".to_string()));
+        assert_eq!("ms".parse::<TimeSpan>(), Err("expected a number in time span `ms`".to_string()));
+        assert!("1.2.3s".parse::<TimeSpan>().is_err());
+        assert!("-5s".parse::<TimeSpan>().is_err());
+        assert!("99999999999999y".parse::<TimeSpan>().is_err());
+        assert!(TimeSpan::parse_with_default_unit("5", "fortnight").is_err());
+    }
+
+    #[test]
+    fn spans_display_canonically_and_parse_back() {
+        let cases = [
+            ("90", "1min 30s"), ("500ms", "500ms"), ("0", "0"), ("infinity", "infinity"),
+            ("1d 25h", "2d 1h"), ("1.5s", "1s 500ms"), ("3us", "3us"),
+        ];
+        for (s, canonical) in cases {
+            assert_eq!(span(s).to_string(), canonical, "{s}");
+            assert_eq!(span(canonical), span(s), "{s}");
+        }
+    }
+
+    #[test]
+    fn infinity_is_longer_than_any_span() {
+        assert!(TimeSpan::Infinity > span("100y"));
+        assert!(span("1s") > span("999ms"));
+        assert_eq!(serde_json::to_string(&span("90")).unwrap(), "\"1min 30s\"");
+        assert_eq!(serde_json::from_str::<TimeSpan>("\"infinity\"").unwrap(), TimeSpan::Infinity);
+    }
+}
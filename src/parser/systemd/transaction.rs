@@ -0,0 +1,438 @@
+//! What starting a unit would do, worked out offline the way systemd's transaction builder does: which
+//! units get pulled in, which jobs get dropped and why, and the order the rest start in.
+
+use serde_derive::Serialize;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt::{Display, Formatter};
+use super::graph::{DependencyGraph, DependencyKind, Edge, EdgeSource};
+use super::loader::{LoadState, UnitLoader};
+use super::{SdBool, SystemdFile, UnitType};
+
+/// Why a start job was taken out of the transaction.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all="camelCase")]
+pub enum DropReason {
+    /// There is no unit file for the unit.
+    NotFound,
+    Masked,
+    /// The unit needs `unit` through `dependency`, and that can't be started. For `Requisite=` that is
+    /// the case when `unit` isn't started by the transaction as well.
+    Requirement { unit: String, dependency: DependencyKind },
+    /// The unit and `unit` have `Conflicts=` between them and this one mattered less.
+    Conflict { unit: String },
+    /// The units are ordered after each other in a circle, which systemd breaks by dropping one of them.
+    OrderingCycle { units: Vec<String> },
+}
+
+impl Display for DropReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DropReason::NotFound => write!(f, "unit not found"),
+            DropReason::Masked => write!(f, "unit is masked"),
+            DropReason::Requirement { unit, dependency } => write!(f, "{dependency}={unit} can't be started"),
+            DropReason::Conflict { unit } => write!(f, "conflicts with {unit}"),
+            DropReason::OrderingCycle { units } => {
+                write!(f, "breaks the ordering cycle {}", units.join(" -> "))?;
+                match units.first() {
+                    Some(first) => write!(f, " -> {first}"),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+/// A unit the transaction would start.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all="camelCase")]
+pub struct Job {
+    pub unit: String,
+    /// The unit and dependency that first pulled this one in, none for the unit the transaction is for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pulled_in_by: Option<(String, DependencyKind)>,
+    /// Whether the unit is needed through `Requires=` and `BindsTo=` alone. Dropping an essential job
+    /// fails the whole transaction, other jobs are dropped quietly.
+    pub essential: bool,
+}
+
+/// A start job that was taken out of the transaction.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all="camelCase")]
+pub struct DroppedJob {
+    pub unit: String,
+    pub reason: DropReason,
+    pub essential: bool,
+}
+
+/// The outcome of starting `target`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all="camelCase")]
+pub struct Transaction {
+    pub target: String,
+    /// The jobs that would run, by unit name. Empty if the transaction failed.
+    pub jobs: Vec<Job>,
+    /// The units of `jobs` in the order they start: each step only waits for the steps before it, the
+    /// units within a step start in parallel.
+    pub start_order: Vec<Vec<String>>,
+    /// Jobs that were pulled in but won't run, in the order they were dropped.
+    pub dropped: Vec<DroppedJob>,
+}
+
+impl Transaction {
+    /// Whether `target` would start. It doesn't if an essential job had to be dropped.
+    pub fn succeeded(&self) -> bool {
+        self.dropped.iter().all(|d| !d.essential)
+    }
+}
+
+/// Works out what starting `target` at boot would do, with the units on the search path of `loader` and
+/// nothing running yet. Dependencies are those of `DependencyGraph::from_loader`, without the `[Install]`
+/// sections of units that aren't enabled, plus the default dependencies systemd adds to services,
+/// sockets, timers, paths and targets on the standard targets that exist below the root.
+pub fn simulate(loader: &UnitLoader, target: &str) -> Transaction {
+    let mut graph = DependencyGraph::from_loader(loader);
+    let anchor = graph.resolve(target).to_string();
+
+    // pull in everything the target needs, adding default dependencies on the way
+    let mut jobs: BTreeMap<String, Job> = BTreeMap::new();
+    let mut defaults: BTreeMap<String, bool> = BTreeMap::new();
+    let mut queue = VecDeque::from([Job { unit: anchor.clone(), pulled_in_by: None, essential: false }]);
+    while let Some(job) = queue.pop_front() {
+        if jobs.contains_key(&job.unit) {
+            continue;
+        }
+        let file = graph.file(&job.unit);
+        let default_dependencies = file.is_none_or(has_default_dependencies);
+        let unit_type = file.and_then(|f| f.unit_type).or_else(|| UnitType::from_file_name(&job.unit));
+        defaults.insert(job.unit.clone(), default_dependencies);
+        if default_dependencies {
+            add_default_dependencies(&mut graph, &job.unit, unit_type);
+        }
+
+        for edge in active_edges(&graph, &job.unit).filter(|e| e.kind.pulls_in()) {
+            queue.push_back(Job {
+                unit: edge.to.clone(),
+                pulled_in_by: Some((job.unit.clone(), edge.kind)),
+                essential: false,
+            });
+        }
+        jobs.insert(job.unit.clone(), job);
+    }
+
+    // targets are ordered after what they pull in unless either side opted out of default dependencies
+    let targets: Vec<&String> = jobs.keys()
+        .filter(|unit| UnitType::from_file_name(unit) == Some(UnitType::Target) && defaults[*unit])
+        .collect();
+    let mut complements = Vec::new();
+    for unit in targets {
+        for edge in active_edges(&graph, unit).filter(|e| e.kind.pulls_in()) {
+            if defaults.get(&edge.to) == Some(&true) && !ordered(&graph, &edge.to, unit) {
+                complements.push(Edge { from: unit.clone(), to: edge.to.clone(), kind: DependencyKind::After, source: EdgeSource::Default });
+            }
+        }
+    }
+    complements.into_iter().for_each(|edge| graph.add_edge(edge));
+
+    let mut transaction = Transaction { target: anchor.clone(), jobs: vec![], start_order: vec![], dropped: vec![] };
+    let mut running: BTreeSet<String> = jobs.keys().cloned().collect();
+    mark_essential(&graph, &anchor, &mut jobs);
+
+    loop {
+        let dropped = drop_unstartable(&graph, &jobs, &running)
+            .or_else(|| drop_conflicting(&graph, &jobs, &running))
+            .or_else(|| break_ordering_cycle(&graph, &jobs, &running));
+        let Some((unit, reason)) = dropped else { break };
+
+        let essential = jobs[&unit].essential;
+        transaction.dropped.push(DroppedJob { unit: unit.clone(), reason, essential });
+        if essential {
+            return transaction;
+        }
+        // whatever only the dropped job pulled in isn't needed any more
+        running.remove(&unit);
+        running = reachable(&graph, &anchor, &running);
+    }
+
+    transaction.start_order = start_order(&graph, &running);
+    transaction.jobs = jobs.into_values().filter(|job| running.contains(&job.unit)).collect();
+
+    transaction
+}
+
+/// The edges of `unit` that count at runtime, see `Edge::is_active`.
+fn active_edges<'a>(graph: &'a DependencyGraph, unit: &'a str) -> impl Iterator<Item = &'a Edge> + 'a {
+    graph.edges_from(unit).filter(|e| e.is_active())
+}
+
+fn ordered(graph: &DependencyGraph, unit: &str, other: &str) -> bool {
+    active_edges(graph, unit).any(|e| e.to == other && e.kind.is_ordering())
+        || active_edges(graph, other).any(|e| e.to == unit && e.kind.is_ordering())
+}
+
+/// Whether the last `DefaultDependencies=` of the `[Unit]` section, if any, leaves them on.
+fn has_default_dependencies(file: &SystemdFile) -> bool {
+    file.sections.iter()
+        .rev()
+        .filter(|s| s.name == "Unit")
+        .find_map(|s| s.get("DefaultDependencies"))
+        .and_then(|value| value.parse::<SdBool>().ok())
+        .is_none_or(|SdBool(on)| on)
+}
+
+/// The dependencies systemd gives units of `unit_type` by default, see systemd.service(5) and friends.
+/// Dependencies on standard targets that aren't below the root are left out, so that a tree with only a
+/// few units doesn't fail for lack of `sysinit.target`.
+fn add_default_dependencies(graph: &mut DependencyGraph, unit: &str, unit_type: Option<UnitType>) {
+    let mut defaults = vec![];
+    let early = |defaults: &mut Vec<(DependencyKind, &str)>| {
+        defaults.push((DependencyKind::Requires, "sysinit.target"));
+        defaults.push((DependencyKind::After, "sysinit.target"));
+    };
+    match unit_type {
+        Some(UnitType::Service) => {
+            early(&mut defaults);
+            defaults.push((DependencyKind::After, "basic.target"));
+        }
+        Some(UnitType::Socket) => {
+            early(&mut defaults);
+            defaults.push((DependencyKind::Before, "sockets.target"));
+        }
+        Some(UnitType::Timer) => {
+            early(&mut defaults);
+            defaults.push((DependencyKind::Before, "timers.target"));
+        }
+        Some(UnitType::Path) => {
+            early(&mut defaults);
+            defaults.push((DependencyKind::Before, "paths.target"));
+        }
+        Some(UnitType::Target) => {}
+        _ => return,
+    }
+    defaults.push((DependencyKind::Conflicts, "shutdown.target"));
+    defaults.push((DependencyKind::Before, "shutdown.target"));
+
+    for (kind, to) in defaults {
+        if to != unit && graph.state(to) == Some(LoadState::Loaded) {
+            graph.add_edge(Edge { from: unit.to_string(), to: to.to_string(), kind, source: EdgeSource::Default });
+        }
+    }
+}
+
+/// Marks `anchor` and the jobs reachable from it over `Requires=` and `BindsTo=` alone as essential.
+fn mark_essential(graph: &DependencyGraph, anchor: &str, jobs: &mut BTreeMap<String, Job>) {
+    let mut queue = VecDeque::from([anchor.to_string()]);
+    while let Some(unit) = queue.pop_front() {
+        match jobs.get_mut(&unit) {
+            Some(job) if !job.essential => job.essential = true,
+            _ => continue,
+        }
+        let required = active_edges(graph, &unit)
+            .filter(|e| matches!(e.kind, DependencyKind::Requires | DependencyKind::BindsTo));
+        queue.extend(required.map(|e| e.to.clone()));
+    }
+}
+
+/// The first job, by name, whose unit can't be loaded or that needs a unit that won't be started.
+fn drop_unstartable(graph: &DependencyGraph, jobs: &BTreeMap<String, Job>, running: &BTreeSet<String>) -> Option<(String, DropReason)> {
+    for unit in running {
+        match graph.state(unit) {
+            Some(LoadState::Loaded) => {}
+            Some(LoadState::Masked) => return Some((unit.clone(), DropReason::Masked)),
+            _ => return Some((unit.clone(), DropReason::NotFound)),
+        }
+
+        let needed = active_edges(graph, unit).filter(|e| {
+            matches!(e.kind, DependencyKind::Requires | DependencyKind::BindsTo | DependencyKind::Requisite)
+        });
+        for edge in needed {
+            if !running.contains(&edge.to) && (jobs.contains_key(&edge.to) || edge.kind == DependencyKind::Requisite) {
+                return Some((unit.clone(), DropReason::Requirement { unit: edge.to.clone(), dependency: edge.kind }));
+            }
+        }
+    }
+
+    None
+}
+
+/// For the first two jobs that conflict, the one that matters less: the one that isn't essential, and
+/// when neither or both are, the one named by `Conflicts=`, which systemd would stop.
+fn drop_conflicting(graph: &DependencyGraph, jobs: &BTreeMap<String, Job>, running: &BTreeSet<String>) -> Option<(String, DropReason)> {
+    for unit in running {
+        let conflict = active_edges(graph, unit)
+            .find(|e| e.kind == DependencyKind::Conflicts && running.contains(&e.to) && e.to != *unit);
+        if let Some(edge) = conflict {
+            let (dropped, kept) = if jobs[unit].essential && !jobs[&edge.to].essential {
+                (&edge.to, unit)
+            } else if !jobs[unit].essential && jobs[&edge.to].essential {
+                (unit, &edge.to)
+            } else {
+                (&edge.to, unit)
+            };
+            return Some((dropped.clone(), DropReason::Conflict { unit: kept.clone() }));
+        }
+    }
+
+    None
+}
+
+/// A job of the first ordering cycle among the jobs, preferring one that isn't essential.
+fn break_ordering_cycle(graph: &DependencyGraph, jobs: &BTreeMap<String, Job>, running: &BTreeSet<String>) -> Option<(String, DropReason)> {
+    let cycles = graph
+        .filter_edges(|e| e.is_active() && running.contains(&e.from) && running.contains(&e.to))
+        .ordering_cycles();
+    let units = cycles.into_iter().next()?;
+    let victim = units.iter().find(|unit| !jobs[*unit].essential).unwrap_or(&units[0]).clone();
+
+    Some((victim, DropReason::OrderingCycle { units }))
+}
+
+/// The units of `running` that `anchor` still pulls in.
+fn reachable(graph: &DependencyGraph, anchor: &str, running: &BTreeSet<String>) -> BTreeSet<String> {
+    let mut reached = BTreeSet::new();
+    let mut queue = VecDeque::from([anchor.to_string()]);
+    while let Some(unit) = queue.pop_front() {
+        if !running.contains(&unit) || !reached.insert(unit.clone()) {
+            continue;
+        }
+        queue.extend(active_edges(graph, &unit).filter(|e| e.kind.pulls_in()).map(|e| e.to.clone()));
+    }
+
+    reached
+}
+
+/// Groups `running` into steps where every unit is ordered after units of earlier steps only.
+fn start_order(graph: &DependencyGraph, running: &BTreeSet<String>) -> Vec<Vec<String>> {
+    let mut waiting_for: BTreeMap<&str, BTreeSet<&str>> = running.iter().map(|u| (u.as_str(), BTreeSet::new())).collect();
+    for (earlier, later) in graph.orderings() {
+        if let (true, Some(waits)) = (running.contains(earlier), waiting_for.get_mut(later)) {
+            waits.insert(earlier);
+        }
+    }
+
+    let mut steps = Vec::new();
+    while !waiting_for.is_empty() {
+        let mut step: Vec<&str> = waiting_for.iter().filter(|(_, waits)| waits.is_empty()).map(|(u, _)| *u).collect();
+        if step.is_empty() {
+            // only left if a cycle couldn't be broken, in which case nothing starts anyway
+            step = waiting_for.keys().copied().collect();
+        }
+        for unit in &step {
+            waiting_for.remove(unit);
+        }
+        for waits in waiting_for.values_mut() {
+            step.iter().for_each(|unit| { waits.remove(unit); });
+        }
+        steps.push(step.into_iter().map(str::to_string).collect());
+    }
+
+    steps
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use super::*;
+    use super::super::loader::Scope;
+    use super::super::testing::unit_root;
+
+    fn simulate_in(root: &Path) -> Transaction {
+        let transaction = simulate(&UnitLoader::new(root, Scope::System), "multi-user.target");
+        fs::remove_dir_all(root).unwrap();
+
+        transaction
+    }
+
+    fn dropped(transaction: &Transaction) -> Vec<(&str, &DropReason)> {
+        transaction.dropped.iter().map(|d| (d.unit.as_str(), &d.reason)).collect()
+    }
+
+    #[test]
+    fn units_start_after_what_they_are_ordered_after() {
+        let transaction = simulate_in(&unit_root("transaction-order", &[
+            ("multi-user.target", "[Unit]\nWants=a.service b.service\n"),
+            ("a.service", "[Service]\nExecStart=/bin/a\n"),
+            ("b.service", "[Unit]\nAfter=a.service\nRequires=c.service\n[Service]\nExecStart=/bin/b\n"),
+            ("c.service", "[Unit]\nBefore=b.service\n[Service]\nExecStart=/bin/c\n"),
+        ]));
+        assert!(transaction.succeeded());
+        assert_eq!(transaction.start_order, [
+            vec!["a.service".to_string(), "c.service".to_string()],
+            vec!["b.service".to_string()],
+            vec!["multi-user.target".to_string()],
+        ]);
+        let c = transaction.jobs.iter().find(|job| job.unit == "c.service").unwrap();
+        assert_eq!(c.pulled_in_by, Some(("b.service".to_string(), DependencyKind::Requires)));
+        assert!(!c.essential);
+    }
+
+    #[test]
+    fn default_dependencies_follow_the_unit_files() {
+        let transaction = simulate_in(&unit_root("transaction-defaults", &[
+            ("multi-user.target", "[Unit]\nWants=basic.target a.service b.service\n"),
+            ("basic.target", "[Unit]\nDescription=basic\n"),
+            ("a.service", "[Service]\nExecStart=/bin/a\n"),
+            ("b.service", "[Unit]\nDefaultDependencies=no\n[Service]\nExecStart=/bin/b\n"),
+        ]));
+        assert_eq!(transaction.start_order, [
+            vec!["b.service".to_string(), "basic.target".to_string()],
+            vec!["a.service".to_string()],
+            vec!["multi-user.target".to_string()],
+        ]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn units_needing_a_masked_unit_are_dropped() {
+        let root = unit_root("transaction-masked", &[
+            ("multi-user.target", "[Unit]\nWants=a.service\n"),
+            ("a.service", "[Unit]\nRequires=m.service\nWants=w.service\n[Service]\nExecStart=/bin/a\n"),
+            ("w.service", "[Service]\nExecStart=/bin/w\n"),
+        ]);
+        std::os::unix::fs::symlink("/dev/null", root.join("usr/lib/systemd/system/m.service")).unwrap();
+        let transaction = simulate_in(&root);
+        assert!(transaction.succeeded());
+        assert_eq!(dropped(&transaction), [
+            ("m.service", &DropReason::Masked),
+            ("a.service", &DropReason::Requirement { unit: "m.service".to_string(), dependency: DependencyKind::Requires }),
+        ]);
+        // w.service was only wanted by the dropped a.service
+        assert_eq!(transaction.start_order, [vec!["multi-user.target".to_string()]]);
+    }
+
+    #[test]
+    fn dropping_an_essential_job_fails_the_transaction() {
+        let transaction = simulate_in(&unit_root("transaction-essential", &[
+            ("multi-user.target", "[Unit]\nRequires=a.service\n"),
+            ("a.service", "[Unit]\nRequires=missing.service\n[Service]\nExecStart=/bin/a\n"),
+        ]));
+        assert!(!transaction.succeeded());
+        assert_eq!(dropped(&transaction), [("missing.service", &DropReason::NotFound)]);
+        assert!(transaction.jobs.is_empty());
+    }
+
+    #[test]
+    fn requisite_units_must_be_started_by_someone_else() {
+        let transaction = simulate_in(&unit_root("transaction-requisite", &[
+            ("multi-user.target", "[Unit]\nWants=a.service\n"),
+            ("a.service", "[Unit]\nRequisite=r.service\n[Service]\nExecStart=/bin/a\n"),
+            ("r.service", "[Service]\nExecStart=/bin/r\n"),
+        ]));
+        assert_eq!(dropped(&transaction), [
+            ("a.service", &DropReason::Requirement { unit: "r.service".to_string(), dependency: DependencyKind::Requisite }),
+        ]);
+    }
+
+    #[test]
+    fn of_two_conflicting_units_the_one_named_by_conflicts_is_dropped() {
+        let transaction = simulate_in(&unit_root("transaction-conflict", &[
+            ("multi-user.target", "[Unit]\nWants=a.service b.service\n"),
+            ("a.service", "[Unit]\nConflicts=b.service\n[Service]\nExecStart=/bin/a\n"),
+            ("b.service", "[Service]\nExecStart=/bin/b\n"),
+        ]));
+        assert!(transaction.succeeded());
+        assert_eq!(dropped(&transaction), [("b.service", &DropReason::Conflict { unit: "a.service".to_string() })]);
+        let units: Vec<&str> = transaction.jobs.iter().map(|job| job.unit.as_str()).collect();
+        assert_eq!(units, ["a.service", "multi-user.target"]);
+    }
+}
@@ -0,0 +1,663 @@
+use serde_derive::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::str::FromStr;
+use super::{write_directives, write_optional_directive, KillMode, MemoryLimit, Percent, SdBool, SectionView, TimeSpan};
+
+/// The kind of unit a file describes, as given by its suffix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all="lowercase")]
+pub enum UnitType {
+    Service,
+    Socket,
+    Timer,
+    Mount,
+    Automount,
+    Path,
+    Swap,
+    Slice,
+    Scope,
+    Target,
+    Device,
+}
+
+impl UnitType {
+    pub const ALL: [UnitType; 11] = [
+        UnitType::Service, UnitType::Socket, UnitType::Timer, UnitType::Mount, UnitType::Automount,
+        UnitType::Path, UnitType::Swap, UnitType::Slice, UnitType::Scope, UnitType::Target, UnitType::Device,
+    ];
+
+    /// The suffix of unit files of this type, without the dot.
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            UnitType::Service => "service",
+            UnitType::Socket => "socket",
+            UnitType::Timer => "timer",
+            UnitType::Mount => "mount",
+            UnitType::Automount => "automount",
+            UnitType::Path => "path",
+            UnitType::Swap => "swap",
+            UnitType::Slice => "slice",
+            UnitType::Scope => "scope",
+            UnitType::Target => "target",
+            UnitType::Device => "device",
+        }
+    }
+
+    /// The name of the type specific section, e.g. `Service`. Targets and devices don't have one.
+    pub fn section_name(&self) -> Option<&'static str> {
+        match self {
+            UnitType::Service => Some("Service"),
+            UnitType::Socket => Some("Socket"),
+            UnitType::Timer => Some("Timer"),
+            UnitType::Mount => Some("Mount"),
+            UnitType::Automount => Some("Automount"),
+            UnitType::Path => Some("Path"),
+            UnitType::Swap => Some("Swap"),
+            UnitType::Slice => Some("Slice"),
+            UnitType::Scope => Some("Scope"),
+            UnitType::Target | UnitType::Device => None,
+        }
+    }
+
+    /// Derives the unit type from a file name or path such as `/etc/systemd/system/foo.timer`.
+    pub fn from_file_name<P: AsRef<Path>>(path: P) -> Option<UnitType> {
+        path.as_ref().extension()?.to_str()?.parse().ok()
+    }
+}
+
+impl Display for UnitType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.suffix())
+    }
+}
+
+impl FromStr for UnitType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        UnitType::ALL.into_iter()
+            .find(|t| t.suffix() == s)
+            .ok_or_else(|| format!("unknown unit type `{s}`"))
+    }
+}
+
+/// `[Socket]` section of a `.socket` unit, see systemd.socket(5).
+#[derive(Default, Debug, Serialize, Deserialize)]
+#[serde(rename_all="camelCase")]
+pub struct SocketSection {
+    pub head: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub listen_stream: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub listen_datagram: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub listen_sequential_packet: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub listen_fifo: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub listen_special: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub listen_netlink: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub accept: Option<SdBool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub service: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub socket_user: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub socket_group: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub socket_mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub directory_mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bind_ipv6_only: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backlog: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_connections: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep_alive: Option<SdBool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pass_credentials: Option<SdBool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remove_on_stop: Option<SdBool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub symlinks: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_descriptor_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comments: Option<Vec<String>>,
+}
+
+impl Display for SocketSection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", self.head)?;
+        if let Some(comment) = self.comments.as_ref() {
+            writeln!(f, "{}", comment.join("\n"))?;
+        }
+        write_directives(f, "ListenStream", &self.listen_stream)?;
+        write_directives(f, "ListenDatagram", &self.listen_datagram)?;
+        write_directives(f, "ListenSequentialPacket", &self.listen_sequential_packet)?;
+        write_directives(f, "ListenFIFO", &self.listen_fifo)?;
+        write_directives(f, "ListenSpecial", &self.listen_special)?;
+        write_directives(f, "ListenNetlink", &self.listen_netlink)?;
+        write_optional_directive(f, "Accept", &self.accept)?;
+        write_optional_directive(f, "Service", &self.service)?;
+        write_optional_directive(f, "SocketUser", &self.socket_user)?;
+        write_optional_directive(f, "SocketGroup", &self.socket_group)?;
+        write_optional_directive(f, "SocketMode", &self.socket_mode)?;
+        write_optional_directive(f, "DirectoryMode", &self.directory_mode)?;
+        write_optional_directive(f, "BindIPv6Only", &self.bind_ipv6_only)?;
+        write_optional_directive(f, "Backlog", &self.backlog)?;
+        write_optional_directive(f, "MaxConnections", &self.max_connections)?;
+        write_optional_directive(f, "KeepAlive", &self.keep_alive)?;
+        write_optional_directive(f, "PassCredentials", &self.pass_credentials)?;
+        write_optional_directive(f, "RemoveOnStop", &self.remove_on_stop)?;
+        write_directives(f, "Symlinks", &self.symlinks)?;
+        write_optional_directive(f, "FileDescriptorName", &self.file_descriptor_name)?;
+
+        writeln!(f)
+    }
+}
+
+impl From<&SectionView<'_>> for SocketSection {
+    fn from(view: &SectionView<'_>) -> Self {
+        SocketSection {
+            head: view.head(),
+            listen_stream: view.all("ListenStream"),
+            listen_datagram: view.all("ListenDatagram"),
+            listen_sequential_packet: view.all("ListenSequentialPacket"),
+            listen_fifo: view.all("ListenFIFO"),
+            listen_special: view.all("ListenSpecial"),
+            listen_netlink: view.all("ListenNetlink"),
+            accept: view.parsed("Accept"),
+            service: view.last("Service"),
+            socket_user: view.last("SocketUser"),
+            socket_group: view.last("SocketGroup"),
+            socket_mode: view.last("SocketMode"),
+            directory_mode: view.last("DirectoryMode"),
+            bind_ipv6_only: view.last("BindIPv6Only"),
+            backlog: view.last("Backlog"),
+            max_connections: view.last("MaxConnections"),
+            keep_alive: view.parsed("KeepAlive"),
+            pass_credentials: view.parsed("PassCredentials"),
+            remove_on_stop: view.parsed("RemoveOnStop"),
+            symlinks: view.all("Symlinks"),
+            file_descriptor_name: view.last("FileDescriptorName"),
+            comments: view.comments(),
+        }
+    }
+}
+
+/// `[Timer]` section of a `.timer` unit, see systemd.timer(5).
+#[derive(Default, Debug, Serialize, Deserialize)]
+#[serde(rename_all="camelCase")]
+pub struct TimerSection {
+    pub head: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_active_sec: Option<Vec<TimeSpan>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_boot_sec: Option<Vec<TimeSpan>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_startup_sec: Option<Vec<TimeSpan>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_unit_active_sec: Option<Vec<TimeSpan>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_unit_inactive_sec: Option<Vec<TimeSpan>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_calendar: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub accuracy_sec: Option<TimeSpan>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub randomized_delay_sec: Option<TimeSpan>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fixed_random_delay: Option<SdBool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_clock_change: Option<SdBool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_timezone_change: Option<SdBool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub persistent: Option<SdBool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wake_system: Option<SdBool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remain_after_elapse: Option<SdBool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comments: Option<Vec<String>>,
+}
+
+impl Display for TimerSection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", self.head)?;
+        if let Some(comment) = self.comments.as_ref() {
+            writeln!(f, "{}", comment.join("\n"))?;
+        }
+        write_directives(f, "OnActiveSec", &self.on_active_sec)?;
+        write_directives(f, "OnBootSec", &self.on_boot_sec)?;
+        write_directives(f, "OnStartupSec", &self.on_startup_sec)?;
+        write_directives(f, "OnUnitActiveSec", &self.on_unit_active_sec)?;
+        write_directives(f, "OnUnitInactiveSec", &self.on_unit_inactive_sec)?;
+        write_directives(f, "OnCalendar", &self.on_calendar)?;
+        write_optional_directive(f, "AccuracySec", &self.accuracy_sec)?;
+        write_optional_directive(f, "RandomizedDelaySec", &self.randomized_delay_sec)?;
+        write_optional_directive(f, "FixedRandomDelay", &self.fixed_random_delay)?;
+        write_optional_directive(f, "OnClockChange", &self.on_clock_change)?;
+        write_optional_directive(f, "OnTimezoneChange", &self.on_timezone_change)?;
+        write_optional_directive(f, "Unit", &self.unit)?;
+        write_optional_directive(f, "Persistent", &self.persistent)?;
+        write_optional_directive(f, "WakeSystem", &self.wake_system)?;
+        write_optional_directive(f, "RemainAfterElapse", &self.remain_after_elapse)?;
+
+        writeln!(f)
+    }
+}
+
+impl From<&SectionView<'_>> for TimerSection {
+    fn from(view: &SectionView<'_>) -> Self {
+        TimerSection {
+            head: view.head(),
+            on_active_sec: view.all_parsed("OnActiveSec"),
+            on_boot_sec: view.all_parsed("OnBootSec"),
+            on_startup_sec: view.all_parsed("OnStartupSec"),
+            on_unit_active_sec: view.all_parsed("OnUnitActiveSec"),
+            on_unit_inactive_sec: view.all_parsed("OnUnitInactiveSec"),
+            on_calendar: view.all("OnCalendar"),
+            accuracy_sec: view.parsed("AccuracySec"),
+            randomized_delay_sec: view.parsed("RandomizedDelaySec"),
+            fixed_random_delay: view.parsed("FixedRandomDelay"),
+            on_clock_change: view.parsed("OnClockChange"),
+            on_timezone_change: view.parsed("OnTimezoneChange"),
+            unit: view.last("Unit"),
+            persistent: view.parsed("Persistent"),
+            wake_system: view.parsed("WakeSystem"),
+            remain_after_elapse: view.parsed("RemainAfterElapse"),
+            comments: view.comments(),
+        }
+    }
+}
+
+/// `[Mount]` section of a `.mount` unit, see systemd.mount(5).
+#[derive(Default, Debug, Serialize, Deserialize)]
+#[serde(rename_all="camelCase")]
+pub struct MountSection {
+    pub head: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub what: Option<String>,
+    #[serde(rename = "where", skip_serializing_if = "Option::is_none")]
+    pub mount_where: Option<String>,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub fs_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub options: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sloppy_options: Option<SdBool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lazy_unmount: Option<SdBool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_write_only: Option<SdBool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub force_unmount: Option<SdBool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub directory_mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout_sec: Option<TimeSpan>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comments: Option<Vec<String>>,
+}
+
+impl Display for MountSection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", self.head)?;
+        if let Some(comment) = self.comments.as_ref() {
+            writeln!(f, "{}", comment.join("\n"))?;
+        }
+        write_optional_directive(f, "What", &self.what)?;
+        write_optional_directive(f, "Where", &self.mount_where)?;
+        write_optional_directive(f, "Type", &self.fs_type)?;
+        write_optional_directive(f, "Options", &self.options)?;
+        write_optional_directive(f, "SloppyOptions", &self.sloppy_options)?;
+        write_optional_directive(f, "LazyUnmount", &self.lazy_unmount)?;
+        write_optional_directive(f, "ReadWriteOnly", &self.read_write_only)?;
+        write_optional_directive(f, "ForceUnmount", &self.force_unmount)?;
+        write_optional_directive(f, "DirectoryMode", &self.directory_mode)?;
+        write_optional_directive(f, "TimeoutSec", &self.timeout_sec)?;
+
+        writeln!(f)
+    }
+}
+
+impl From<&SectionView<'_>> for MountSection {
+    fn from(view: &SectionView<'_>) -> Self {
+        MountSection {
+            head: view.head(),
+            what: view.last("What"),
+            mount_where: view.last("Where"),
+            fs_type: view.last("Type"),
+            options: view.last("Options"),
+            sloppy_options: view.parsed("SloppyOptions"),
+            lazy_unmount: view.parsed("LazyUnmount"),
+            read_write_only: view.parsed("ReadWriteOnly"),
+            force_unmount: view.parsed("ForceUnmount"),
+            directory_mode: view.last("DirectoryMode"),
+            timeout_sec: view.parsed("TimeoutSec"),
+            comments: view.comments(),
+        }
+    }
+}
+
+/// `[Automount]` section of an `.automount` unit, see systemd.automount(5).
+#[derive(Default, Debug, Serialize, Deserialize)]
+#[serde(rename_all="camelCase")]
+pub struct AutomountSection {
+    pub head: String,
+    #[serde(rename = "where", skip_serializing_if = "Option::is_none")]
+    pub mount_where: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extra_options: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub directory_mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout_idle_sec: Option<TimeSpan>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comments: Option<Vec<String>>,
+}
+
+impl Display for AutomountSection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", self.head)?;
+        if let Some(comment) = self.comments.as_ref() {
+            writeln!(f, "{}", comment.join("\n"))?;
+        }
+        write_optional_directive(f, "Where", &self.mount_where)?;
+        write_optional_directive(f, "ExtraOptions", &self.extra_options)?;
+        write_optional_directive(f, "DirectoryMode", &self.directory_mode)?;
+        write_optional_directive(f, "TimeoutIdleSec", &self.timeout_idle_sec)?;
+
+        writeln!(f)
+    }
+}
+
+impl From<&SectionView<'_>> for AutomountSection {
+    fn from(view: &SectionView<'_>) -> Self {
+        AutomountSection {
+            head: view.head(),
+            mount_where: view.last("Where"),
+            extra_options: view.last("ExtraOptions"),
+            directory_mode: view.last("DirectoryMode"),
+            timeout_idle_sec: view.parsed("TimeoutIdleSec"),
+            comments: view.comments(),
+        }
+    }
+}
+
+/// `[Path]` section of a `.path` unit, see systemd.path(5).
+#[derive(Default, Debug, Serialize, Deserialize)]
+#[serde(rename_all="camelCase")]
+pub struct PathSection {
+    pub head: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path_exists: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path_exists_glob: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path_changed: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path_modified: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub directory_not_empty: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub make_directory: Option<SdBool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub directory_mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trigger_limit_interval_sec: Option<TimeSpan>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trigger_limit_burst: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comments: Option<Vec<String>>,
+}
+
+impl Display for PathSection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", self.head)?;
+        if let Some(comment) = self.comments.as_ref() {
+            writeln!(f, "{}", comment.join("\n"))?;
+        }
+        write_directives(f, "PathExists", &self.path_exists)?;
+        write_directives(f, "PathExistsGlob", &self.path_exists_glob)?;
+        write_directives(f, "PathChanged", &self.path_changed)?;
+        write_directives(f, "PathModified", &self.path_modified)?;
+        write_directives(f, "DirectoryNotEmpty", &self.directory_not_empty)?;
+        write_optional_directive(f, "Unit", &self.unit)?;
+        write_optional_directive(f, "MakeDirectory", &self.make_directory)?;
+        write_optional_directive(f, "DirectoryMode", &self.directory_mode)?;
+        write_optional_directive(f, "TriggerLimitIntervalSec", &self.trigger_limit_interval_sec)?;
+        write_optional_directive(f, "TriggerLimitBurst", &self.trigger_limit_burst)?;
+
+        writeln!(f)
+    }
+}
+
+impl From<&SectionView<'_>> for PathSection {
+    fn from(view: &SectionView<'_>) -> Self {
+        PathSection {
+            head: view.head(),
+            path_exists: view.all("PathExists"),
+            path_exists_glob: view.all("PathExistsGlob"),
+            path_changed: view.all("PathChanged"),
+            path_modified: view.all("PathModified"),
+            directory_not_empty: view.all("DirectoryNotEmpty"),
+            unit: view.last("Unit"),
+            make_directory: view.parsed("MakeDirectory"),
+            directory_mode: view.last("DirectoryMode"),
+            trigger_limit_interval_sec: view.parsed("TriggerLimitIntervalSec"),
+            trigger_limit_burst: view.last("TriggerLimitBurst"),
+            comments: view.comments(),
+        }
+    }
+}
+
+/// `[Swap]` section of a `.swap` unit, see systemd.swap(5).
+#[derive(Default, Debug, Serialize, Deserialize)]
+#[serde(rename_all="camelCase")]
+pub struct SwapSection {
+    pub head: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub what: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub options: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout_sec: Option<TimeSpan>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comments: Option<Vec<String>>,
+}
+
+impl Display for SwapSection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", self.head)?;
+        if let Some(comment) = self.comments.as_ref() {
+            writeln!(f, "{}", comment.join("\n"))?;
+        }
+        write_optional_directive(f, "What", &self.what)?;
+        write_optional_directive(f, "Priority", &self.priority)?;
+        write_optional_directive(f, "Options", &self.options)?;
+        write_optional_directive(f, "TimeoutSec", &self.timeout_sec)?;
+
+        writeln!(f)
+    }
+}
+
+impl From<&SectionView<'_>> for SwapSection {
+    fn from(view: &SectionView<'_>) -> Self {
+        SwapSection {
+            head: view.head(),
+            what: view.last("What"),
+            priority: view.last("Priority"),
+            options: view.last("Options"),
+            timeout_sec: view.parsed("TimeoutSec"),
+            comments: view.comments(),
+        }
+    }
+}
+
+/// `[Slice]` section of a `.slice` unit. Slices only take resource control settings, see
+/// systemd.resource-control(5).
+#[derive(Default, Debug, Serialize, Deserialize)]
+#[serde(rename_all="camelCase")]
+pub struct SliceSection {
+    pub head: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu_weight: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu_quota: Option<Percent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory_high: Option<MemoryLimit>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory_max: Option<MemoryLimit>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tasks_max: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub io_weight: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comments: Option<Vec<String>>,
+}
+
+impl Display for SliceSection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", self.head)?;
+        if let Some(comment) = self.comments.as_ref() {
+            writeln!(f, "{}", comment.join("\n"))?;
+        }
+        write_optional_directive(f, "CPUWeight", &self.cpu_weight)?;
+        write_optional_directive(f, "CPUQuota", &self.cpu_quota)?;
+        write_optional_directive(f, "MemoryHigh", &self.memory_high)?;
+        write_optional_directive(f, "MemoryMax", &self.memory_max)?;
+        write_optional_directive(f, "TasksMax", &self.tasks_max)?;
+        write_optional_directive(f, "IOWeight", &self.io_weight)?;
+
+        writeln!(f)
+    }
+}
+
+impl From<&SectionView<'_>> for SliceSection {
+    fn from(view: &SectionView<'_>) -> Self {
+        SliceSection {
+            head: view.head(),
+            cpu_weight: view.last("CPUWeight"),
+            cpu_quota: view.parsed("CPUQuota"),
+            memory_high: view.parsed("MemoryHigh"),
+            memory_max: view.parsed("MemoryMax"),
+            tasks_max: view.last("TasksMax"),
+            io_weight: view.last("IOWeight"),
+            comments: view.comments(),
+        }
+    }
+}
+
+/// `[Scope]` section of a `.scope` unit, see systemd.scope(5).
+#[derive(Default, Debug, Serialize, Deserialize)]
+#[serde(rename_all="camelCase")]
+pub struct ScopeSection {
+    pub head: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub runtime_max_sec: Option<TimeSpan>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub oom_policy: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kill_mode: Option<KillMode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu_weight: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu_quota: Option<Percent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory_high: Option<MemoryLimit>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory_max: Option<MemoryLimit>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tasks_max: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub io_weight: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comments: Option<Vec<String>>,
+}
+
+impl Display for ScopeSection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", self.head)?;
+        if let Some(comment) = self.comments.as_ref() {
+            writeln!(f, "{}", comment.join("\n"))?;
+        }
+        write_optional_directive(f, "RuntimeMaxSec", &self.runtime_max_sec)?;
+        write_optional_directive(f, "OOMPolicy", &self.oom_policy)?;
+        write_optional_directive(f, "KillMode", &self.kill_mode)?;
+        write_optional_directive(f, "CPUWeight", &self.cpu_weight)?;
+        write_optional_directive(f, "CPUQuota", &self.cpu_quota)?;
+        write_optional_directive(f, "MemoryHigh", &self.memory_high)?;
+        write_optional_directive(f, "MemoryMax", &self.memory_max)?;
+        write_optional_directive(f, "TasksMax", &self.tasks_max)?;
+        write_optional_directive(f, "IOWeight", &self.io_weight)?;
+
+        writeln!(f)
+    }
+}
+
+impl From<&SectionView<'_>> for ScopeSection {
+    fn from(view: &SectionView<'_>) -> Self {
+        ScopeSection {
+            head: view.head(),
+            runtime_max_sec: view.parsed("RuntimeMaxSec"),
+            oom_policy: view.last("OOMPolicy"),
+            kill_mode: view.parsed("KillMode"),
+            cpu_weight: view.last("CPUWeight"),
+            cpu_quota: view.parsed("CPUQuota"),
+            memory_high: view.parsed("MemoryHigh"),
+            memory_max: view.parsed("MemoryMax"),
+            tasks_max: view.last("TasksMax"),
+            io_weight: view.last("IOWeight"),
+            comments: view.comments(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::parse_str;
+
+    #[test]
+    fn unit_types_come_from_the_file_suffix() {
+        for unit_type in UnitType::ALL {
+            let path = format!("/etc/systemd/system/foo.{unit_type}");
+            assert_eq!(UnitType::from_file_name(&path), Some(unit_type));
+            assert_eq!(unit_type.suffix().parse(), Ok(unit_type));
+        }
+        assert_eq!(UnitType::from_file_name("foo.service.d/override.conf"), None);
+        assert_eq!(UnitType::from_file_name("foo"), None);
+        assert_eq!(UnitType::Target.section_name(), None);
+    }
+
+    #[test]
+    fn timers_and_sockets_parse_without_a_service_section() {
+        let file = parse_str("[Unit]\nDescription=t\n[Timer]\nOnCalendar=daily\nOnCalendar=weekly\nUnit=a.service\n").unwrap();
+        assert_eq!(file.unit_type, Some(UnitType::Timer));
+        assert!(file.service.is_none());
+        let timer = file.timer.unwrap();
+        assert_eq!(timer.on_calendar, Some(vec!["daily".to_string(), "weekly".to_string()]));
+        assert_eq!(timer.unit.as_deref(), Some("a.service"));
+
+        let file = parse_str("[Socket]\nListenStream=0.0.0.0:80\nAccept=yes\n").unwrap();
+        assert_eq!(file.unit_type, Some(UnitType::Socket));
+        assert!(file.service.is_none());
+        let socket = file.socket.unwrap();
+        assert_eq!(socket.listen_stream, Some(vec!["0.0.0.0:80".to_string()]));
+        assert_eq!(socket.to_string(), "[Socket]\nListenStream=0.0.0.0:80\nAccept=yes\n\n");
+    }
+}
@@ -0,0 +1,485 @@
+//! Value types shared by many directives: booleans, byte sizes, percentages and resource limits.
+
+use serde_derive::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// Directives that take a boolean and nothing else.
+pub const BOOLEAN_DIRECTIVES: &[&str] = &[
+    // [Unit]
+    "StopWhenUnneeded", "RefuseManualStart", "RefuseManualStop", "AllowIsolate", "DefaultDependencies",
+    "IgnoreOnIsolate",
+    // [Service]
+    "RemainAfterExit", "GuessMainPID", "PermissionsStartOnly", "RootDirectoryStartOnly", "NonBlocking",
+    // [Socket]
+    "Accept", "Writable", "FreeBind", "Transparent", "Broadcast", "PassCredentials", "PassPIDFD",
+    "PassSecurity", "PassPacketInfo", "KeepAlive", "NoDelay", "ReusePort", "RemoveOnStop", "FlushPending",
+    // [Timer]
+    "FixedRandomDelay", "OnClockChange", "OnTimezoneChange", "Persistent", "WakeSystem", "RemainAfterElapse",
+    // [Mount]
+    "SloppyOptions", "LazyUnmount", "ReadWriteOnly", "ForceUnmount",
+    // [Path]
+    "MakeDirectory",
+    // systemd.exec(5)
+    "PrivateTmp", "PrivateDevices", "PrivateNetwork", "PrivateIPC", "PrivateMounts", "NoNewPrivileges",
+    "ProtectKernelTunables", "ProtectKernelModules", "ProtectKernelLogs", "ProtectControlGroups",
+    "ProtectClock", "ProtectHostname", "MemoryDenyWriteExecute", "RestrictRealtime", "RestrictSUIDSGID",
+    "LockPersonality", "DynamicUser", "RemoveIPC", "MountAPIVFS", "IgnoreSIGPIPE", "TTYReset", "TTYVHangup",
+    "TTYVTDisallocate",
+    // systemd.kill(5)
+    "SendSIGHUP", "SendSIGKILL",
+    // systemd.resource-control(5)
+    "CPUAccounting", "MemoryAccounting", "TasksAccounting", "IOAccounting", "IPAccounting",
+    "BlockIOAccounting",
+];
+
+/// Memory limits of systemd.resource-control(5), see `MemoryLimit`.
+pub const MEMORY_LIMIT_DIRECTIVES: &[&str] = &[
+    "MemoryMin", "MemoryLow", "StartupMemoryLow", "DefaultStartupMemoryLow", "MemoryHigh", "StartupMemoryHigh",
+    "MemoryMax", "StartupMemoryMax", "MemorySwapMax", "StartupMemorySwapMax", "MemoryZSwapMax",
+    "StartupMemoryZSwapMax", "MemoryLimit",
+];
+
+/// Directives that take a percentage.
+pub const PERCENT_DIRECTIVES: &[&str] = &["CPUQuota", "ManagedOOMMemoryPressureLimit"];
+
+/// Resource limits that take a number or byte size. `LimitCPU=` and `LimitRTTIME=` take time spans and
+/// `LimitNICE=` a priority, so they aren't listed.
+pub const RLIMIT_DIRECTIVES: &[&str] = &[
+    "LimitFSIZE", "LimitDATA", "LimitSTACK", "LimitCORE", "LimitRSS", "LimitNOFILE", "LimitAS", "LimitNPROC",
+    "LimitMEMLOCK", "LimitLOCKS", "LimitSIGPENDING", "LimitMSGQUEUE", "LimitRTPRIO",
+];
+
+/// The resource limits of `RLIMIT_DIRECTIVES` that are sizes in bytes. The others count things, like open
+/// files or processes, and take plain numbers.
+pub const RLIMIT_SIZE_DIRECTIVES: &[&str] = &[
+    "LimitFSIZE", "LimitDATA", "LimitSTACK", "LimitCORE", "LimitRSS", "LimitAS", "LimitMEMLOCK", "LimitMSGQUEUE",
+];
+
+/// A boolean as systemd reads it: `1`, `yes`, `y`, `true`, `t` and `on` are true, `0`, `no`, `n`, `false`,
+/// `f` and `off` are false, in any case. Written back as `yes` or `no`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SdBool(pub bool);
+
+impl FromStr for SdBool {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "1" | "yes" | "y" | "true" | "t" | "on" => Ok(SdBool(true)),
+            "0" | "no" | "n" | "false" | "f" | "off" => Ok(SdBool(false)),
+            _ => Err(format!("`{s}` is not a boolean, expected yes or no")),
+        }
+    }
+}
+
+impl From<bool> for SdBool {
+    fn from(value: bool) -> Self {
+        SdBool(value)
+    }
+}
+
+impl From<SdBool> for bool {
+    fn from(value: SdBool) -> Self {
+        value.0
+    }
+}
+
+impl Display for SdBool {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", if self.0 { "yes" } else { "no" })
+    }
+}
+
+/// Suffixes of `ByteSize`, largest first.
+const BYTE_SUFFIXES: &[(char, u64)] = &[
+    ('E', 1 << 60), ('P', 1 << 50), ('T', 1 << 40), ('G', 1 << 30), ('M', 1 << 20), ('K', 1 << 10),
+];
+
+/// A size in bytes such as `2G`. The suffixes `K`, `M`, `G`, `T`, `P` and `E` are powers of 1024, a bare
+/// number or one suffixed with `B` is bytes. Fractions like `1.5G` are allowed and rounded down to a byte.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ByteSize(pub u64);
+
+impl ByteSize {
+    pub fn bytes(&self) -> u64 {
+        self.0
+    }
+}
+
+impl FromStr for ByteSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let trimmed = s.trim();
+        let (number, factor) = match trimmed.chars().last() {
+            Some('B') => (&trimmed[..trimmed.len() - 1], 1),
+            Some(c) if c.is_ascii_alphabetic() => {
+                let factor = BYTE_SUFFIXES.iter().find(|(suffix, _)| *suffix == c).map(|(_, f)| *f)
+                    .ok_or_else(|| format!("unknown size suffix `{c}` in `{s}`, expected K, M, G, T, P or E"))?;
+                (&trimmed[..trimmed.len() - 1], factor)
+            }
+            _ => (trimmed, 1),
+        };
+        let number = number.trim_end();
+
+        let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+        if whole.is_empty() || !whole.bytes().all(|b| b.is_ascii_digit()) || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(format!("`{s}` is not a size"));
+        }
+        let too_large = || format!("size `{s}` is too large");
+        let whole: u64 = whole.parse().map_err(|_| too_large())?;
+        let fraction_bytes = match fraction {
+            "" => 0,
+            _ => {
+                let digits: u128 = fraction.parse().map_err(|_| too_large())?;
+                let scale = 10u128.checked_pow(fraction.len() as u32).ok_or_else(too_large)?;
+                (digits.checked_mul(u128::from(factor)).ok_or_else(too_large)? / scale) as u64
+            }
+        };
+        let bytes = whole.checked_mul(factor)
+            .and_then(|b| b.checked_add(fraction_bytes))
+            .ok_or_else(too_large)?;
+
+        Ok(ByteSize(bytes))
+    }
+}
+
+impl TryFrom<String> for ByteSize {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<ByteSize> for String {
+    fn from(size: ByteSize) -> Self {
+        size.to_string()
+    }
+}
+
+impl Display for ByteSize {
+    /// Writes the size with the largest suffix that divides it evenly, e.g. `2G` or `1536M`.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match BYTE_SUFFIXES.iter().find(|(_, factor)| self.0 != 0 && self.0 % factor == 0) {
+            Some((suffix, factor)) => write!(f, "{}{suffix}", self.0 / factor),
+            None => write!(f, "{}", self.0),
+        }
+    }
+}
+
+/// A percentage such as `150%` or `12.5%`, kept in hundredths of a percent. Values above 100% are allowed,
+/// `CPUQuota=` uses them for more than one CPU.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Percent {
+    pub hundredths: u64,
+}
+
+impl Percent {
+    /// The percentage as a fraction, `1.5` for `150%`.
+    pub fn as_fraction(&self) -> f64 {
+        self.hundredths as f64 / 10_000.0
+    }
+}
+
+impl FromStr for Percent {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let number = s.trim().strip_suffix('%').ok_or_else(|| format!("`{s}` is not a percentage, expected e.g. 50%"))?;
+        let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+        if whole.is_empty() || !whole.bytes().all(|b| b.is_ascii_digit()) || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(format!("`{s}` is not a percentage, expected e.g. 50%"));
+        }
+        if fraction.len() > 2 {
+            return Err(format!("percentage `{s}` has more than two decimal places"));
+        }
+
+        let whole: u64 = whole.parse().map_err(|_| format!("percentage `{s}` is too large"))?;
+        let fraction: u64 = format!("{fraction:0<2}").parse().unwrap_or_default();
+        let hundredths = whole.checked_mul(100)
+            .and_then(|w| w.checked_add(fraction))
+            .ok_or_else(|| format!("percentage `{s}` is too large"))?;
+
+        Ok(Percent { hundredths })
+    }
+}
+
+impl TryFrom<String> for Percent {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Percent> for String {
+    fn from(percent: Percent) -> Self {
+        percent.to_string()
+    }
+}
+
+impl Display for Percent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let (whole, fraction) = (self.hundredths / 100, self.hundredths % 100);
+        match fraction {
+            0 => write!(f, "{whole}%"),
+            _ if fraction % 10 == 0 => write!(f, "{whole}.{}%", fraction / 10),
+            _ => write!(f, "{whole}.{fraction:02}%"),
+        }
+    }
+}
+
+/// A memory limit of systemd.resource-control(5): a size, a percentage of the physical memory or
+/// `infinity`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum MemoryLimit {
+    Bytes(ByteSize),
+    Percent(Percent),
+    Infinity,
+}
+
+impl FromStr for MemoryLimit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let trimmed = s.trim();
+        if trimmed == "infinity" {
+            Ok(MemoryLimit::Infinity)
+        } else if trimmed.ends_with('%') {
+            Ok(MemoryLimit::Percent(trimmed.parse()?))
+        } else {
+            trimmed.parse().map(MemoryLimit::Bytes)
+                .map_err(|_| format!("`{s}` is not a size, a percentage or infinity"))
+        }
+    }
+}
+
+impl TryFrom<String> for MemoryLimit {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<MemoryLimit> for String {
+    fn from(limit: MemoryLimit) -> Self {
+        limit.to_string()
+    }
+}
+
+impl Display for MemoryLimit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MemoryLimit::Bytes(size) => write!(f, "{size}"),
+            MemoryLimit::Percent(percent) => write!(f, "{percent}"),
+            MemoryLimit::Infinity => write!(f, "infinity"),
+        }
+    }
+}
+
+/// One side of an `RLimit`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RLimitValue {
+    Value(u64),
+    Infinity,
+}
+
+impl RLimitValue {
+    /// Parses a size in bytes, which may use the size suffixes of `ByteSize`, or `infinity`.
+    pub fn parse_size(s: &str) -> Result<Self, String> {
+        match s.trim() {
+            "infinity" => Ok(RLimitValue::Infinity),
+            value => value.parse::<ByteSize>()
+                .map(|size| RLimitValue::Value(size.bytes()))
+                .map_err(|_| format!("`{s}` is not a size or infinity")),
+        }
+    }
+}
+
+/// Parses a plain number or `infinity`.
+impl FromStr for RLimitValue {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "infinity" => Ok(RLimitValue::Infinity),
+            value => value.parse::<u64>()
+                .map(RLimitValue::Value)
+                .map_err(|_| format!("`{s}` is not a number or infinity")),
+        }
+    }
+}
+
+impl Display for RLimitValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RLimitValue::Value(value) => write!(f, "{value}"),
+            RLimitValue::Infinity => write!(f, "infinity"),
+        }
+    }
+}
+
+/// A resource limit of the `Limit*=` directives, see "Process Properties" in systemd.exec(5). Either a
+/// single value for both the soft and the hard limit, or `soft:hard`. Parsing it with `FromStr` takes plain
+/// numbers, as limits that count things like `LimitNOFILE=` do; see `RLimit::parse_for` for sizes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct RLimit {
+    pub soft: RLimitValue,
+    pub hard: RLimitValue,
+}
+
+impl RLimit {
+    /// Parses the value of the `Limit*=` directive `key`. The limits of `RLIMIT_SIZE_DIRECTIVES` may use
+    /// the size suffixes of `ByteSize`, the others only take plain numbers.
+    pub fn parse_for(key: &str, s: &str) -> Result<Self, String> {
+        if RLIMIT_SIZE_DIRECTIVES.contains(&key) {
+            RLimit::parse_with(s, RLimitValue::parse_size)
+        } else {
+            s.parse()
+        }
+    }
+
+    fn parse_with(s: &str, value: fn(&str) -> Result<RLimitValue, String>) -> Result<Self, String> {
+        let (soft, hard) = match s.split_once(':') {
+            Some((soft, hard)) => (value(soft)?, value(hard)?),
+            None => {
+                let value = value(s)?;
+                (value, value)
+            }
+        };
+        if soft > hard {
+            return Err(format!("soft limit {soft} is above the hard limit {hard}"));
+        }
+
+        Ok(RLimit { soft, hard })
+    }
+}
+
+impl FromStr for RLimit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        RLimit::parse_with(s, RLimitValue::from_str)
+    }
+}
+
+impl TryFrom<String> for RLimit {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<RLimit> for String {
+    fn from(limit: RLimit) -> Self {
+        limit.to_string()
+    }
+}
+
+impl Display for RLimit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.soft == self.hard {
+            write!(f, "{}", self.soft)
+        } else {
+            write!(f, "{}:{}", self.soft, self.hard)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn booleans_take_every_spelling_systemd_does() {
+        for s in ["1", "yes", "y", "true", "t", "on", "YES", "On"] {
+            assert_eq!(s.parse(), Ok(SdBool(true)), "{s}");
+        }
+        for s in ["0", "no", "n", "false", "f", "off", "Off"] {
+            assert_eq!(s.parse(), Ok(SdBool(false)), "{s}");
+        }
+        assert_eq!("maybe".parse::<SdBool>(), Err("`maybe` is not a boolean, expected yes or no".to_string()));
+        assert!("".parse::<SdBool>().is_err());
+        assert_eq!((SdBool(true).to_string(), SdBool(false).to_string()), ("yes".to_string(), "no".to_string()));
+    }
+
+    #[test]
+    fn sizes_use_powers_of_1024() {
+        let size = |s: &str| s.parse::<ByteSize>().map(|size| size.bytes());
+        assert_eq!(size("512"), Ok(512));
+        assert_eq!(size("512B"), Ok(512));
+        assert_eq!(size("4K"), Ok(4096));
+        assert_eq!(size("2M"), Ok(2 << 20));
+        assert_eq!(size("1.5G"), Ok(3 << 29));
+        assert_eq!(size("1T"), Ok(1 << 40));
+        assert_eq!(size(" 8 M "), Ok(8 << 20));
+        assert_eq!(size("1X"), Err("unknown size suffix `X` in `1X`, expected K, M, G, T, P or E".to_string()));
+        assert!(size("K").is_err());
+        assert!(size("-1K").is_err());
+        assert!(size("1.2.3M").is_err());
+        assert!(size("16E").is_err());
+    }
+
+    #[test]
+    fn sizes_display_with_the_largest_even_suffix() {
+        for (bytes, s) in [(0, "0"), (1000, "1000"), (2048, "2K"), (1536 << 20, "1536M"), (1 << 30, "1G")] {
+            assert_eq!(ByteSize(bytes).to_string(), s);
+            assert_eq!(s.parse(), Ok(ByteSize(bytes)));
+        }
+    }
+
+    #[test]
+    fn percentages_keep_two_decimal_places() {
+        let percent = |s: &str| s.parse::<Percent>().map(|p| p.hundredths);
+        assert_eq!(percent("50%"), Ok(5000));
+        assert_eq!(percent("150%"), Ok(15000));
+        assert_eq!(percent("12.5%"), Ok(1250));
+        assert_eq!(percent("0.05%"), Ok(5));
+        assert_eq!("12.5%".parse::<Percent>().unwrap().as_fraction(), 0.125);
+        assert!(percent("50").is_err());
+        assert!(percent("%").is_err());
+        assert!(percent("1.234%").is_err());
+        for s in ["50%", "12.5%", "0.05%"] {
+            assert_eq!(s.parse::<Percent>().unwrap().to_string(), s);
+        }
+    }
+
+    #[test]
+    fn memory_limits_take_sizes_percentages_and_infinity() {
+        assert_eq!("1G".parse(), Ok(MemoryLimit::Bytes(ByteSize(1 << 30))));
+        assert_eq!("80%".parse(), Ok(MemoryLimit::Percent(Percent { hundredths: 8000 })));
+        assert_eq!("infinity".parse(), Ok(MemoryLimit::Infinity));
+        assert_eq!("lots".parse::<MemoryLimit>(), Err("`lots` is not a size, a percentage or infinity".to_string()));
+        assert!("80.123%".parse::<MemoryLimit>().is_err());
+        assert_eq!(serde_json::to_string(&MemoryLimit::Bytes(ByteSize(512 << 20))).unwrap(), "\"512M\"");
+    }
+
+    #[test]
+    fn count_limits_take_plain_numbers() {
+        assert_eq!("1024".parse::<RLimit>(), Ok(RLimit { soft: RLimitValue::Value(1024), hard: RLimitValue::Value(1024) }));
+        assert!("2K".parse::<RLimit>().is_err());
+        assert!(RLimit::parse_for("LimitNOFILE", "2K").is_err());
+        assert_eq!(
+            RLimit::parse_for("LimitNPROC", "100:infinity"),
+            Ok(RLimit { soft: RLimitValue::Value(100), hard: RLimitValue::Infinity }),
+        );
+    }
+
+    #[test]
+    fn size_limits_take_size_suffixes() {
+        assert_eq!(
+            RLimit::parse_for("LimitCORE", "2K"),
+            Ok(RLimit { soft: RLimitValue::Value(2048), hard: RLimitValue::Value(2048) }),
+        );
+        assert!(RLimit::parse_for("LimitMEMLOCK", "2M:1M").is_err());
+    }
+}